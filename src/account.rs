@@ -1,29 +1,63 @@
 use anyhow::Result;
 use bson::oid::ObjectId;
-use futures::TryStreamExt;
-use mongodb::{ClientSession, Database};
+use mongodb::ClientSession;
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, sync::Arc};
 use tokio::sync::Mutex;
 
-use crate::db_util;
+use crate::{db_util, namespace::Namespace, owner::Owner, versioned::Versioned};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AccountType {
+    Individual,
+    Joint,
+    TraditionalIra,
+    RothIra,
+    Corporate,
+}
+
+impl AccountType {
+    /// The tax treatment of accounts of this type unless stated otherwise.
+    pub fn default_tax_treatment(&self) -> TaxTreatment {
+        match self {
+            AccountType::TraditionalIra => TaxTreatment::TaxDeferred,
+            AccountType::RothIra => TaxTreatment::TaxExempt,
+            AccountType::Individual | AccountType::Joint | AccountType::Corporate => {
+                TaxTreatment::Taxable
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TaxTreatment {
+    Taxable,
+    TaxDeferred,
+    TaxExempt,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct BrokerageAccount {
     _id: ObjectId,
     brokerage_id: String,
     account_id: String,
+    owner_id: Option<ObjectId>,
+    account_type: Option<AccountType>,
+    tax_treatment: Option<TaxTreatment>,
+    display_name: Option<String>,
+    opened_timestamp_ms: Option<i64>,
+    closed_timestamp_ms: Option<i64>,
 }
 
 impl BrokerageAccount {
     pub const COLLECTION_NAME: &'static str = "brokerage_accounts";
 
     pub fn new(brokerage_id: &str, account_id: &str) -> Self {
-        Self {
-            _id: ObjectId::new(),
-            brokerage_id: brokerage_id.to_owned(),
-            account_id: account_id.to_owned(),
-        }
+        Self::builder(brokerage_id, account_id).build()
+    }
+
+    pub fn builder(brokerage_id: &str, account_id: &str) -> Builder {
+        Builder::new(brokerage_id, account_id)
     }
 
     pub fn id(&self) -> ObjectId {
@@ -38,45 +72,212 @@ impl BrokerageAccount {
         &self.account_id
     }
 
+    pub fn owner_id(&self) -> Option<ObjectId> {
+        self.owner_id
+    }
+
+    pub fn account_type(&self) -> Option<&AccountType> {
+        self.account_type.as_ref()
+    }
+
+    pub fn tax_treatment(&self) -> Option<&TaxTreatment> {
+        self.tax_treatment.as_ref()
+    }
+
+    /// True unless the account is known to be tax-deferred or tax-exempt,
+    /// either explicitly or from its account type.
+    pub fn is_taxable(&self) -> bool {
+        let treatment = self.tax_treatment.clone().or_else(|| {
+            self.account_type
+                .as_ref()
+                .map(AccountType::default_tax_treatment)
+        });
+        matches!(treatment, None | Some(TaxTreatment::Taxable))
+    }
+
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+
+    pub fn opened_timestamp_ms(&self) -> Option<i64> {
+        self.opened_timestamp_ms
+    }
+
+    pub fn closed_timestamp_ms(&self) -> Option<i64> {
+        self.closed_timestamp_ms
+    }
+
     pub async fn insert(
         &self,
-        db: &Database,
+        db: &impl Namespace,
         session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<()> {
         // Option<&mut ClientSession>
         db_util::insert(self, db, Self::COLLECTION_NAME, session).await
     }
 
-    pub async fn find(db: &Database) -> Result<Vec<Self>> {
-        Ok(db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find(bson::doc! {})
-            .await?
-            .try_collect()
-            .await?)
+    /// Assigns the account to an owner, or detaches it with `None`.
+    pub async fn assign_owner(
+        &mut self,
+        db: &impl Namespace,
+        owner_id: Option<ObjectId>,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::update_one::<Self>(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"_id": self._id},
+            bson::doc! {"$set": {"owner_id": owner_id}},
+            session,
+        )
+        .await?;
+        self.set_owner_id(owner_id);
+
+        Ok(())
+    }
+
+    pub(crate) fn set_owner_id(&mut self, owner_id: Option<ObjectId>) {
+        self.owner_id = owner_id;
+    }
+
+    pub async fn find(
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(db, Self::COLLECTION_NAME, bson::doc! {}, None, session).await
     }
 
     pub async fn find_by_brokerage_and_account_id(
-        db: &Database,
+        db: &impl Namespace,
         brokerage_id: &str,
         account_id: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<Option<Self>> {
-        let result = db
-            .collection::<BrokerageAccount>(BrokerageAccount::COLLECTION_NAME)
-            .find_one(bson::doc! {
+        db_util::find_one(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
             "brokerage_id": brokerage_id,
-            "account_id": account_id})
-            .await?;
+            "account_id": account_id},
+            session,
+        )
+        .await
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    pub async fn find_by_owner_id(
+        db: &impl Namespace,
+        owner_id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"owner_id": owner_id},
+            None,
+            session,
+        )
+        .await
+    }
+
+    pub async fn owner(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Owner>> {
+        match self.owner_id {
+            Some(owner_id) => Owner::find_by_id(db, owner_id, session).await,
+            None => Ok(None),
+        }
+    }
+}
+
+impl Versioned for BrokerageAccount {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+pub struct Builder {
+    _id: ObjectId,
+    brokerage_id: String,
+    account_id: String,
+    owner_id: Option<ObjectId>,
+    account_type: Option<AccountType>,
+    tax_treatment: Option<TaxTreatment>,
+    display_name: Option<String>,
+    opened_timestamp_ms: Option<i64>,
+    closed_timestamp_ms: Option<i64>,
+}
+
+impl Builder {
+    fn new(brokerage_id: &str, account_id: &str) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            brokerage_id: brokerage_id.to_owned(),
+            account_id: account_id.to_owned(),
+            owner_id: None,
+            account_type: None,
+            tax_treatment: None,
+            display_name: None,
+            opened_timestamp_ms: None,
+            closed_timestamp_ms: None,
+        }
+    }
 
-        Ok(result)
+    pub fn owner_id(mut self, owner_id: ObjectId) -> Self {
+        self.owner_id = Some(owner_id);
+        self
     }
 
-    pub async fn find_by_id(db: &Database, id: ObjectId) -> Result<Option<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(bson::doc! {"_id": id})
-            .await?;
+    pub fn account_type(mut self, account_type: AccountType) -> Self {
+        self.account_type = Some(account_type);
+        self
+    }
 
-        Ok(result)
+    /// Defaults to the account type's
+    /// [`default_tax_treatment`](AccountType::default_tax_treatment).
+    pub fn tax_treatment(mut self, tax_treatment: TaxTreatment) -> Self {
+        self.tax_treatment = Some(tax_treatment);
+        self
+    }
+
+    pub fn display_name(mut self, display_name: &str) -> Self {
+        self.display_name = Some(display_name.to_owned());
+        self
+    }
+
+    pub fn opened_timestamp_ms(mut self, opened_timestamp_ms: i64) -> Self {
+        self.opened_timestamp_ms = Some(opened_timestamp_ms);
+        self
+    }
+
+    pub fn closed_timestamp_ms(mut self, closed_timestamp_ms: i64) -> Self {
+        self.closed_timestamp_ms = Some(closed_timestamp_ms);
+        self
+    }
+
+    pub fn build(self) -> BrokerageAccount {
+        let tax_treatment = self.tax_treatment.or_else(|| {
+            self.account_type
+                .as_ref()
+                .map(AccountType::default_tax_treatment)
+        });
+        BrokerageAccount {
+            _id: self._id,
+            brokerage_id: self.brokerage_id,
+            account_id: self.account_id,
+            owner_id: self.owner_id,
+            account_type: self.account_type,
+            tax_treatment,
+            display_name: self.display_name,
+            opened_timestamp_ms: self.opened_timestamp_ms,
+            closed_timestamp_ms: self.closed_timestamp_ms,
+        }
     }
 }
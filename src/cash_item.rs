@@ -0,0 +1,253 @@
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    db_util,
+    namespace::Namespace,
+    subscription::Watched,
+    versioned::Versioned,
+    withholding::{ReclaimStatus, Withholding},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CashItemKind {
+    /// Stock loan fee charged for borrowing shares sold short.
+    BorrowFee,
+    /// Interest charged on a short position, e.g. on the short sale proceeds.
+    ShortInterest,
+    /// Interest credited on cash or fixed income.
+    Interest,
+}
+
+/// A cash movement in a brokerage account that is not a trade, such as a
+/// charge for holding a short position or interest received.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CashItem {
+    _id: ObjectId,
+    brokerage_account_id: ObjectId,
+    /// The security the item relates to, if any.
+    security_id: Option<ObjectId>,
+    kind: CashItemKind,
+    /// Positive when credited to the account, negative when charged. Net of
+    /// any withholding.
+    amount: f64,
+    timestamp_ms: i64,
+    description: Option<String>,
+    /// Tax withheld at source by a foreign country.
+    withholding: Option<Withholding>,
+}
+
+impl CashItem {
+    pub const COLLECTION_NAME: &'static str = "cash_items";
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn security_id(&self) -> Option<ObjectId> {
+        self.security_id
+    }
+
+    pub fn kind(&self) -> CashItemKind {
+        self.kind
+    }
+
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+
+    pub fn timestamp_ms(&self) -> i64 {
+        self.timestamp_ms
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn withholding(&self) -> Option<&Withholding> {
+        self.withholding.as_ref()
+    }
+
+    /// Amount before withholding.
+    pub fn gross_amount(&self) -> f64 {
+        self.amount + self.withholding.as_ref().map_or(0.0, |w| w.amount)
+    }
+
+    /// Sum of the amounts of the items of `kind`.
+    pub fn total(items: &[CashItem], kind: CashItemKind) -> f64 {
+        items
+            .iter()
+            .filter(|item| item.kind == kind)
+            .map(CashItem::amount)
+            .sum()
+    }
+
+    pub async fn insert(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::insert(self, db, Self::COLLECTION_NAME, session).await
+    }
+
+    /// Updates the status of the reclaim of the withholding.
+    /// Fails if the status does not fit the reclaimable amount.
+    pub async fn update_reclaim_status(
+        &mut self,
+        db: &impl Namespace,
+        status: ReclaimStatus,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        let Some(withholding) = self.withholding.as_mut() else {
+            bail!("cash item {} has no withholding", self._id);
+        };
+        withholding.check_reclaim_status(status)?;
+        db_util::update_one::<Self>(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"_id": self._id},
+            bson::doc! { "$set": { "withholding.reclaim_status": bson::to_bson(&status)? } },
+            session,
+        )
+        .await?;
+        withholding.reclaim_status = status;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    /// Returns the account's cash items within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+}
+
+impl Versioned for CashItem {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for CashItem {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+
+    fn account_field() -> Option<&'static str> {
+        Some("brokerage_account_id")
+    }
+}
+
+pub struct Builder {
+    _id: ObjectId,
+    brokerage_account_id: Option<ObjectId>,
+    security_id: Option<ObjectId>,
+    kind: Option<CashItemKind>,
+    amount: Option<f64>,
+    timestamp_ms: Option<i64>,
+    description: Option<String>,
+    withholding: Option<Withholding>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            _id: ObjectId::new(),
+            brokerage_account_id: None,
+            security_id: None,
+            kind: None,
+            amount: None,
+            timestamp_ms: None,
+            description: None,
+            withholding: None,
+        }
+    }
+
+    pub fn brokerage_account_id(mut self, id: ObjectId) -> Self {
+        self.brokerage_account_id = Some(id);
+        self
+    }
+
+    pub fn security_id(mut self, id: ObjectId) -> Self {
+        self.security_id = Some(id);
+        self
+    }
+
+    pub fn kind(mut self, kind: CashItemKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn amount(mut self, amount: f64) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_owned());
+        self
+    }
+
+    /// The amount is net of the withholding.
+    pub fn withholding(mut self, withholding: Withholding) -> Self {
+        self.withholding = Some(withholding);
+        self
+    }
+
+    pub fn build(self) -> Result<CashItem> {
+        if let Some(withholding) = &self.withholding {
+            withholding.check()?;
+        }
+
+        Ok(CashItem {
+            _id: self._id,
+            brokerage_account_id: self.brokerage_account_id.unwrap(),
+            security_id: self.security_id,
+            kind: self.kind.unwrap(),
+            amount: self.amount.unwrap(),
+            timestamp_ms: self.timestamp_ms.unwrap(),
+            description: self.description,
+            withholding: self.withholding,
+        })
+    }
+}
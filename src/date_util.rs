@@ -0,0 +1,45 @@
+pub const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// Converts a UTC timestamp to a proleptic Gregorian `(year, month, day)`.
+pub fn civil_from_timestamp_ms(timestamp_ms: i64) -> (i32, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = timestamp_ms.div_euclid(MS_PER_DAY) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+    (year, month, day)
+}
+
+/// The UTC midnight starting the day of `timestamp_ms`.
+pub fn start_of_day_ms(timestamp_ms: i64) -> i64 {
+    timestamp_ms.div_euclid(MS_PER_DAY) * MS_PER_DAY
+}
+
+/// The UTC hour of the day of `timestamp_ms`, from 0 to 23.
+pub fn hour_of_day(timestamp_ms: i64) -> u32 {
+    (timestamp_ms.rem_euclid(MS_PER_DAY) / (60 * 60 * 1000)) as u32
+}
+
+/// True if a position held from `acquired_ms` to `disposed_ms` was held for
+/// more than one year, comparing UTC calendar dates.
+pub fn is_long_term(acquired_ms: i64, disposed_ms: i64) -> bool {
+    let (ay, am, ad) = civil_from_timestamp_ms(acquired_ms);
+    let disposed = civil_from_timestamp_ms(disposed_ms);
+    (ay + 1, am, ad) < disposed
+}
+
+/// The UTC midnight starting January 1 of `year`.
+pub fn start_of_year_ms(year: i32) -> i64 {
+    // Howard Hinnant's days-from-civil algorithm, for March 1 of the previous
+    // year plus the 306 days to January 1.
+    let y = i64::from(year) - 1;
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + 306;
+    (era * 146_097 + doe - 719_468) * MS_PER_DAY
+}
@@ -0,0 +1,1179 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow, bail};
+use bson::oid::ObjectId;
+use mongodb::{
+    Client, ClientSession, Database,
+    error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+    options::{DatabaseOptions, ReadConcern, SessionOptions, WriteConcern},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    account::BrokerageAccount,
+    cash_item::CashItem,
+    dividend::{self, Dividend, DividendIncomeReport},
+    eod_summary::EODSummary,
+    namespace::Namespace,
+    option_event::{OptionEvent, OptionEventKind},
+    order::{Order, OrderFill, OrderStatus},
+    owner::Owner,
+    security::Security,
+    store::MongoStore,
+    tag::{ExecutionQuery, Tag, TagPnl},
+    trade_execution::TradeExecution,
+    withholding::{ForeignWithholdingReport, ReclaimStatus},
+};
+
+/// How strictly references between entities are checked on insert.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntegrityMode {
+    /// Insert documents as given, relying only on the unique indexes.
+    #[default]
+    Unchecked,
+    /// Verify that referenced owners, accounts, securities, orders, trade
+    /// executions and tags exist before inserting a document that points at
+    /// them.
+    Strict,
+}
+
+/// How long [`BrokerageDb::with_transaction`] keeps retrying, matching the
+/// MongoDB drivers' convenient transaction API.
+const TRANSACTION_RETRY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A handle to an initialized brokerage database.
+///
+/// Owns the MongoDB client and database, runs the migrations when opened, and
+/// exposes a repository per entity type. Handles with different collection
+/// prefixes are isolated from each other within the same database.
+#[derive(Clone, Debug)]
+pub struct BrokerageDb {
+    client: Client,
+    db: Database,
+    collection_prefix: String,
+    integrity_mode: IntegrityMode,
+}
+
+impl BrokerageDb {
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn integrity_mode(&self) -> IntegrityMode {
+        self.integrity_mode
+    }
+
+    /// The database as a [`crate::store::BrokerageStore`].
+    pub fn store(&self) -> MongoStore {
+        MongoStore::new(self.db.clone()).with_collection_prefix(&self.collection_prefix)
+    }
+
+    pub fn owners(&self) -> OwnerRepository<'_> {
+        OwnerRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn accounts(&self) -> AccountRepository<'_> {
+        AccountRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn securities(&self) -> SecurityRepository<'_> {
+        SecurityRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn trade_executions(&self) -> TradeExecutionRepository<'_> {
+        TradeExecutionRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn eod_summaries(&self) -> EODSummaryRepository<'_> {
+        EODSummaryRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn orders(&self) -> OrderRepository<'_> {
+        OrderRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn tags(&self) -> TagRepository<'_> {
+        TagRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn cash_items(&self) -> CashItemRepository<'_> {
+        CashItemRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn option_events(&self) -> OptionEventRepository<'_> {
+        OptionEventRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    pub fn dividends(&self) -> DividendRepository<'_> {
+        DividendRepository {
+            bdb: self,
+            session: None,
+        }
+    }
+
+    /// Starts a causally consistent session.
+    ///
+    /// Pass it to entity methods or bind repositories to it with
+    /// `with_session` so that each read observes the session's earlier
+    /// writes, even when served by a secondary.
+    pub async fn start_session(&self) -> Result<Arc<Mutex<ClientSession>>> {
+        let session = self
+            .client
+            .start_session()
+            .with_options(SessionOptions::builder().causal_consistency(true).build())
+            .await?;
+        Ok(Arc::new(Mutex::new(session)))
+    }
+
+    /// Runs `f` in a transaction and commits it.
+    ///
+    /// The whole transaction is retried when an operation fails with a
+    /// `TransientTransactionError`, and the commit alone is retried on an
+    /// `UnknownTransactionCommitResult`, until two minutes have passed. Any
+    /// other error aborts the transaction and is returned. Since `f` may run
+    /// more than once it should not have side effects outside the database.
+    pub async fn with_transaction<F, Fut, T>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut(Transaction) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let session = self.start_session().await?;
+        let started = Instant::now();
+
+        'transaction: loop {
+            session.lock().await.start_transaction().await?;
+
+            let value = match f(Transaction {
+                bdb: self.clone(),
+                session: session.clone(),
+            })
+            .await
+            {
+                Ok(value) => value,
+                Err(error) => {
+                    // Best effort, the server aborts it on its own eventually.
+                    let _ = session.lock().await.abort_transaction().await;
+                    if has_label(&error, TRANSIENT_TRANSACTION_ERROR)
+                        && started.elapsed() < TRANSACTION_RETRY_TIMEOUT
+                    {
+                        tracing::warn!("retrying transaction after {:#}", error);
+                        continue 'transaction;
+                    }
+                    return Err(error);
+                }
+            };
+
+            loop {
+                let error = match session.lock().await.commit_transaction().await {
+                    Ok(()) => return Ok(value),
+                    Err(error) => error,
+                };
+                if started.elapsed() >= TRANSACTION_RETRY_TIMEOUT {
+                    return Err(error.into());
+                }
+                if error.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT) {
+                    tracing::warn!("retrying commit after {}", error);
+                    continue;
+                }
+                if error.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+                    tracing::warn!("retrying transaction after {}", error);
+                    continue 'transaction;
+                }
+                return Err(error.into());
+            }
+        }
+    }
+
+    /// Removes all collections and indexes created by the migrations.
+    pub async fn remove_data(&self) -> Result<()> {
+        crate::remove_data(self).await
+    }
+
+    async fn check_account_exists(
+        &self,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if self.integrity_mode == IntegrityMode::Strict
+            && BrokerageAccount::find_by_id(self, id, session)
+                .await?
+                .is_none()
+        {
+            bail!("brokerage account {} does not exist", id);
+        }
+        Ok(())
+    }
+
+    async fn check_security_exists(
+        &self,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if self.integrity_mode == IntegrityMode::Strict
+            && Security::find_by_id(self, id, session).await?.is_none()
+        {
+            bail!("security {} does not exist", id);
+        }
+        Ok(())
+    }
+
+    /// Checks that the order filled by the execution, if any, exists and is
+    /// for the execution's account and security.
+    async fn check_execution_order(
+        &self,
+        execution: &TradeExecution,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if let Some(id) = execution.order_id()
+            && self.integrity_mode == IntegrityMode::Strict
+        {
+            match Order::find_by_id(self, id, session).await? {
+                Some(order)
+                    if order.brokerage_account_id() != execution.brokerage_account_id()
+                        || order.security_id() != execution.security_id() =>
+                {
+                    bail!(
+                        "order {} is not for the account and security of trade execution {}",
+                        id,
+                        execution.id()
+                    )
+                }
+                Some(_) => {}
+                None => bail!("order {} does not exist", id),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the purchase reinvesting the dividend, if any, exists and
+    /// buys the dividend's security in its account.
+    async fn check_dividend_reinvestment(
+        &self,
+        dividend: &Dividend,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if let Some(id) = dividend.reinvestment_execution_id()
+            && self.integrity_mode == IntegrityMode::Strict
+        {
+            match TradeExecution::find_by_id(self, id, session).await? {
+                Some(execution) => dividend::check_reinvestment(dividend, &execution)?,
+                None => bail!("trade execution {} does not exist", id),
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_tags_exist(
+        &self,
+        ids: &[ObjectId],
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if self.integrity_mode == IntegrityMode::Strict {
+            for id in ids {
+                if Tag::find_by_id(self, *id, session.clone()).await?.is_none() {
+                    bail!("tag {} does not exist", id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn check_owner_exists(
+        &self,
+        id: Option<ObjectId>,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if let Some(id) = id
+            && self.integrity_mode == IntegrityMode::Strict
+            && Owner::find_by_id(self, id, session).await?.is_none()
+        {
+            bail!("owner {} does not exist", id);
+        }
+        Ok(())
+    }
+}
+
+pub struct Builder {
+    uri: Option<String>,
+    client: Option<Client>,
+    database_name: Option<String>,
+    collection_prefix: String,
+    read_concern: Option<ReadConcern>,
+    write_concern: Option<WriteConcern>,
+    integrity_mode: IntegrityMode,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            uri: None,
+            client: None,
+            database_name: None,
+            collection_prefix: String::new(),
+            read_concern: None,
+            write_concern: None,
+            integrity_mode: IntegrityMode::default(),
+        }
+    }
+
+    /// Connects with a MongoDB connection string. Ignored if a client is given.
+    pub fn uri(mut self, uri: &str) -> Self {
+        self.uri = Some(uri.to_owned());
+        self
+    }
+
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn database_name(mut self, database_name: &str) -> Self {
+        self.database_name = Some(database_name.to_owned());
+        self
+    }
+
+    /// Prepended to every collection name, e.g. `"tenant_a."`.
+    pub fn collection_prefix(mut self, collection_prefix: &str) -> Self {
+        self.collection_prefix = collection_prefix.to_owned();
+        self
+    }
+
+    pub fn read_concern(mut self, read_concern: ReadConcern) -> Self {
+        self.read_concern = Some(read_concern);
+        self
+    }
+
+    pub fn write_concern(mut self, write_concern: WriteConcern) -> Self {
+        self.write_concern = Some(write_concern);
+        self
+    }
+
+    pub fn integrity_mode(mut self, integrity_mode: IntegrityMode) -> Self {
+        self.integrity_mode = integrity_mode;
+        self
+    }
+
+    /// Connects, applies pending migrations and returns the handle.
+    pub async fn open(self) -> Result<BrokerageDb> {
+        let client = match (self.client, self.uri) {
+            (Some(client), _) => client,
+            (None, Some(uri)) => Client::with_uri_str(uri).await?,
+            (None, None) => bail!("either a client or a connection string is required"),
+        };
+
+        let database_name = self
+            .database_name
+            .or_else(|| client.default_database().map(|db| db.name().to_owned()))
+            .ok_or_else(|| anyhow!("no database name given or set in the connection string"))?;
+
+        let db = client.database_with_options(
+            &database_name,
+            DatabaseOptions::builder()
+                .read_concern(self.read_concern)
+                .write_concern(self.write_concern)
+                .build(),
+        );
+
+        let bdb = BrokerageDb {
+            client,
+            db,
+            collection_prefix: self.collection_prefix,
+            integrity_mode: self.integrity_mode,
+        };
+        crate::initialize(&bdb).await?;
+
+        Ok(bdb)
+    }
+}
+
+pub struct OwnerRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl OwnerRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, owner: &Owner) -> Result<()> {
+        owner.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find(&self) -> Result<Vec<Owner>> {
+        Owner::find(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<Owner>> {
+        Owner::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+}
+
+pub struct AccountRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl AccountRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, account: &BrokerageAccount) -> Result<()> {
+        self.bdb
+            .check_owner_exists(account.owner_id(), self.session.clone())
+            .await?;
+        account.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn assign_owner(
+        &self,
+        account: &mut BrokerageAccount,
+        owner_id: Option<ObjectId>,
+    ) -> Result<()> {
+        self.bdb
+            .check_owner_exists(owner_id, self.session.clone())
+            .await?;
+        account
+            .assign_owner(self.bdb, owner_id, self.session.clone())
+            .await
+    }
+
+    pub async fn find(&self) -> Result<Vec<BrokerageAccount>> {
+        BrokerageAccount::find(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<BrokerageAccount>> {
+        BrokerageAccount::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_brokerage_and_account_id(
+        &self,
+        brokerage_id: &str,
+        account_id: &str,
+    ) -> Result<Option<BrokerageAccount>> {
+        BrokerageAccount::find_by_brokerage_and_account_id(
+            self.bdb,
+            brokerage_id,
+            account_id,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_owner_id(&self, owner_id: ObjectId) -> Result<Vec<BrokerageAccount>> {
+        BrokerageAccount::find_by_owner_id(self.bdb, owner_id, self.session.clone()).await
+    }
+}
+
+pub struct SecurityRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl SecurityRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, security: &Security) -> Result<()> {
+        security.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<Security>> {
+        Security::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_ticker_and_exchange(
+        &self,
+        ticker: &str,
+        listing_exchange: &str,
+    ) -> Result<Option<Security>> {
+        Security::find_by_ticker_and_exchange(
+            self.bdb,
+            ticker,
+            listing_exchange,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_conid(&self, ibkr_conid: u32) -> Result<Option<Security>> {
+        Security::find_by_conid(self.bdb, ibkr_conid, self.session.clone()).await
+    }
+
+    pub async fn find_by_ticker(&self, ticker: &str) -> Result<Vec<Security>> {
+        Security::find_by_ticker(self.bdb, ticker, self.session.clone()).await
+    }
+}
+
+pub struct TradeExecutionRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl TradeExecutionRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, trade_execution: &TradeExecution) -> Result<()> {
+        self.bdb
+            .check_account_exists(trade_execution.brokerage_account_id(), self.session.clone())
+            .await?;
+        self.bdb
+            .check_security_exists(trade_execution.security_id(), self.session.clone())
+            .await?;
+        self.bdb
+            .check_execution_order(trade_execution, self.session.clone())
+            .await?;
+        self.bdb
+            .check_tags_exist(trade_execution.tag_ids(), self.session.clone())
+            .await?;
+        trade_execution.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<TradeExecution>> {
+        TradeExecution::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_brokerage_execution_id(
+        &self,
+        execution_id: &str,
+    ) -> Result<Option<TradeExecution>> {
+        TradeExecution::find_by_brokerage_execution_id(self.bdb, execution_id, self.session.clone())
+            .await
+    }
+
+    pub async fn find_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_account_id_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_account_id_and_venue(
+        &self,
+        brokerage_account_id: ObjectId,
+        venue: &str,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_account_id_and_venue(
+            self.bdb,
+            brokerage_account_id,
+            venue,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_account_id_and_strategy_tag(
+        &self,
+        brokerage_account_id: ObjectId,
+        strategy_tag: &str,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_account_id_and_strategy_tag(
+            self.bdb,
+            brokerage_account_id,
+            strategy_tag,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_account_id_and_order_reference(
+        &self,
+        brokerage_account_id: ObjectId,
+        order_reference: &str,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_account_id_and_order_reference(
+            self.bdb,
+            brokerage_account_id,
+            order_reference,
+            self.session.clone(),
+        )
+        .await
+    }
+}
+
+pub struct EODSummaryRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl EODSummaryRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, eod_summary: &EODSummary) -> Result<()> {
+        self.bdb
+            .check_account_exists(eod_summary.brokerage_account_id(), self.session.clone())
+            .await?;
+        eod_summary.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<EODSummary>> {
+        EODSummary::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_account_id(
+        &self,
+        brokerage_account_id: ObjectId,
+    ) -> Result<Vec<EODSummary>> {
+        EODSummary::find_by_account_id(self.bdb, brokerage_account_id, self.session.clone()).await
+    }
+
+    pub async fn find_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<EODSummary>> {
+        EODSummary::find_by_account_id_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+}
+
+pub struct OrderRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl OrderRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, order: &Order) -> Result<()> {
+        self.bdb
+            .check_account_exists(order.brokerage_account_id(), self.session.clone())
+            .await?;
+        self.bdb
+            .check_security_exists(order.security_id(), self.session.clone())
+            .await?;
+        order.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn update_status(
+        &self,
+        order: &mut Order,
+        status: OrderStatus,
+        timestamp_ms: i64,
+    ) -> Result<()> {
+        order
+            .update_status(self.bdb, status, timestamp_ms, self.session.clone())
+            .await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<Order>> {
+        Order::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_brokerage_order_id(
+        &self,
+        brokerage_account_id: ObjectId,
+        brokerage_order_id: &str,
+    ) -> Result<Option<Order>> {
+        Order::find_by_brokerage_order_id(
+            self.bdb,
+            brokerage_account_id,
+            brokerage_order_id,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<Order>> {
+        Order::find_by_account_id_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn fills_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<OrderFill>> {
+        OrderFill::for_account_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+}
+
+pub struct TagRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl TagRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, tag: &Tag) -> Result<()> {
+        tag.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find(&self) -> Result<Vec<Tag>> {
+        Tag::find(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<Tag>> {
+        Tag::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_name(&self, name: &str) -> Result<Option<Tag>> {
+        Tag::find_by_name(self.bdb, name, self.session.clone()).await
+    }
+
+    pub async fn tag_executions(&self, tag: &Tag, query: &ExecutionQuery) -> Result<u64> {
+        self.bdb
+            .check_tags_exist(&[tag.id()], self.session.clone())
+            .await?;
+        tag.tag_executions(self.bdb, query, self.session.clone())
+            .await
+    }
+
+    pub async fn untag_executions(&self, tag: &Tag, query: &ExecutionQuery) -> Result<u64> {
+        tag.untag_executions(self.bdb, query, self.session.clone())
+            .await
+    }
+
+    pub async fn pnl_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<BTreeMap<ObjectId, TagPnl>> {
+        TagPnl::for_account_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+}
+
+pub struct CashItemRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl CashItemRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, cash_item: &CashItem) -> Result<()> {
+        self.bdb
+            .check_account_exists(cash_item.brokerage_account_id(), self.session.clone())
+            .await?;
+        if let Some(security_id) = cash_item.security_id() {
+            self.bdb
+                .check_security_exists(security_id, self.session.clone())
+                .await?;
+        }
+        cash_item.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn update_reclaim_status(
+        &self,
+        cash_item: &mut CashItem,
+        status: ReclaimStatus,
+    ) -> Result<()> {
+        cash_item
+            .update_reclaim_status(self.bdb, status, self.session.clone())
+            .await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<CashItem>> {
+        CashItem::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<CashItem>> {
+        CashItem::find_by_account_id_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+}
+
+pub struct OptionEventRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl OptionEventRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Records the event and inserts the executions it implies; see
+    /// [`OptionEvent::record`].
+    pub async fn record(
+        &self,
+        brokerage_account_id: ObjectId,
+        option: &Security,
+        kind: OptionEventKind,
+        contracts: f64,
+        timestamp_ms: i64,
+    ) -> Result<(OptionEvent, Vec<TradeExecution>)> {
+        self.bdb
+            .check_account_exists(brokerage_account_id, self.session.clone())
+            .await?;
+        if let Some(contract) = option.option_contract() {
+            self.bdb
+                .check_security_exists(contract.underlying_id, self.session.clone())
+                .await?;
+        }
+        OptionEvent::record(
+            self.bdb,
+            brokerage_account_id,
+            option,
+            kind,
+            contracts,
+            timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<OptionEvent>> {
+        OptionEvent::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<OptionEvent>> {
+        OptionEvent::find_by_account_id_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+}
+
+pub struct DividendRepository<'a> {
+    bdb: &'a BrokerageDb,
+    session: Option<Arc<Mutex<ClientSession>>>,
+}
+
+impl DividendRepository<'_> {
+    /// Runs this repository's operations in `session`.
+    pub fn with_session(mut self, session: Arc<Mutex<ClientSession>>) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    pub async fn insert(&self, dividend: &Dividend) -> Result<()> {
+        self.bdb
+            .check_account_exists(dividend.brokerage_account_id(), self.session.clone())
+            .await?;
+        self.bdb
+            .check_security_exists(dividend.security_id(), self.session.clone())
+            .await?;
+        self.bdb
+            .check_dividend_reinvestment(dividend, self.session.clone())
+            .await?;
+        dividend.insert(self.bdb, self.session.clone()).await
+    }
+
+    pub async fn reinvest(
+        &self,
+        dividend: &mut Dividend,
+        execution: &TradeExecution,
+    ) -> Result<()> {
+        dividend
+            .reinvest(self.bdb, execution, self.session.clone())
+            .await
+    }
+
+    pub async fn update_reclaim_status(
+        &self,
+        dividend: &mut Dividend,
+        status: ReclaimStatus,
+    ) -> Result<()> {
+        dividend
+            .update_reclaim_status(self.bdb, status, self.session.clone())
+            .await
+    }
+
+    pub async fn find_by_id(&self, id: ObjectId) -> Result<Option<Dividend>> {
+        Dividend::find_by_id(self.bdb, id, self.session.clone()).await
+    }
+
+    pub async fn find_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<Dividend>> {
+        Dividend::find_by_account_id_and_range(
+            self.bdb,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    pub async fn find_by_security_id(&self, security_id: ObjectId) -> Result<Vec<Dividend>> {
+        Dividend::find_by_security_id(self.bdb, security_id, self.session.clone()).await
+    }
+
+    pub async fn income_report_for_owner(
+        &self,
+        owner_id: ObjectId,
+        tax_year: i32,
+    ) -> Result<DividendIncomeReport> {
+        DividendIncomeReport::for_owner(self.bdb, owner_id, tax_year, self.session.clone()).await
+    }
+
+    /// Foreign withholding on the dividends and interest of the given
+    /// accounts.
+    pub async fn foreign_withholding_report_for_accounts(
+        &self,
+        brokerage_account_ids: &[ObjectId],
+        tax_year: i32,
+    ) -> Result<ForeignWithholdingReport> {
+        ForeignWithholdingReport::for_accounts(
+            self.bdb,
+            brokerage_account_ids,
+            tax_year,
+            self.session.clone(),
+        )
+        .await
+    }
+
+    /// Foreign withholding on the dividends and interest of the owner's
+    /// taxable accounts.
+    pub async fn foreign_withholding_report_for_owner(
+        &self,
+        owner_id: ObjectId,
+        tax_year: i32,
+    ) -> Result<ForeignWithholdingReport> {
+        ForeignWithholdingReport::for_owner(self.bdb, owner_id, tax_year, self.session.clone())
+            .await
+    }
+}
+
+/// A transaction started by [`BrokerageDb::with_transaction`].
+///
+/// Operations through its repositories run in the transaction's session, so
+/// reads observe the transaction's own uncommitted writes.
+#[derive(Clone)]
+pub struct Transaction {
+    bdb: BrokerageDb,
+    session: Arc<Mutex<ClientSession>>,
+}
+
+impl Transaction {
+    /// The session to pass to entity methods called directly.
+    pub fn session(&self) -> Arc<Mutex<ClientSession>> {
+        self.session.clone()
+    }
+
+    pub fn owners(&self) -> OwnerRepository<'_> {
+        OwnerRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn accounts(&self) -> AccountRepository<'_> {
+        AccountRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn securities(&self) -> SecurityRepository<'_> {
+        SecurityRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn trade_executions(&self) -> TradeExecutionRepository<'_> {
+        TradeExecutionRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn eod_summaries(&self) -> EODSummaryRepository<'_> {
+        EODSummaryRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn orders(&self) -> OrderRepository<'_> {
+        OrderRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn tags(&self) -> TagRepository<'_> {
+        TagRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn cash_items(&self) -> CashItemRepository<'_> {
+        CashItemRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn option_events(&self) -> OptionEventRepository<'_> {
+        OptionEventRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+
+    pub fn dividends(&self) -> DividendRepository<'_> {
+        DividendRepository {
+            bdb: &self.bdb,
+            session: Some(self.session.clone()),
+        }
+    }
+}
+
+impl Namespace for BrokerageDb {
+    fn database(&self) -> &Database {
+        &self.db
+    }
+
+    fn collection_prefix(&self) -> &str {
+        &self.collection_prefix
+    }
+}
+
+impl Namespace for Transaction {
+    fn database(&self) -> &Database {
+        &self.bdb.db
+    }
+
+    fn collection_prefix(&self) -> &str {
+        &self.bdb.collection_prefix
+    }
+}
+
+fn has_label(error: &anyhow::Error, label: &str) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<mongodb::error::Error>())
+        .any(|error| error.contains_label(label))
+}
@@ -1,28 +1,32 @@
 use anyhow::Result;
-use mongodb::{ClientSession, Database};
-use serde::Serialize;
+use bson::Document;
+use futures::TryStreamExt;
+use mongodb::ClientSession;
+
+use crate::namespace::Namespace;
+use crate::versioned::{self, Versioned};
 use std::{any::type_name, fmt::Debug, sync::Arc};
 use tokio::sync::Mutex;
 
 pub async fn insert<T>(
     t: &T,
-    db: &Database,
+    db: &impl Namespace,
     collection_name: &str,
     session: Option<Arc<Mutex<ClientSession>>>,
 ) -> Result<()>
 where
-    T: Serialize + Send + Sync + Debug,
+    T: Versioned + Send + Sync + Debug,
 {
-    let collection = db.collection::<T>(collection_name);
+    let collection = db.collection::<Document>(collection_name);
+    let document = versioned::to_document(t)?;
 
-    let result = if session.is_some() {
-        let session_am = session.unwrap();
+    let result = if let Some(session_am) = session {
         collection
-            .insert_one(t)
+            .insert_one(document)
             .session(&mut *session_am.lock().await)
             .await?
     } else {
-        collection.insert_one(t).await?
+        collection.insert_one(document).await?
     };
 
     tracing::info!(
@@ -33,3 +37,112 @@ where
     );
     Ok(())
 }
+
+pub async fn find_one<T>(
+    db: &impl Namespace,
+    collection_name: &str,
+    filter: Document,
+    session: Option<Arc<Mutex<ClientSession>>>,
+) -> Result<Option<T>>
+where
+    T: Versioned + Send + Sync,
+{
+    let collection = db.collection::<Document>(collection_name);
+
+    let document = if let Some(session_am) = session {
+        collection
+            .find_one(filter)
+            .session(&mut *session_am.lock().await)
+            .await?
+    } else {
+        collection.find_one(filter).await?
+    };
+    document.map(versioned::from_document).transpose()
+}
+
+/// Collects every document matching `filter`, ordered by `sort` if given.
+pub async fn find<T>(
+    db: &impl Namespace,
+    collection_name: &str,
+    filter: Document,
+    sort: Option<Document>,
+    session: Option<Arc<Mutex<ClientSession>>>,
+) -> Result<Vec<T>>
+where
+    T: Versioned + Send + Sync,
+{
+    let collection = db.collection::<Document>(collection_name);
+
+    let documents: Vec<Document> = if let Some(session_am) = session {
+        let mut session = session_am.lock().await;
+        collection
+            .find(filter)
+            .sort(sort.unwrap_or_default())
+            .session(&mut *session)
+            .await?
+            .stream(&mut session)
+            .try_collect()
+            .await?
+    } else {
+        collection
+            .find(filter)
+            .sort(sort.unwrap_or_default())
+            .await?
+            .try_collect()
+            .await?
+    };
+    documents
+        .into_iter()
+        .map(versioned::from_document)
+        .collect()
+}
+
+pub async fn update_one<T>(
+    db: &impl Namespace,
+    collection_name: &str,
+    filter: Document,
+    update: Document,
+    session: Option<Arc<Mutex<ClientSession>>>,
+) -> Result<()>
+where
+    T: Send + Sync,
+{
+    let collection = db.collection::<T>(collection_name);
+
+    if let Some(session_am) = session {
+        collection
+            .update_one(filter, update)
+            .session(&mut *session_am.lock().await)
+            .await?;
+    } else {
+        collection.update_one(filter, update).await?;
+    }
+
+    Ok(())
+}
+
+/// Applies `update` to every document matching `filter` and returns the number
+/// of documents modified.
+pub async fn update_many<T>(
+    db: &impl Namespace,
+    collection_name: &str,
+    filter: Document,
+    update: Document,
+    session: Option<Arc<Mutex<ClientSession>>>,
+) -> Result<u64>
+where
+    T: Send + Sync,
+{
+    let collection = db.collection::<T>(collection_name);
+
+    let result = if let Some(session_am) = session {
+        collection
+            .update_many(filter, update)
+            .session(&mut *session_am.lock().await)
+            .await?
+    } else {
+        collection.update_many(filter, update).await?
+    };
+
+    Ok(result.modified_count)
+}
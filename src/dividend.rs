@@ -0,0 +1,514 @@
+//! Per-security dividends received by brokerage accounts.
+//!
+//! Unlike the account-level totals of an [`EODSummary`](crate::eod_summary::EODSummary),
+//! each [`Dividend`] records the dates, rate, withholding and tax classification
+//! of one distribution, and the purchase that reinvested it under a dividend
+//! reinvestment plan (DRIP).
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::{Result, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    account::BrokerageAccount,
+    date_util, db_util,
+    namespace::Namespace,
+    subscription::Watched,
+    trade_execution::{TradeExecution, TradeSide},
+    versioned::Versioned,
+    withholding::{ReclaimStatus, Withholding},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DividendClassification {
+    /// Taxed at long-term capital gains rates.
+    Qualified,
+    /// Taxed as ordinary income.
+    NonQualified,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Dividend {
+    _id: ObjectId,
+    brokerage_account_id: ObjectId,
+    security_id: ObjectId,
+    declaration_timestamp_ms: Option<i64>,
+    ex_timestamp_ms: i64,
+    record_timestamp_ms: Option<i64>,
+    pay_timestamp_ms: i64,
+    amount_per_share: f64,
+    /// Shares held on the record date.
+    quantity: f64,
+    /// Tax withheld at source, as a positive amount.
+    withholding_tax: f64,
+    /// The part of `withholding_tax` withheld by a foreign country.
+    foreign_withholding: Option<Withholding>,
+    classification: DividendClassification,
+    /// The purchase that reinvested the dividend, if any.
+    reinvestment_execution_id: Option<ObjectId>,
+}
+
+impl Dividend {
+    pub const COLLECTION_NAME: &'static str = "dividends";
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    pub fn declaration_timestamp_ms(&self) -> Option<i64> {
+        self.declaration_timestamp_ms
+    }
+
+    pub fn ex_timestamp_ms(&self) -> i64 {
+        self.ex_timestamp_ms
+    }
+
+    pub fn record_timestamp_ms(&self) -> Option<i64> {
+        self.record_timestamp_ms
+    }
+
+    pub fn pay_timestamp_ms(&self) -> i64 {
+        self.pay_timestamp_ms
+    }
+
+    pub fn amount_per_share(&self) -> f64 {
+        self.amount_per_share
+    }
+
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    pub fn withholding_tax(&self) -> f64 {
+        self.withholding_tax
+    }
+
+    pub fn foreign_withholding(&self) -> Option<&Withholding> {
+        self.foreign_withholding.as_ref()
+    }
+
+    pub fn classification(&self) -> DividendClassification {
+        self.classification
+    }
+
+    pub fn reinvestment_execution_id(&self) -> Option<ObjectId> {
+        self.reinvestment_execution_id
+    }
+
+    /// Amount before withholding.
+    pub fn gross_amount(&self) -> f64 {
+        self.amount_per_share * self.quantity
+    }
+
+    /// Amount credited to the account.
+    pub fn net_amount(&self) -> f64 {
+        self.gross_amount() - self.withholding_tax
+    }
+
+    pub async fn insert(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::insert(self, db, Self::COLLECTION_NAME, session).await
+    }
+
+    /// Links the purchase that reinvested the dividend, which must buy the
+    /// same security in the same account.
+    pub async fn reinvest(
+        &mut self,
+        db: &impl Namespace,
+        execution: &TradeExecution,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        check_reinvestment(self, execution)?;
+        db_util::update_one::<Self>(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"_id": self._id},
+            bson::doc! { "$set": { "reinvestment_execution_id": execution.id() } },
+            session,
+        )
+        .await?;
+        self.reinvestment_execution_id = Some(execution.id());
+
+        Ok(())
+    }
+
+    /// Updates the status of the reclaim of the foreign withholding.
+    /// Fails if the status does not fit the reclaimable amount.
+    pub async fn update_reclaim_status(
+        &mut self,
+        db: &impl Namespace,
+        status: ReclaimStatus,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        let Some(withholding) = self.foreign_withholding.as_mut() else {
+            bail!("dividend {} has no foreign withholding", self._id);
+        };
+        withholding.check_reclaim_status(status)?;
+        db_util::update_one::<Self>(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"_id": self._id},
+            bson::doc! { "$set": { "foreign_withholding.reclaim_status": bson::to_bson(&status)? } },
+            session,
+        )
+        .await?;
+        withholding.reclaim_status = status;
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    /// Returns the account's dividends paid within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "pay_timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "pay_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+
+    /// Returns the security's dividends across accounts, by ex-date.
+    pub async fn find_by_security_id(
+        db: &impl Namespace,
+        security_id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! { "security_id": security_id },
+            Some(bson::doc! { "ex_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+
+    pub async fn reinvestment(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<TradeExecution>> {
+        match self.reinvestment_execution_id {
+            Some(id) => TradeExecution::find_by_id(db, id, session).await,
+            None => Ok(None),
+        }
+    }
+}
+
+impl Versioned for Dividend {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for Dividend {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+
+    fn account_field() -> Option<&'static str> {
+        Some("brokerage_account_id")
+    }
+}
+
+pub(crate) fn check_reinvestment(dividend: &Dividend, execution: &TradeExecution) -> Result<()> {
+    if *execution.side() != TradeSide::Buy
+        || execution.brokerage_account_id() != dividend.brokerage_account_id
+        || execution.security_id() != dividend.security_id
+    {
+        bail!(
+            "execution {} does not buy the dividend's security in its account",
+            execution.brokerage_execution_id()
+        );
+    }
+    Ok(())
+}
+
+/// Dividend income totals; see [`DividendIncomeReport`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DividendTotals {
+    pub qualified: f64,
+    pub non_qualified: f64,
+    pub withholding_tax: f64,
+    /// Net amount reinvested.
+    pub reinvested: f64,
+}
+
+impl DividendTotals {
+    fn add(&mut self, dividend: &Dividend) {
+        match dividend.classification {
+            DividendClassification::Qualified => self.qualified += dividend.gross_amount(),
+            DividendClassification::NonQualified => self.non_qualified += dividend.gross_amount(),
+        }
+        self.withholding_tax += dividend.withholding_tax;
+        if dividend.reinvestment_execution_id.is_some() {
+            self.reinvested += dividend.net_amount();
+        }
+    }
+
+    /// Total dividends before withholding.
+    pub fn gross(&self) -> f64 {
+        self.qualified + self.non_qualified
+    }
+}
+
+/// Dividends paid in one tax year, in total and per security.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DividendIncomeReport {
+    tax_year: i32,
+    totals: DividendTotals,
+    by_security: BTreeMap<ObjectId, DividendTotals>,
+}
+
+impl DividendIncomeReport {
+    /// Builds the report from the dividends paid during `tax_year`.
+    pub fn from_dividends(tax_year: i32, dividends: &[Dividend]) -> Self {
+        let mut report = Self {
+            tax_year,
+            totals: DividendTotals::default(),
+            by_security: BTreeMap::new(),
+        };
+        for dividend in dividends {
+            let (year, _, _) = date_util::civil_from_timestamp_ms(dividend.pay_timestamp_ms);
+            if year != tax_year {
+                continue;
+            }
+            report.totals.add(dividend);
+            report
+                .by_security
+                .entry(dividend.security_id)
+                .or_default()
+                .add(dividend);
+        }
+        report
+    }
+
+    /// Reports the dividends paid to the given accounts in `tax_year`.
+    pub async fn for_accounts(
+        db: &impl Namespace,
+        brokerage_account_ids: &[ObjectId],
+        tax_year: i32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let start = date_util::start_of_year_ms(tax_year);
+        let end = date_util::start_of_year_ms(tax_year + 1) - 1;
+        let mut dividends = Vec::new();
+        for id in brokerage_account_ids {
+            dividends.extend(
+                Dividend::find_by_account_id_and_range(db, *id, start, end, session.clone())
+                    .await?,
+            );
+        }
+        Ok(Self::from_dividends(tax_year, &dividends))
+    }
+
+    /// Reports the dividends paid in `tax_year` to the owner's taxable
+    /// accounts.
+    pub async fn for_owner(
+        db: &impl Namespace,
+        owner_id: ObjectId,
+        tax_year: i32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let account_ids: Vec<ObjectId> =
+            BrokerageAccount::find_by_owner_id(db, owner_id, session.clone())
+                .await?
+                .iter()
+                .filter(|a| a.is_taxable())
+                .map(BrokerageAccount::id)
+                .collect();
+        Self::for_accounts(db, &account_ids, tax_year, session).await
+    }
+
+    pub fn tax_year(&self) -> i32 {
+        self.tax_year
+    }
+
+    pub fn totals(&self) -> DividendTotals {
+        self.totals
+    }
+
+    pub fn by_security(&self) -> &BTreeMap<ObjectId, DividendTotals> {
+        &self.by_security
+    }
+}
+
+pub struct Builder {
+    _id: ObjectId,
+    brokerage_account_id: Option<ObjectId>,
+    security_id: Option<ObjectId>,
+    declaration_timestamp_ms: Option<i64>,
+    ex_timestamp_ms: Option<i64>,
+    record_timestamp_ms: Option<i64>,
+    pay_timestamp_ms: Option<i64>,
+    amount_per_share: Option<f64>,
+    quantity: Option<f64>,
+    withholding_tax: Option<f64>,
+    foreign_withholding: Option<Withholding>,
+    classification: DividendClassification,
+    reinvestment_execution_id: Option<ObjectId>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            _id: ObjectId::new(),
+            brokerage_account_id: None,
+            security_id: None,
+            declaration_timestamp_ms: None,
+            ex_timestamp_ms: None,
+            record_timestamp_ms: None,
+            pay_timestamp_ms: None,
+            amount_per_share: None,
+            quantity: None,
+            withholding_tax: None,
+            foreign_withholding: None,
+            classification: DividendClassification::NonQualified,
+            reinvestment_execution_id: None,
+        }
+    }
+
+    pub fn brokerage_account_id(mut self, id: ObjectId) -> Self {
+        self.brokerage_account_id = Some(id);
+        self
+    }
+
+    pub fn security_id(mut self, id: ObjectId) -> Self {
+        self.security_id = Some(id);
+        self
+    }
+
+    pub fn declaration_timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.declaration_timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn ex_timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.ex_timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn record_timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.record_timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn pay_timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.pay_timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn amount_per_share(mut self, amount: f64) -> Self {
+        self.amount_per_share = Some(amount);
+        self
+    }
+
+    pub fn quantity(mut self, quantity: f64) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    /// Defaults to the foreign withholding, if any, and otherwise to zero.
+    pub fn withholding_tax(mut self, amount: f64) -> Self {
+        self.withholding_tax = Some(amount);
+        self
+    }
+
+    pub fn foreign_withholding(mut self, withholding: Withholding) -> Self {
+        self.foreign_withholding = Some(withholding);
+        self
+    }
+
+    /// Defaults to [`DividendClassification::NonQualified`].
+    pub fn classification(mut self, classification: DividendClassification) -> Self {
+        self.classification = classification;
+        self
+    }
+
+    /// The purchase that reinvested the dividend. Inserting through
+    /// [`DividendRepository`](crate::db::DividendRepository) in strict mode
+    /// checks it as [`Dividend::reinvest`] does.
+    pub fn reinvestment_execution_id(mut self, id: ObjectId) -> Self {
+        self.reinvestment_execution_id = Some(id);
+        self
+    }
+
+    pub fn build(self) -> Result<Dividend> {
+        let ex_timestamp_ms = self.ex_timestamp_ms.unwrap();
+        let pay_timestamp_ms = self.pay_timestamp_ms.unwrap();
+        if pay_timestamp_ms < ex_timestamp_ms {
+            bail!("dividend pay date precedes its ex-date");
+        }
+        let foreign_amount = self.foreign_withholding.as_ref().map_or(0.0, |w| w.amount);
+        let withholding_tax = self.withholding_tax.unwrap_or(foreign_amount);
+        if withholding_tax < 0.0 {
+            bail!(
+                "withholding tax must not be negative, got {}",
+                withholding_tax
+            );
+        }
+        if let Some(withholding) = &self.foreign_withholding {
+            withholding.check()?;
+            if withholding_tax < withholding.amount {
+                bail!(
+                    "withholding tax {} is less than the {} withheld abroad",
+                    withholding_tax,
+                    withholding.amount
+                );
+            }
+        }
+
+        Ok(Dividend {
+            _id: self._id,
+            brokerage_account_id: self.brokerage_account_id.unwrap(),
+            security_id: self.security_id.unwrap(),
+            declaration_timestamp_ms: self.declaration_timestamp_ms,
+            ex_timestamp_ms,
+            record_timestamp_ms: self.record_timestamp_ms,
+            pay_timestamp_ms,
+            amount_per_share: self.amount_per_share.unwrap(),
+            quantity: self.quantity.unwrap(),
+            withholding_tax,
+            foreign_withholding: self.foreign_withholding,
+            classification: self.classification,
+            reinvestment_execution_id: self.reinvestment_execution_id,
+        })
+    }
+}
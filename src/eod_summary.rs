@@ -2,14 +2,16 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use bson::oid::ObjectId;
-use futures::TryStreamExt;
-use mongodb::{ClientSession, Database};
+use mongodb::ClientSession;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
-use crate::{account::BrokerageAccount, db_util};
+use crate::{
+    account::BrokerageAccount, db_util, namespace::Namespace, subscription::Watched,
+    versioned::Versioned,
+};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct EODSummary {
     _id: ObjectId,
     brokerage_account_id: ObjectId,
@@ -184,37 +186,81 @@ impl EODSummary {
 
     pub async fn insert(
         &self,
-        db: &Database,
+        db: &impl Namespace,
         session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<()> {
         db_util::insert(self, db, Self::COLLECTION_NAME, session).await
     }
 
-    pub async fn find_by_id(db: &Database, id: ObjectId) -> Result<Option<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(bson::doc! {"_id": id})
-            .await?;
-
-        Ok(result)
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
     }
 
     pub async fn find_by_account_id(
-        db: &Database,
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"brokerage_account_id": brokerage_account_id },
+            None,
+            session,
+        )
+        .await
+    }
+
+    /// Returns the account's summaries whose end timestamp falls within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
         brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<Vec<Self>> {
-        Ok(db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find(bson::doc! {"brokerage_account_id": brokerage_account_id })
-            .await?
-            .try_collect()
-            .await?)
-    }
-
-    pub async fn brokerage_account(&self, db: &Database) -> Result<BrokerageAccount> {
-        Ok(BrokerageAccount::find_by_id(db, self.brokerage_account_id)
-            .await?
-            .unwrap())
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "end_timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "end_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+
+    pub async fn brokerage_account(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<BrokerageAccount> {
+        Ok(
+            BrokerageAccount::find_by_id(db, self.brokerage_account_id, session)
+                .await?
+                .unwrap(),
+        )
+    }
+}
+
+impl Versioned for EODSummary {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for EODSummary {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+
+    fn account_field() -> Option<&'static str> {
+        Some("brokerage_account_id")
     }
 }
 
@@ -0,0 +1,350 @@
+//! Execution quality: slippage of fills against arrival, interval VWAP and
+//! closing prices.
+//!
+//! Slippage is reported in basis points of the benchmark price and is positive
+//! when the fill was worse than the benchmark, i.e. bought above or sold below
+//! it. Benchmarks are derived from stored [`PriceBar`]s of a single duration.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use tokio::sync::Mutex;
+
+use crate::{
+    date_util::{self, MS_PER_DAY},
+    namespace::Namespace,
+    order::Order,
+    price_bar::PriceBar,
+    trade_execution::{TradeExecution, TradeSide},
+};
+
+const BPS: f64 = 10_000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Benchmark {
+    /// The last price before the order was submitted.
+    Arrival,
+    /// The volume weighted average price over the order's lifetime.
+    IntervalVwap,
+    /// The last price of the execution's UTC day.
+    Close,
+}
+
+impl Benchmark {
+    pub const ALL: [Benchmark; 3] = [
+        Benchmark::Arrival,
+        Benchmark::IntervalVwap,
+        Benchmark::Close,
+    ];
+}
+
+/// One execution and the benchmark prices it is measured against.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionSlippage {
+    execution: TradeExecution,
+    arrival_price: Option<f64>,
+    interval_vwap: Option<f64>,
+    close_price: Option<f64>,
+}
+
+impl ExecutionSlippage {
+    /// Measures `execution` against `bars` of its security, oldest first.
+    ///
+    /// The arrival price and interval VWAP need the parent order and are
+    /// `None` without one, as is any benchmark without covering bars.
+    pub fn compute(execution: &TradeExecution, order: Option<&Order>, bars: &[PriceBar]) -> Self {
+        let arrival_price = order.and_then(|o| price_at(bars, o.submitted_timestamp_ms()));
+        let interval_vwap =
+            order.and_then(|o| vwap(bars, o.submitted_timestamp_ms(), order_end_ms(o, execution)));
+
+        let day_start = date_util::start_of_day_ms(execution.execution_timestamp_ms());
+        let close_price = bars
+            .iter()
+            .filter(|b| b.start_timestamp_ms() >= day_start)
+            .take_while(|b| b.start_timestamp_ms() < day_start + MS_PER_DAY)
+            .last()
+            .map(PriceBar::close);
+
+        Self {
+            execution: execution.clone(),
+            arrival_price,
+            interval_vwap,
+            close_price,
+        }
+    }
+
+    pub fn execution(&self) -> &TradeExecution {
+        &self.execution
+    }
+
+    pub fn benchmark_price(&self, benchmark: Benchmark) -> Option<f64> {
+        match benchmark {
+            Benchmark::Arrival => self.arrival_price,
+            Benchmark::IntervalVwap => self.interval_vwap,
+            Benchmark::Close => self.close_price,
+        }
+    }
+
+    /// Slippage in basis points, positive when worse than the benchmark.
+    pub fn slippage_bps(&self, benchmark: Benchmark) -> Option<f64> {
+        let benchmark_price = self.benchmark_price(benchmark)?;
+        if benchmark_price.abs() < f64::EPSILON {
+            return None;
+        }
+        let direction = match self.execution.side() {
+            TradeSide::Buy => 1.0,
+            TradeSide::Sell => -1.0,
+        };
+        Some(direction * (self.execution.price() - benchmark_price) / benchmark_price * BPS)
+    }
+
+    /// Slippage in currency, positive when worse than the benchmark.
+    pub fn slippage_cost(&self, benchmark: Benchmark) -> Option<f64> {
+        let bps = self.slippage_bps(benchmark)?;
+        Some(bps / BPS * self.benchmark_price(benchmark)? * self.execution.quantity().abs())
+    }
+
+    fn notional(&self) -> f64 {
+        self.execution.quantity().abs() * self.execution.price()
+    }
+}
+
+/// When the order stopped working: its final status change, or the execution
+/// itself if it is still open.
+fn order_end_ms(order: &Order, execution: &TradeExecution) -> i64 {
+    match order.status_history().last() {
+        Some(change) if change.status.is_final() => change.timestamp_ms,
+        _ => execution.execution_timestamp_ms(),
+    }
+}
+
+/// The close of the last bar ending by `timestamp_ms`, or else the open of
+/// the bar containing it.
+fn price_at(bars: &[PriceBar], timestamp_ms: i64) -> Option<f64> {
+    bars.iter()
+        .rfind(|b| b.end_timestamp_ms() <= timestamp_ms)
+        .map(PriceBar::close)
+        .or_else(|| {
+            bars.iter()
+                .find(|b| {
+                    b.start_timestamp_ms() <= timestamp_ms && timestamp_ms < b.end_timestamp_ms()
+                })
+                .map(PriceBar::open)
+        })
+}
+
+/// VWAP of the bars overlapping `[start_ms, end_ms]`.
+fn vwap(bars: &[PriceBar], start_ms: i64, end_ms: i64) -> Option<f64> {
+    let (value, volume) = bars
+        .iter()
+        .filter(|b| b.start_timestamp_ms() <= end_ms && b.end_timestamp_ms() > start_ms)
+        .fold((0.0, 0.0), |(value, volume), b| {
+            (value + b.typical_price() * b.volume(), volume + b.volume())
+        });
+    (volume > 0.0).then(|| value / volume)
+}
+
+/// Notional weighted slippage over a group of executions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SlippageSummary {
+    executions: usize,
+    quantity: f64,
+    notional: f64,
+    weighted_bps: BTreeMap<Benchmark, (f64, f64)>,
+    cost: BTreeMap<Benchmark, f64>,
+}
+
+impl SlippageSummary {
+    fn add(&mut self, slippage: &ExecutionSlippage) {
+        let notional = slippage.notional();
+        self.executions += 1;
+        self.quantity += slippage.execution.quantity().abs();
+        self.notional += notional;
+
+        for benchmark in Benchmark::ALL {
+            if let (Some(bps), Some(cost)) = (
+                slippage.slippage_bps(benchmark),
+                slippage.slippage_cost(benchmark),
+            ) {
+                let (sum, weight) = self.weighted_bps.entry(benchmark).or_default();
+                *sum += bps * notional;
+                *weight += notional;
+                *self.cost.entry(benchmark).or_default() += cost;
+            }
+        }
+    }
+
+    pub fn executions(&self) -> usize {
+        self.executions
+    }
+
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    pub fn notional(&self) -> f64 {
+        self.notional
+    }
+
+    /// Average slippage weighted by notional, over the executions that have
+    /// the benchmark.
+    pub fn average_slippage_bps(&self, benchmark: Benchmark) -> Option<f64> {
+        let (sum, weight) = self.weighted_bps.get(&benchmark)?;
+        (*weight > 0.0).then(|| sum / weight)
+    }
+
+    pub fn total_slippage_cost(&self, benchmark: Benchmark) -> Option<f64> {
+        self.cost.get(&benchmark).copied()
+    }
+}
+
+/// Slippage of a set of executions, with aggregates by account, security and
+/// time of day.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionQualityReport {
+    executions: Vec<ExecutionSlippage>,
+}
+
+impl ExecutionQualityReport {
+    /// Measures each execution against its parent order, if among `orders`,
+    /// and the bars of its security.
+    pub fn from_executions(
+        executions: &[TradeExecution],
+        orders: &[Order],
+        bars: &[PriceBar],
+    ) -> Self {
+        let orders: HashMap<ObjectId, &Order> = orders.iter().map(|o| (o.id(), o)).collect();
+        let mut bars_by_security: HashMap<ObjectId, Vec<PriceBar>> = HashMap::new();
+        for bar in bars {
+            bars_by_security
+                .entry(bar.security_id())
+                .or_default()
+                .push(bar.clone());
+        }
+        for bars in bars_by_security.values_mut() {
+            bars.sort_by_key(PriceBar::start_timestamp_ms);
+        }
+
+        let mut executions: Vec<ExecutionSlippage> = executions
+            .iter()
+            .map(|execution| {
+                let order = execution.order_id().and_then(|id| orders.get(&id).copied());
+                let bars = bars_by_security
+                    .get(&execution.security_id())
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                ExecutionSlippage::compute(execution, order, bars)
+            })
+            .collect();
+        executions.sort_by_key(|s| s.execution.execution_timestamp_ms());
+
+        Self { executions }
+    }
+
+    /// Measures the accounts' executions within
+    /// `[start_timestamp_ms, end_timestamp_ms]` against stored bars of
+    /// `bar_duration_ms`.
+    pub async fn for_accounts(
+        db: &impl Namespace,
+        brokerage_account_ids: &[ObjectId],
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        bar_duration_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let mut executions = Vec::new();
+        for id in brokerage_account_ids {
+            executions.extend(
+                TradeExecution::find_by_account_id_and_range(
+                    db,
+                    *id,
+                    start_timestamp_ms,
+                    end_timestamp_ms,
+                    session.clone(),
+                )
+                .await?,
+            );
+        }
+
+        let mut order_ids: Vec<ObjectId> = executions.iter().filter_map(|e| e.order_id()).collect();
+        order_ids.sort();
+        order_ids.dedup();
+        let mut orders = Vec::new();
+        for id in order_ids {
+            if let Some(order) = Order::find_by_id(db, id, session.clone()).await? {
+                orders.push(order);
+            }
+        }
+
+        // Each security's bars from before its earliest order to the end of the
+        // day of its latest execution, or to the end of its latest order if
+        // that worked past the day.
+        let mut ranges: HashMap<ObjectId, (i64, i64)> = HashMap::new();
+        for execution in &executions {
+            let timestamp = execution.execution_timestamp_ms();
+            let order = orders.iter().find(|o| Some(o.id()) == execution.order_id());
+            let order_start = order.map_or(timestamp, Order::submitted_timestamp_ms);
+            let order_end = order.map_or(timestamp, |o| order_end_ms(o, execution));
+            let start = date_util::start_of_day_ms(order_start.min(timestamp)) - bar_duration_ms;
+            let end = order_end.max(date_util::start_of_day_ms(timestamp) + MS_PER_DAY);
+            let range = ranges
+                .entry(execution.security_id())
+                .or_insert((start, end));
+            *range = (range.0.min(start), range.1.max(end));
+        }
+        let mut bars = Vec::new();
+        for (security_id, (start, end)) in ranges {
+            bars.extend(
+                PriceBar::find_by_security_id_and_range(
+                    db,
+                    security_id,
+                    bar_duration_ms,
+                    start,
+                    end,
+                    session.clone(),
+                )
+                .await?,
+            );
+        }
+
+        Ok(Self::from_executions(&executions, &orders, &bars))
+    }
+
+    /// Oldest first.
+    pub fn executions(&self) -> &[ExecutionSlippage] {
+        &self.executions
+    }
+
+    pub fn summary(&self) -> SlippageSummary {
+        self.summarize(|_| ()).remove(&()).unwrap_or_default()
+    }
+
+    pub fn by_account(&self) -> BTreeMap<ObjectId, SlippageSummary> {
+        self.summarize(|s| s.execution.brokerage_account_id())
+    }
+
+    pub fn by_security(&self) -> BTreeMap<ObjectId, SlippageSummary> {
+        self.summarize(|s| s.execution.security_id())
+    }
+
+    /// Keyed by the UTC hour of the execution, from 0 to 23.
+    pub fn by_hour_of_day(&self) -> BTreeMap<u32, SlippageSummary> {
+        self.summarize(|s| date_util::hour_of_day(s.execution.execution_timestamp_ms()))
+    }
+
+    fn summarize<K: Ord>(
+        &self,
+        key: impl Fn(&ExecutionSlippage) -> K,
+    ) -> BTreeMap<K, SlippageSummary> {
+        let mut summaries: BTreeMap<K, SlippageSummary> = BTreeMap::new();
+        for slippage in &self.executions {
+            summaries.entry(key(slippage)).or_default().add(slippage);
+        }
+        summaries
+    }
+}
@@ -0,0 +1,107 @@
+//! Itemized execution fees.
+//!
+//! Brokers report commissions, exchange, regulatory and clearing fees, ECN
+//! rebates and taxes separately. A [`TradeExecution`] keeps the items and
+//! stores their total as its commission, so code reading only the commission
+//! keeps working. Rebates are negative amounts.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Result;
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{namespace::Namespace, trade_execution::TradeExecution};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FeeCategory {
+    Commission,
+    Exchange,
+    /// SEC Section 31 fee.
+    Sec,
+    /// FINRA trading activity fee.
+    FinraTaf,
+    Clearing,
+    EcnRebate,
+    Tax,
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Fee {
+    pub category: FeeCategory,
+    pub amount: f64,
+}
+
+impl Fee {
+    pub fn new(category: FeeCategory, amount: f64) -> Self {
+        Self { category, amount }
+    }
+}
+
+/// Fees per category over a set of executions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeeSummary {
+    executions: usize,
+    by_category: BTreeMap<FeeCategory, f64>,
+}
+
+impl FeeSummary {
+    /// Executions without a breakdown count their commission as
+    /// [`FeeCategory::Commission`].
+    pub fn from_executions(executions: &[TradeExecution]) -> Self {
+        let mut summary = Self::default();
+        for execution in executions {
+            summary.executions += 1;
+            for fee in execution.fees() {
+                *summary.by_category.entry(fee.category).or_default() += fee.amount;
+            }
+        }
+        summary
+    }
+
+    /// Sums the fees of the account's executions within
+    /// `[start_timestamp_ms, end_timestamp_ms]`.
+    pub async fn for_account_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let executions = TradeExecution::find_by_account_id_and_range(
+            db,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            session,
+        )
+        .await?;
+        Ok(Self::from_executions(&executions))
+    }
+
+    pub fn executions(&self) -> usize {
+        self.executions
+    }
+
+    pub fn amount(&self, category: FeeCategory) -> f64 {
+        self.by_category.get(&category).copied().unwrap_or_default()
+    }
+
+    /// The categories that occurred, with their totals.
+    pub fn by_category(&self) -> &BTreeMap<FeeCategory, f64> {
+        &self.by_category
+    }
+
+    /// Net of rebates.
+    pub fn total(&self) -> f64 {
+        self.by_category.values().sum()
+    }
+
+    /// Sum of the negative amounts, as a negative number.
+    pub fn rebates(&self) -> f64 {
+        self.by_category.values().filter(|a| **a < 0.0).sum()
+    }
+}
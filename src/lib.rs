@@ -1,20 +1,40 @@
 // Public modules.
 pub mod account;
+pub mod cash_item;
+pub mod db;
+pub mod dividend;
 pub mod eod_summary;
+pub mod execution_quality;
+pub mod fee;
+pub mod lot;
+pub mod migrations;
+pub mod namespace;
+pub mod option_event;
+pub mod order;
+pub mod owner;
+pub mod performance;
+pub mod price_bar;
+pub mod round_trip;
 pub mod security;
+pub mod store;
+pub mod subscription;
+pub mod tag;
+pub mod tax_report;
 pub mod trade_execution;
+pub mod versioned;
+pub mod withholding;
 
 // Internal modules.
+mod date_util;
 mod db_util;
-mod migrations;
 
 use anyhow::Result;
-use mongodb::Database;
+use namespace::Namespace;
 
-pub async fn initialize(db: &Database) -> Result<()> {
+pub async fn initialize(db: &impl Namespace) -> Result<()> {
     migrations::run_migrations(db).await
 }
 
-pub async fn remove_data(db: &Database) -> Result<()> {
+pub async fn remove_data(db: &impl Namespace) -> Result<()> {
     migrations::run_down_migrations(db).await
 }
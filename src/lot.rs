@@ -0,0 +1,739 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use anyhow::{Result, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use tokio::sync::Mutex;
+
+use crate::{
+    account::BrokerageAccount,
+    namespace::Namespace,
+    trade_execution::{PositionEffect, TradeExecution, TradeSide},
+};
+
+/// Quantities smaller than this are treated as zero.
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+/// Losses are disallowed if substantially identical shares are acquired within
+/// this many milliseconds before or after the sale.
+pub const WASH_SALE_WINDOW_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// A tax lot still held, or still owed if short, at the end of the matched
+/// executions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenLot {
+    brokerage_account_id: ObjectId,
+    security_id: ObjectId,
+    opening_execution_id: ObjectId,
+    acquired_timestamp_ms: i64,
+    short: bool,
+    quantity: f64,
+    cost_basis: f64,
+    proceeds: f64,
+    wash_sale_adjustment: f64,
+}
+
+impl OpenLot {
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    pub fn opening_execution_id(&self) -> ObjectId {
+        self.opening_execution_id
+    }
+
+    /// Acquisition time, moved earlier by the holding period of any wash sale
+    /// this lot replaced.
+    pub fn acquired_timestamp_ms(&self) -> i64 {
+        self.acquired_timestamp_ms
+    }
+
+    /// Whether the lot was opened by a short sale.
+    pub fn is_short(&self) -> bool {
+        self.short
+    }
+
+    /// Shares held, or owed if short, as a positive number.
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    /// Total cost basis including commission and any wash sale adjustment.
+    /// Zero for short lots until they are covered.
+    pub fn cost_basis(&self) -> f64 {
+        self.cost_basis
+    }
+
+    /// Proceeds of the short sale net of commission. Zero for long lots.
+    pub fn proceeds(&self) -> f64 {
+        self.proceeds
+    }
+
+    /// Disallowed loss carried into this lot's basis. For a short lot it is
+    /// added to the cost of covering.
+    pub fn wash_sale_adjustment(&self) -> f64 {
+        self.wash_sale_adjustment
+    }
+
+    fn add_wash_sale_adjustment(&mut self, amount: f64, holding_period_ms: i64) {
+        if !self.short {
+            self.cost_basis += amount;
+        }
+        self.wash_sale_adjustment += amount;
+        self.acquired_timestamp_ms -= holding_period_ms;
+    }
+
+    fn split_off(&mut self, quantity: f64) -> OpenLot {
+        let fraction = quantity / self.quantity;
+        let split = OpenLot {
+            quantity,
+            cost_basis: self.cost_basis * fraction,
+            proceeds: self.proceeds * fraction,
+            wash_sale_adjustment: self.wash_sale_adjustment * fraction,
+            ..self.clone()
+        };
+        self.quantity -= quantity;
+        self.cost_basis -= split.cost_basis;
+        self.proceeds -= split.proceeds;
+        self.wash_sale_adjustment -= split.wash_sale_adjustment;
+        split
+    }
+}
+
+/// A tax lot, or part of one, disposed of by a closing execution.
+///
+/// For a short lot the opening execution is the short sale and the closing
+/// execution the purchase that covered it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClosedLot {
+    brokerage_account_id: ObjectId,
+    security_id: ObjectId,
+    opening_execution_id: ObjectId,
+    closing_execution_id: ObjectId,
+    acquired_timestamp_ms: i64,
+    disposed_timestamp_ms: i64,
+    short: bool,
+    quantity: f64,
+    proceeds: f64,
+    cost_basis: f64,
+    disallowed_loss: f64,
+}
+
+impl ClosedLot {
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    pub fn opening_execution_id(&self) -> ObjectId {
+        self.opening_execution_id
+    }
+
+    pub fn closing_execution_id(&self) -> ObjectId {
+        self.closing_execution_id
+    }
+
+    /// For a short lot, the time of the covering purchase: the gain is
+    /// realized when the short is covered and is short-term.
+    pub fn acquired_timestamp_ms(&self) -> i64 {
+        self.acquired_timestamp_ms
+    }
+
+    /// For a short lot, the time the short was covered.
+    pub fn disposed_timestamp_ms(&self) -> i64 {
+        self.disposed_timestamp_ms
+    }
+
+    pub fn is_short(&self) -> bool {
+        self.short
+    }
+
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    /// Sale proceeds net of commission.
+    pub fn proceeds(&self) -> f64 {
+        self.proceeds
+    }
+
+    pub fn cost_basis(&self) -> f64 {
+        self.cost_basis
+    }
+
+    /// Loss disallowed by the wash sale rule, as a positive amount.
+    pub fn disallowed_loss(&self) -> f64 {
+        self.disallowed_loss
+    }
+
+    pub fn is_wash_sale(&self) -> bool {
+        self.disallowed_loss > 0.0
+    }
+
+    /// Realized gain or loss after adding back any disallowed loss.
+    pub fn gain_loss(&self) -> f64 {
+        self.proceeds - self.cost_basis + self.disallowed_loss
+    }
+}
+
+/// A loss disallowed because of replacement shares: a purchase for a long lot
+/// sold at a loss, or a short sale for a short lot covered at a loss.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WashSale {
+    loss_execution_id: ObjectId,
+    replacement_execution_id: ObjectId,
+    replacement_brokerage_account_id: ObjectId,
+    quantity: f64,
+    disallowed_loss: f64,
+    holding_period_ms: i64,
+    permanent: bool,
+}
+
+impl WashSale {
+    /// The closing execution that realized the loss.
+    pub fn loss_execution_id(&self) -> ObjectId {
+        self.loss_execution_id
+    }
+
+    /// The purchase or short sale whose lot receives the disallowed loss.
+    pub fn replacement_execution_id(&self) -> ObjectId {
+        self.replacement_execution_id
+    }
+
+    pub fn replacement_brokerage_account_id(&self) -> ObjectId {
+        self.replacement_brokerage_account_id
+    }
+
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    pub fn disallowed_loss(&self) -> f64 {
+        self.disallowed_loss
+    }
+
+    /// Holding period of the sold shares, tacked onto the replacement lot.
+    /// Zero for short lots, whose gains are short-term regardless.
+    pub fn holding_period_ms(&self) -> i64 {
+        self.holding_period_ms
+    }
+
+    /// Whether the replacement was bought in a tax-deferred or tax-exempt
+    /// account. The loss is then never added to a basis and is lost for good.
+    pub fn is_permanent(&self) -> bool {
+        self.permanent
+    }
+}
+
+/// The result of matching executions to tax lots first-in, first-out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LotReport {
+    open_lots: Vec<OpenLot>,
+    closed_lots: Vec<ClosedLot>,
+    wash_sales: Vec<WashSale>,
+}
+
+impl LotReport {
+    /// Matches executions to lots per account and security, without wash sale
+    /// detection.
+    pub fn from_executions(executions: &[TradeExecution]) -> Result<Self> {
+        Matcher::new(executions, false, HashSet::new()).run()
+    }
+
+    /// Matches executions to lots and applies the wash sale rule across all of
+    /// the given executions, which should cover every account of one taxpayer.
+    ///
+    /// Only purchases still held at the time of the loss sale, or made after it,
+    /// count as replacement shares. Shares from the same purchase as the sold
+    /// lot never replace it. Losses on covering a short are washed the same
+    /// way by short sales, and purchases that cover a short or sales that close
+    /// a long position replace nothing.
+    pub fn from_executions_with_wash_sales(executions: &[TradeExecution]) -> Result<Self> {
+        Matcher::new(executions, true, HashSet::new()).run()
+    }
+
+    /// Like [`Self::from_executions_with_wash_sales`], but losses in accounts
+    /// that are not taxable are never washed, and replacement shares bought in
+    /// them disallow the loss permanently instead of adjusting their basis.
+    pub fn from_account_executions_with_wash_sales(
+        executions: &[TradeExecution],
+        accounts: &[BrokerageAccount],
+    ) -> Result<Self> {
+        let non_taxable = accounts
+            .iter()
+            .filter(|a| !a.is_taxable())
+            .map(BrokerageAccount::id)
+            .collect();
+        Matcher::new(executions, true, non_taxable).run()
+    }
+
+    /// Loads all executions of the given accounts and matches them to lots.
+    /// Wash sales follow the accounts' tax treatment.
+    pub async fn for_accounts(
+        db: &impl Namespace,
+        brokerage_account_ids: &[ObjectId],
+        detect_wash_sales: bool,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let mut accounts = Vec::new();
+        for id in brokerage_account_ids {
+            if let Some(account) = BrokerageAccount::find_by_id(db, *id, session.clone()).await? {
+                accounts.push(account);
+            }
+        }
+        Self::load(
+            db,
+            brokerage_account_ids,
+            &accounts,
+            detect_wash_sales,
+            session,
+        )
+        .await
+    }
+
+    /// Loads all executions across every account of an owner and matches them
+    /// to lots.
+    pub async fn for_owner(
+        db: &impl Namespace,
+        owner_id: ObjectId,
+        detect_wash_sales: bool,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let accounts = BrokerageAccount::find_by_owner_id(db, owner_id, session.clone()).await?;
+        let account_ids: Vec<ObjectId> = accounts.iter().map(BrokerageAccount::id).collect();
+        Self::load(db, &account_ids, &accounts, detect_wash_sales, session).await
+    }
+
+    /// Matches the executions of `brokerage_account_ids`, treating those not
+    /// among `accounts` as taxable.
+    pub(crate) async fn load(
+        db: &impl Namespace,
+        brokerage_account_ids: &[ObjectId],
+        accounts: &[BrokerageAccount],
+        detect_wash_sales: bool,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let mut executions = Vec::new();
+        for id in brokerage_account_ids {
+            executions.extend(
+                TradeExecution::find_by_account_id_and_range(
+                    db,
+                    *id,
+                    i64::MIN,
+                    i64::MAX,
+                    session.clone(),
+                )
+                .await?,
+            );
+        }
+        if detect_wash_sales {
+            Self::from_account_executions_with_wash_sales(&executions, accounts)
+        } else {
+            Self::from_executions(&executions)
+        }
+    }
+
+    pub fn open_lots(&self) -> &[OpenLot] {
+        &self.open_lots
+    }
+
+    pub fn closed_lots(&self) -> &[ClosedLot] {
+        &self.closed_lots
+    }
+
+    pub fn wash_sales(&self) -> &[WashSale] {
+        &self.wash_sales
+    }
+
+    /// Shares of the security held in the account, negative if short.
+    pub fn net_quantity(&self, brokerage_account_id: ObjectId, security_id: ObjectId) -> f64 {
+        self.open_lots
+            .iter()
+            .filter(|lot| {
+                lot.brokerage_account_id == brokerage_account_id && lot.security_id == security_id
+            })
+            .map(|lot| {
+                if lot.short {
+                    -lot.quantity
+                } else {
+                    lot.quantity
+                }
+            })
+            .sum()
+    }
+}
+
+struct Adjustment {
+    quantity: f64,
+    amount: f64,
+    holding_period_ms: i64,
+}
+
+struct Matcher<'a> {
+    executions: Vec<&'a TradeExecution>,
+    detect_wash_sales: bool,
+    /// Accounts that are not taxable, see
+    /// [`LotReport::from_account_executions_with_wash_sales`].
+    non_taxable_accounts: HashSet<ObjectId>,
+    open: HashMap<(ObjectId, ObjectId), VecDeque<OpenLot>>,
+    replacement_capacity: HashMap<ObjectId, f64>,
+    pending_adjustments: HashMap<ObjectId, Vec<Adjustment>>,
+    report: LotReport,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(
+        executions: &'a [TradeExecution],
+        detect_wash_sales: bool,
+        non_taxable_accounts: HashSet<ObjectId>,
+    ) -> Self {
+        let mut sorted: Vec<&TradeExecution> = executions.iter().collect();
+        sorted.sort_by_key(|e| e.execution_timestamp_ms());
+
+        // Shares bought to cover a short cannot replace shares sold at a loss,
+        // so a purchase only counts with what it adds to a long position, and
+        // a sale with what it adds to a short one. Positions do not depend on
+        // wash sales, so this is known up front.
+        let mut positions: HashMap<(ObjectId, ObjectId), f64> = HashMap::new();
+        let mut replacement_capacity = HashMap::new();
+        for execution in &sorted {
+            let position = positions
+                .entry((execution.brokerage_account_id(), execution.security_id()))
+                .or_default();
+            let quantity = execution.quantity().abs();
+            if *execution.side() == TradeSide::Buy {
+                let covered = quantity.min((-*position).max(0.0));
+                replacement_capacity.insert(execution.id(), quantity - covered);
+                *position += quantity;
+            } else {
+                let closed = quantity.min(position.max(0.0));
+                replacement_capacity.insert(execution.id(), quantity - closed);
+                *position -= quantity;
+            }
+        }
+
+        Self {
+            executions: sorted,
+            detect_wash_sales,
+            non_taxable_accounts,
+            open: HashMap::new(),
+            replacement_capacity,
+            pending_adjustments: HashMap::new(),
+            report: LotReport::default(),
+        }
+    }
+
+    fn run(mut self) -> Result<LotReport> {
+        for i in 0..self.executions.len() {
+            self.match_execution(self.executions[i])?;
+        }
+
+        self.report.open_lots = self.open.into_values().flatten().collect();
+        self.report
+            .open_lots
+            .sort_by_key(|lot| lot.acquired_timestamp_ms);
+        Ok(self.report)
+    }
+
+    /// Closes lots on the other side of the execution first-in, first-out and
+    /// opens a lot with the rest, so a sale beyond the long position opens a
+    /// short one and a purchase beyond the short position a long one. An
+    /// explicit position effect on the execution must agree.
+    fn match_execution(&mut self, execution: &'a TradeExecution) -> Result<()> {
+        let key = (execution.brokerage_account_id(), execution.security_id());
+        let buying = *execution.side() == TradeSide::Buy;
+        let side = if buying { "buy" } else { "sell" };
+        let quantity = execution.quantity().abs();
+
+        // An account holds either long or short lots of a security, not both.
+        let holds_opposite = self
+            .open
+            .get(&key)
+            .and_then(VecDeque::front)
+            .is_some_and(|lot| lot.short == buying);
+        let closed = if holds_opposite {
+            if execution.position_effect() == Some(PositionEffect::Open) {
+                bail!(
+                    "{} execution {} opens a position while the opposite one is held",
+                    side,
+                    execution.brokerage_execution_id()
+                );
+            }
+            self.close_lots(execution, quantity)
+        } else {
+            0.0
+        };
+
+        let remaining = quantity - closed;
+        if remaining > QUANTITY_EPSILON {
+            if execution.position_effect() == Some(PositionEffect::Close) {
+                bail!(
+                    "{} execution {} exceeds the open position",
+                    side,
+                    execution.brokerage_execution_id()
+                );
+            }
+            self.open_lot(execution, remaining);
+        }
+
+        Ok(())
+    }
+
+    /// Commission per share of the execution, negative for a net rebate.
+    fn commission_per_unit(execution: &TradeExecution) -> f64 {
+        let quantity = execution.quantity().abs();
+        if quantity > 0.0 {
+            execution.commission() / quantity
+        } else {
+            0.0
+        }
+    }
+
+    fn open_lot(&mut self, execution: &TradeExecution, quantity: f64) {
+        let commission_per_unit = Self::commission_per_unit(execution);
+        let short = *execution.side() == TradeSide::Sell;
+        let mut lot = OpenLot {
+            brokerage_account_id: execution.brokerage_account_id(),
+            security_id: execution.security_id(),
+            opening_execution_id: execution.id(),
+            acquired_timestamp_ms: execution.execution_timestamp_ms(),
+            short,
+            quantity,
+            cost_basis: 0.0,
+            proceeds: 0.0,
+            wash_sale_adjustment: 0.0,
+        };
+        if short {
+            lot.proceeds = quantity * (execution.price() - commission_per_unit);
+        } else {
+            lot.cost_basis = quantity * (execution.price() + commission_per_unit);
+        }
+
+        let lots = self
+            .open
+            .entry((lot.brokerage_account_id, lot.security_id))
+            .or_default();
+        for adjustment in self
+            .pending_adjustments
+            .remove(&execution.id())
+            .unwrap_or_default()
+        {
+            if lot.quantity <= QUANTITY_EPSILON {
+                break;
+            }
+            let mut adjusted = lot.split_off(adjustment.quantity.min(lot.quantity));
+            adjusted.add_wash_sale_adjustment(adjustment.amount, adjustment.holding_period_ms);
+            lots.push_back(adjusted);
+        }
+        if lot.quantity > QUANTITY_EPSILON {
+            lots.push_back(lot);
+        }
+    }
+
+    /// Closes up to `quantity` of the open lots and returns the quantity
+    /// closed.
+    fn close_lots(&mut self, execution: &TradeExecution, quantity: f64) -> f64 {
+        let key = (execution.brokerage_account_id(), execution.security_id());
+        let commission_per_unit = Self::commission_per_unit(execution);
+        let timestamp_ms = execution.execution_timestamp_ms();
+
+        let lots = self.open.entry(key).or_default();
+        let mut remaining = quantity;
+        let mut closed = Vec::new();
+        while remaining > QUANTITY_EPSILON {
+            let Some(lot) = lots.front_mut() else {
+                break;
+            };
+
+            let matched_quantity = remaining.min(lot.quantity);
+            let matched = lot.split_off(matched_quantity);
+            if lot.quantity <= QUANTITY_EPSILON {
+                lots.pop_front();
+            }
+            remaining -= matched_quantity;
+
+            let closed_lot = if matched.short {
+                ClosedLot {
+                    brokerage_account_id: matched.brokerage_account_id,
+                    security_id: matched.security_id,
+                    opening_execution_id: matched.opening_execution_id,
+                    closing_execution_id: execution.id(),
+                    acquired_timestamp_ms: timestamp_ms,
+                    disposed_timestamp_ms: timestamp_ms,
+                    short: true,
+                    quantity: matched_quantity,
+                    proceeds: matched.proceeds,
+                    cost_basis: matched_quantity * (execution.price() + commission_per_unit)
+                        + matched.wash_sale_adjustment,
+                    disallowed_loss: 0.0,
+                }
+            } else {
+                ClosedLot {
+                    brokerage_account_id: matched.brokerage_account_id,
+                    security_id: matched.security_id,
+                    opening_execution_id: matched.opening_execution_id,
+                    closing_execution_id: execution.id(),
+                    acquired_timestamp_ms: matched.acquired_timestamp_ms,
+                    disposed_timestamp_ms: timestamp_ms,
+                    short: false,
+                    quantity: matched_quantity,
+                    proceeds: matched_quantity * (execution.price() - commission_per_unit),
+                    cost_basis: matched.cost_basis,
+                    disallowed_loss: 0.0,
+                }
+            };
+            closed.push(closed_lot);
+        }
+
+        for mut lot in closed {
+            if self.detect_wash_sales
+                && lot.gain_loss() < 0.0
+                && !self
+                    .non_taxable_accounts
+                    .contains(&lot.brokerage_account_id)
+            {
+                self.apply_wash_sale(execution, &mut lot);
+            }
+            self.report.closed_lots.push(lot);
+        }
+
+        quantity - remaining
+    }
+
+    fn apply_wash_sale(&mut self, execution: &TradeExecution, lot: &mut ClosedLot) {
+        let loss_per_unit = -lot.gain_loss() / lot.quantity;
+        let holding_period_ms = lot.disposed_timestamp_ms - lot.acquired_timestamp_ms;
+        let sale_ms = execution.execution_timestamp_ms();
+        let replacement_side = if lot.short {
+            TradeSide::Sell
+        } else {
+            TradeSide::Buy
+        };
+        let mut remaining = lot.quantity;
+
+        let candidates: Vec<&TradeExecution> = self
+            .executions
+            .iter()
+            .copied()
+            .filter(|e| {
+                *e.side() == replacement_side
+                    && e.security_id() == lot.security_id
+                    && e.id() != lot.opening_execution_id
+                    && (e.execution_timestamp_ms() - sale_ms).abs() <= WASH_SALE_WINDOW_MS
+            })
+            .collect();
+
+        for candidate in candidates {
+            if remaining <= QUANTITY_EPSILON {
+                break;
+            }
+
+            let already_open = candidate.execution_timestamp_ms() <= sale_ms;
+            let capacity = self.replacement_capacity[&candidate.id()];
+            let available = if already_open {
+                capacity.min(self.held_quantity(candidate))
+            } else {
+                capacity
+            };
+            let quantity = available.min(remaining);
+            if quantity <= QUANTITY_EPSILON {
+                continue;
+            }
+
+            let amount = quantity * loss_per_unit;
+            *self.replacement_capacity.get_mut(&candidate.id()).unwrap() -= quantity;
+            remaining -= quantity;
+            lot.disallowed_loss += amount;
+
+            // Basis in a tax-advantaged account never yields a deductible
+            // loss, so a loss replaced there is not carried over.
+            let permanent = self
+                .non_taxable_accounts
+                .contains(&candidate.brokerage_account_id());
+            if !permanent {
+                if already_open {
+                    self.adjust_open_lots(candidate, quantity, amount, holding_period_ms);
+                } else {
+                    self.pending_adjustments
+                        .entry(candidate.id())
+                        .or_default()
+                        .push(Adjustment {
+                            quantity,
+                            amount,
+                            holding_period_ms,
+                        });
+                }
+            }
+
+            self.report.wash_sales.push(WashSale {
+                loss_execution_id: execution.id(),
+                replacement_execution_id: candidate.id(),
+                replacement_brokerage_account_id: candidate.brokerage_account_id(),
+                quantity,
+                disallowed_loss: amount,
+                holding_period_ms,
+                permanent,
+            });
+        }
+    }
+
+    fn held_quantity(&self, opening: &TradeExecution) -> f64 {
+        self.open
+            .get(&(opening.brokerage_account_id(), opening.security_id()))
+            .map(|lots| {
+                lots.iter()
+                    .filter(|lot| lot.opening_execution_id == opening.id())
+                    .map(|lot| lot.quantity)
+                    .sum()
+            })
+            .unwrap_or(0.0)
+    }
+
+    fn adjust_open_lots(
+        &mut self,
+        opening: &TradeExecution,
+        quantity: f64,
+        amount: f64,
+        holding_period_ms: i64,
+    ) {
+        let Some(lots) = self
+            .open
+            .get_mut(&(opening.brokerage_account_id(), opening.security_id()))
+        else {
+            return;
+        };
+
+        let mut remaining = quantity;
+        let mut index = 0;
+        while remaining > QUANTITY_EPSILON && index < lots.len() {
+            if lots[index].opening_execution_id != opening.id() {
+                index += 1;
+                continue;
+            }
+
+            let take = remaining.min(lots[index].quantity);
+            let untouched = lots[index].quantity - take;
+            if untouched > QUANTITY_EPSILON {
+                // Keep the unadjusted remainder right after the adjusted part so
+                // FIFO order is unchanged.
+                let rest = lots[index].split_off(untouched);
+                lots.insert(index + 1, rest);
+            }
+
+            lots[index].add_wash_sale_adjustment(amount * take / quantity, holding_period_ms);
+
+            remaining -= take;
+            index += 1;
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::{Result, bail};
+use bson::{Bson, Document, doc};
+use futures::TryStreamExt;
+
+use super::StepProgress;
+use crate::namespace::Namespace;
+
+/// A document transformation applied in batches.
+///
+/// Documents matching `filter` are updated with the aggregation pipeline
+/// `update`, in `_id` order. The update must make documents stop matching the
+/// filter, which is what makes a backfill idempotent and verifiable.
+#[derive(Clone, Debug)]
+pub struct Backfill {
+    pub collection_name: String,
+    pub description: String,
+    pub filter: Document,
+    pub update: Vec<Document>,
+    /// Undoes the backfill when migrating down. Without it the migration
+    /// cannot be reverted.
+    pub reverse: Option<Box<Backfill>>,
+}
+
+impl Backfill {
+    pub fn new(
+        collection_name: &str,
+        description: &str,
+        filter: Document,
+        update: Vec<Document>,
+    ) -> Self {
+        Self {
+            collection_name: collection_name.to_owned(),
+            description: description.to_owned(),
+            filter,
+            update,
+            reverse: None,
+        }
+    }
+
+    /// Sets the backfill that undoes this one on the same collection.
+    pub fn with_reverse(
+        mut self,
+        description: &str,
+        filter: Document,
+        update: Vec<Document>,
+    ) -> Self {
+        self.reverse = Some(Box::new(Self {
+            collection_name: self.collection_name.clone(),
+            description: description.to_owned(),
+            filter,
+            update,
+            reverse: None,
+        }));
+        self
+    }
+
+    /// Updates the matching documents after `resume_after`, recording the last
+    /// updated `_id` after each batch so an interrupted run can resume.
+    pub(super) async fn run<N: Namespace>(
+        &self,
+        db: &N,
+        batch_size: u32,
+        resume_after: Option<Bson>,
+        progress: &StepProgress<'_, N>,
+    ) -> Result<()> {
+        let collection = db.database().collection::<Document>(&self.collection_name);
+
+        let total_before = collection.count_documents(doc! {}).await?;
+        let expected = collection.count_documents(self.filter.clone()).await?;
+        tracing::info!(
+            "backfill {}: {} of {} documents to update",
+            self.description,
+            expected,
+            total_before
+        );
+
+        let mut last_id = resume_after;
+        let mut updated = 0;
+        loop {
+            let filter = match &last_id {
+                Some(id) => doc! { "$and": [self.filter.clone(), { "_id": { "$gt": id } }] },
+                None => self.filter.clone(),
+            };
+            let ids: Vec<Bson> = collection
+                .find(filter)
+                .projection(doc! { "_id": 1 })
+                .sort(doc! { "_id": 1 })
+                .limit(i64::from(batch_size))
+                .await?
+                .try_collect::<Vec<Document>>()
+                .await?
+                .into_iter()
+                .filter_map(|d| d.get("_id").cloned())
+                .collect();
+            let Some(batch_last_id) = ids.last().cloned() else {
+                break;
+            };
+
+            let result = collection
+                .update_many(doc! { "_id": { "$in": &ids } }, self.update.clone())
+                .await?;
+            updated += result.modified_count;
+            progress.save(Some(batch_last_id.clone())).await?;
+            last_id = Some(batch_last_id);
+            tracing::info!(
+                "backfill {}: {}/{} documents updated",
+                self.description,
+                updated,
+                expected
+            );
+        }
+
+        let remaining = collection.count_documents(self.filter.clone()).await?;
+        if remaining > 0 {
+            bail!(
+                "backfill {} left {} documents matching {}",
+                self.description,
+                remaining,
+                self.filter
+            );
+        }
+        let total_after = collection.count_documents(doc! {}).await?;
+        if total_after < total_before {
+            bail!(
+                "backfill {} lost documents: {} before, {} after",
+                self.description,
+                total_before,
+                total_after
+            );
+        }
+
+        Ok(())
+    }
+}
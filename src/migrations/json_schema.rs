@@ -0,0 +1,69 @@
+use bson::{Bson, Document, doc};
+
+/// A `$jsonSchema` collection validator for documents serialized from a Rust
+/// struct.
+///
+/// Later migrations start from the schema installed before them and add their
+/// fields, so the previous validator can be restored when migrating down.
+#[derive(Clone, Debug, Default)]
+pub(super) struct JsonSchema {
+    required: Vec<String>,
+    properties: Document,
+}
+
+impl JsonSchema {
+    pub(super) fn new() -> Self {
+        Self::default().required("_id", object_id())
+    }
+
+    /// A field that must be present with the given type. Replaces the type of
+    /// a field already in the schema.
+    pub(super) fn required(mut self, name: &str, field_type: Document) -> Self {
+        if !self.required.iter().any(|r| r == name) {
+            self.required.push(name.to_owned());
+        }
+        self.properties.insert(name, field_type);
+        self
+    }
+
+    /// An `Option` field, which may be missing or null.
+    pub(super) fn optional(mut self, name: &str, field_type: Document) -> Self {
+        self.properties
+            .insert(name, doc! { "anyOf": [field_type, { "bsonType": "null" }] });
+        self
+    }
+
+    pub(super) fn validator(&self) -> Document {
+        doc! {
+            "$jsonSchema": {
+                "bsonType": "object",
+                "required": &self.required,
+                "properties": self.properties.clone(),
+            }
+        }
+    }
+}
+
+pub(super) fn object_id() -> Document {
+    doc! { "bsonType": "objectId" }
+}
+
+pub(super) fn string() -> Document {
+    doc! { "bsonType": "string" }
+}
+
+/// An integer field such as a timestamp.
+pub(super) fn integer() -> Document {
+    doc! { "bsonType": ["int", "long"] }
+}
+
+/// A floating point field, which also accepts integers written by other
+/// clients.
+pub(super) fn number() -> Document {
+    doc! { "bsonType": ["double", "int", "long"] }
+}
+
+/// A unit-variant enum, serialized as the variant name.
+pub(super) fn one_of(variants: &[&str]) -> Document {
+    doc! { "enum": variants.iter().map(|v| Bson::from(*v)).collect::<Vec<_>>() }
+}
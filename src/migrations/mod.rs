@@ -1,11 +1,277 @@
-use anyhow::Result;
-use mongodb::Database;
-use tfiala_mongodb_migrator::{migration::Migration, migrator::default::DefaultMigrator};
+//! Schema migrations for the brokerage collections.
+//!
+//! Each migration declares the collections, indexes, validators and document
+//! backfills it makes. The runner applies them in order to reach a target version, reverses
+//! them to go back down, and can report the changes without applying them.
+//! Applied migrations are recorded by id in the `migrations` collection of the
+//! namespace, together with the progress of an unfinished migration so that a
+//! rerun resumes where the interrupted one stopped.
+//!
+//! Migrating down refuses to drop collections that still hold documents unless
+//! [`MigrationOptions::force`] is set.
 
+use std::fmt;
+
+use anyhow::{Result, bail};
+use bson::{Bson, Document, doc};
+use futures::TryStreamExt;
+use mongodb::{IndexModel, error::ErrorKind};
+use serde::{Deserialize, Serialize};
+
+use crate::namespace::Namespace;
+use json_schema::JsonSchema;
+
+mod backfill;
+mod json_schema;
 mod v001_add_accounts;
 mod v002_add_security;
 mod v003_add_trade_executions;
 mod v004_add_eod_summary;
+mod v005_add_owners;
+mod v006_add_validators;
+mod v007_add_schema_versions;
+mod v008_add_resume_tokens;
+mod v009_add_orders;
+mod v010_add_price_bars;
+mod v011_add_execution_fees;
+mod v012_add_execution_routing;
+mod v013_add_tags;
+mod v014_add_cash_items;
+mod v015_add_option_events;
+mod v016_add_dividends;
+mod v017_add_foreign_withholding;
+
+pub use backfill::Backfill;
+pub(crate) use v001_add_accounts::BROKERAGE_ACCOUNT_UNIQUE_INDEX_NAME;
+pub(crate) use v002_add_security::SECURITIES_UNIQUE_INDEX_NAME;
+pub(crate) use v003_add_trade_executions::TRADE_EXECUTIONS_UNIQUE_INDEX_NAME;
+pub(crate) use v004_add_eod_summary::EOD_SUMMARIES_UNIQUE_INDEX_NAME;
+
+/// Records which migrations ran, prefixed like the other collections.
+const MIGRATIONS_COLLECTION_NAME: &str = "migrations";
+
+/// Server error codes for changes that were already made, which a resumed
+/// migration may run into.
+const NAMESPACE_EXISTS_CODE: i32 = 48;
+const INDEX_NOT_FOUND_CODE: i32 = 27;
+
+/// A single collection, index, validator or document change made by a
+/// migration.
+#[derive(Clone, Debug)]
+pub enum SchemaChange {
+    CreateCollection {
+        collection_name: String,
+    },
+    DropCollection {
+        collection_name: String,
+    },
+    CreateIndex {
+        collection_name: String,
+        index: Box<IndexModel>,
+    },
+    DropIndex {
+        collection_name: String,
+        index_name: String,
+    },
+    /// Replaces the collection's validator. An empty validator removes it.
+    SetValidator {
+        collection_name: String,
+        validator: Document,
+        /// The validator to restore when migrating down.
+        previous: Document,
+    },
+    Backfill(Backfill),
+}
+
+impl SchemaChange {
+    fn create_collection(collection_prefix: &str, name: &str) -> Self {
+        SchemaChange::CreateCollection {
+            collection_name: collection_name(collection_prefix, name),
+        }
+    }
+
+    fn create_index(collection_prefix: &str, name: &str, index: IndexModel) -> Self {
+        SchemaChange::CreateIndex {
+            collection_name: collection_name(collection_prefix, name),
+            index: Box::new(index),
+        }
+    }
+
+    fn set_validator(
+        collection_prefix: &str,
+        name: &str,
+        schema: &JsonSchema,
+        previous: Option<&JsonSchema>,
+    ) -> Self {
+        SchemaChange::SetValidator {
+            collection_name: collection_name(collection_prefix, name),
+            validator: schema.validator(),
+            previous: previous.map(JsonSchema::validator).unwrap_or_default(),
+        }
+    }
+
+    /// The change that undoes this one.
+    fn reverse(&self) -> Result<SchemaChange> {
+        Ok(match self {
+            SchemaChange::CreateCollection { collection_name } => SchemaChange::DropCollection {
+                collection_name: collection_name.clone(),
+            },
+            SchemaChange::CreateIndex {
+                collection_name,
+                index,
+            } => SchemaChange::DropIndex {
+                collection_name: collection_name.clone(),
+                index_name: index_name(index)?,
+            },
+            SchemaChange::SetValidator {
+                collection_name,
+                validator,
+                previous,
+            } => SchemaChange::SetValidator {
+                collection_name: collection_name.clone(),
+                validator: previous.clone(),
+                previous: validator.clone(),
+            },
+            SchemaChange::Backfill(backfill) => match &backfill.reverse {
+                Some(reverse) => SchemaChange::Backfill(reverse.as_ref().clone()),
+                None => bail!("cannot reverse {}", self),
+            },
+            SchemaChange::DropCollection { .. } | SchemaChange::DropIndex { .. } => {
+                bail!("cannot reverse {}", self)
+            }
+        })
+    }
+
+    /// Applies the change. Every change can be applied again after it
+    /// completed, so an interrupted migration can resume with the change it
+    /// was working on.
+    async fn apply<N: Namespace>(
+        &self,
+        namespace: &N,
+        options: &MigrationOptions,
+        resume_after: Option<Bson>,
+        progress: &StepProgress<'_, N>,
+    ) -> Result<()> {
+        let db = namespace.database();
+        match self {
+            SchemaChange::CreateCollection { collection_name } => {
+                let result = db.create_collection(collection_name).await;
+                ignore_command_error(result, NAMESPACE_EXISTS_CODE)?;
+            }
+            SchemaChange::DropCollection { collection_name } => {
+                db.collection::<Document>(collection_name).drop().await?;
+            }
+            SchemaChange::CreateIndex {
+                collection_name,
+                index,
+            } => {
+                db.collection::<Document>(collection_name)
+                    .create_index(index.as_ref().clone())
+                    .await?;
+            }
+            SchemaChange::DropIndex {
+                collection_name,
+                index_name,
+            } => {
+                let result = db
+                    .collection::<Document>(collection_name)
+                    .drop_index(index_name)
+                    .await;
+                ignore_command_error(result, INDEX_NOT_FOUND_CODE)?;
+            }
+            SchemaChange::SetValidator {
+                collection_name,
+                validator,
+                ..
+            } => {
+                db.run_command(doc! { "collMod": collection_name, "validator": validator })
+                    .await?;
+            }
+            SchemaChange::Backfill(backfill) => {
+                backfill
+                    .run(namespace, options.batch_size, resume_after, progress)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaChange::CreateCollection { collection_name } => {
+                write!(f, "create collection {}", collection_name)
+            }
+            SchemaChange::DropCollection { collection_name } => {
+                write!(f, "drop collection {}", collection_name)
+            }
+            SchemaChange::CreateIndex {
+                collection_name,
+                index,
+            } => {
+                let unique = index
+                    .options
+                    .as_ref()
+                    .and_then(|o| o.unique)
+                    .unwrap_or(false);
+                write!(
+                    f,
+                    "create {}index {} on {} {}",
+                    if unique { "unique " } else { "" },
+                    index_name(index).unwrap_or_default(),
+                    collection_name,
+                    index.keys
+                )
+            }
+            SchemaChange::DropIndex {
+                collection_name,
+                index_name,
+            } => write!(f, "drop index {} on {}", index_name, collection_name),
+            SchemaChange::SetValidator {
+                collection_name,
+                validator,
+                ..
+            } if validator.is_empty() => write!(f, "remove validator on {}", collection_name),
+            SchemaChange::SetValidator {
+                collection_name, ..
+            } => write!(f, "set validator on {}", collection_name),
+            SchemaChange::Backfill(backfill) => write!(
+                f,
+                "backfill {} on {} where {}",
+                backfill.description, backfill.collection_name, backfill.filter
+            ),
+        }
+    }
+}
+
+/// Treats the server command error `code` as success.
+fn ignore_command_error(result: mongodb::error::Result<()>, code: i32) -> Result<()> {
+    match result {
+        Err(error) if matches!(*error.kind, ErrorKind::Command(ref e) if e.code == code) => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn index_name(index: &IndexModel) -> Result<String> {
+    match index.options.as_ref().and_then(|o| o.name.clone()) {
+        Some(name) => Ok(name),
+        None => bail!("index on {} has no name", index.keys),
+    }
+}
+
+trait Migration: Send + Sync {
+    /// Key of the migration's record in the migrations collection.
+    fn id(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// The changes made when migrating up, in order. Migrating down applies
+    /// their reversals in the opposite order.
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange>;
+}
+
+fn collection_name(collection_prefix: &str, name: &str) -> String {
+    format!("{}{}", collection_prefix, name)
+}
 
 fn get_migrations() -> Vec<Box<dyn Migration>> {
     vec![
@@ -13,24 +279,387 @@ fn get_migrations() -> Vec<Box<dyn Migration>> {
         Box::new(v002_add_security::Migration002 {}),
         Box::new(v003_add_trade_executions::Migration003 {}),
         Box::new(v004_add_eod_summary::Migration004 {}),
+        Box::new(v005_add_owners::Migration005 {}),
+        Box::new(v006_add_validators::Migration006 {}),
+        Box::new(v007_add_schema_versions::Migration007 {}),
+        Box::new(v008_add_resume_tokens::Migration008 {}),
+        Box::new(v009_add_orders::Migration009 {}),
+        Box::new(v010_add_price_bars::Migration010 {}),
+        Box::new(v011_add_execution_fees::Migration011 {}),
+        Box::new(v012_add_execution_routing::Migration012 {}),
+        Box::new(v013_add_tags::Migration013 {}),
+        Box::new(v014_add_cash_items::Migration014 {}),
+        Box::new(v015_add_option_events::Migration015 {}),
+        Box::new(v016_add_dividends::Migration016 {}),
+        Box::new(v017_add_foreign_withholding::Migration017 {}),
     ]
 }
 
-pub async fn run_migrations(db: &Database) -> Result<()> {
-    DefaultMigrator::new()
-        .with_conn(db.clone())
-        .with_migrations_vec(get_migrations())
-        .up()
+/// The version a fully migrated database is at.
+pub fn latest_version() -> u32 {
+    get_migrations().len() as u32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum RecordStatus {
+    InProgress,
+    Success,
+    Fail,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MigrationRecord {
+    _id: String,
+    status: RecordStatus,
+    /// Progress of an unfinished migration.
+    #[serde(default)]
+    direction: Option<Direction>,
+    #[serde(default)]
+    completed_changes: usize,
+    /// Last `_id` updated by the backfill at `completed_changes`.
+    #[serde(default)]
+    resume_after: Option<Bson>,
+}
+
+/// A known migration and whether it is applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MigrationInfo {
+    pub version: u32,
+    pub id: &'static str,
+    pub description: &'static str,
+    pub applied: bool,
+}
+
+/// Reports every known migration, oldest first.
+pub async fn status(db: &impl Namespace) -> Result<Vec<MigrationInfo>> {
+    let records: Vec<MigrationRecord> = db
+        .collection::<MigrationRecord>(MIGRATIONS_COLLECTION_NAME)
+        .find(doc! {})
+        .await?
+        .try_collect()
         .await?;
+
+    Ok(get_migrations()
+        .iter()
+        .enumerate()
+        .map(|(i, m)| MigrationInfo {
+            version: i as u32 + 1,
+            id: m.id(),
+            description: m.description(),
+            applied: records
+                .iter()
+                .any(|r| r._id == m.id() && r.status == RecordStatus::Success),
+        })
+        .collect())
+}
+
+/// The highest version up to which every migration is applied, 0 for an
+/// uninitialized namespace.
+pub async fn current_version(db: &impl Namespace) -> Result<u32> {
+    Ok(status(db)
+        .await?
+        .iter()
+        .take_while(|m| m.applied)
+        .last()
+        .map_or(0, |m| m.version))
+}
+
+/// Migrations not yet applied, oldest first.
+pub async fn pending(db: &impl Namespace) -> Result<Vec<MigrationInfo>> {
+    Ok(status(db)
+        .await?
+        .into_iter()
+        .filter(|m| !m.applied)
+        .collect())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// One migration to run and the changes it makes.
+#[derive(Clone, Debug)]
+pub struct PlannedMigration {
+    pub migration: MigrationInfo,
+    pub direction: Direction,
+    pub changes: Vec<SchemaChange>,
+}
+
+/// The migrations that bring a namespace to a target version. Its `Display`
+/// output is the dry run report.
+#[derive(Clone, Debug)]
+pub struct MigrationPlan {
+    pub from_version: u32,
+    pub target_version: u32,
+    pub steps: Vec<PlannedMigration>,
+}
+
+impl MigrationPlan {
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "migrate from version {} to {}",
+            self.from_version, self.target_version
+        )?;
+        for step in &self.steps {
+            writeln!(
+                f,
+                "{} {} ({}): {}",
+                match step.direction {
+                    Direction::Up => "up",
+                    Direction::Down => "down",
+                },
+                step.migration.version,
+                step.migration.id,
+                step.migration.description
+            )?;
+            for change in &step.changes {
+                writeln!(f, "  {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Works out the migrations that bring the namespace to `target_version`
+/// without changing anything. Going up applies every pending migration up to
+/// the target; going down reverts every applied migration above it, newest
+/// first.
+pub async fn plan(db: &impl Namespace, target_version: u32) -> Result<MigrationPlan> {
+    let latest = latest_version();
+    if target_version > latest {
+        bail!(
+            "target version {} is newer than the latest version {}",
+            target_version,
+            latest
+        );
+    }
+
+    let migrations = get_migrations();
+    let status = status(db).await?;
+    let from_version = status
+        .iter()
+        .take_while(|m| m.applied)
+        .last()
+        .map_or(0, |m| m.version);
+
+    let mut steps = Vec::new();
+    for (migration, info) in migrations.iter().zip(&status) {
+        if info.version <= target_version && !info.applied {
+            steps.push(PlannedMigration {
+                migration: info.clone(),
+                direction: Direction::Up,
+                changes: migration.changes(db.collection_prefix()),
+            });
+        }
+    }
+    for (migration, info) in migrations.iter().zip(&status).rev() {
+        if info.version > target_version && info.applied {
+            let changes = migration
+                .changes(db.collection_prefix())
+                .iter()
+                .rev()
+                .map(SchemaChange::reverse)
+                .collect::<Result<_>>()?;
+            steps.push(PlannedMigration {
+                migration: info.clone(),
+                direction: Direction::Down,
+                changes,
+            });
+        }
+    }
+
+    Ok(MigrationPlan {
+        from_version,
+        target_version,
+        steps,
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct MigrationOptions {
+    /// Drop collections even if they still hold documents.
+    pub force: bool,
+    /// Documents updated per backfill batch.
+    pub batch_size: u32,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            force: false,
+            batch_size: 1000,
+        }
+    }
+}
+
+/// Migrates the namespace up or down to `target_version` with the default
+/// options and returns what was done.
+pub async fn migrate_to(db: &impl Namespace, target_version: u32) -> Result<MigrationPlan> {
+    migrate_to_with_options(db, target_version, &MigrationOptions::default()).await
+}
+
+/// Migrates the namespace to `target_version`, resuming a migration that was
+/// interrupted in the same direction.
+pub async fn migrate_to_with_options(
+    db: &impl Namespace,
+    target_version: u32,
+    options: &MigrationOptions,
+) -> Result<MigrationPlan> {
+    let plan = plan(db, target_version).await?;
+    if !options.force {
+        check_dropped_collections_empty(db, &plan).await?;
+    }
+
+    let records = db.collection::<MigrationRecord>(MIGRATIONS_COLLECTION_NAME);
+    for step in &plan.steps {
+        let id = step.migration.id;
+        let (completed_changes, mut resume_after) =
+            match records.find_one(doc! { "_id": id }).await? {
+                Some(record)
+                    if record.status != RecordStatus::Success
+                        && record.direction == Some(step.direction) =>
+                {
+                    (record.completed_changes, record.resume_after)
+                }
+                _ => (0, None),
+            };
+        tracing::info!(
+            "migrating {:?} {} ({}) from change {}",
+            step.direction,
+            step.migration.version,
+            id,
+            completed_changes
+        );
+
+        for (index, change) in step.changes.iter().enumerate().skip(completed_changes) {
+            let progress = StepProgress {
+                db,
+                id,
+                direction: step.direction,
+                change_index: index,
+            };
+            progress.save(resume_after.clone()).await?;
+
+            if let Err(error) = change
+                .apply(db, options, resume_after.take(), &progress)
+                .await
+            {
+                set_status(db, id, RecordStatus::Fail).await?;
+                return Err(error.context(format!("migration {} failed at: {}", id, change)));
+            }
+        }
+
+        match step.direction {
+            Direction::Up => {
+                records
+                    .replace_one(
+                        doc! { "_id": id },
+                        MigrationRecord {
+                            _id: id.to_owned(),
+                            status: RecordStatus::Success,
+                            direction: None,
+                            completed_changes: 0,
+                            resume_after: None,
+                        },
+                    )
+                    .upsert(true)
+                    .await?;
+            }
+            Direction::Down => {
+                records.delete_one(doc! { "_id": id }).await?;
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Fails before changing anything if the plan would drop documents.
+async fn check_dropped_collections_empty(db: &impl Namespace, plan: &MigrationPlan) -> Result<()> {
+    let mut non_empty = Vec::new();
+    for change in plan.steps.iter().flat_map(|s| &s.changes) {
+        if let SchemaChange::DropCollection { collection_name } = change {
+            let count = db
+                .database()
+                .collection::<Document>(collection_name)
+                .count_documents(doc! {})
+                .await?;
+            if count > 0 {
+                non_empty.push(format!("{} ({} documents)", collection_name, count));
+            }
+        }
+    }
+    if !non_empty.is_empty() {
+        bail!(
+            "refusing to drop non-empty collections without force: {}",
+            non_empty.join(", ")
+        );
+    }
     Ok(())
 }
 
-pub async fn run_down_migrations(db: &Database) -> Result<()> {
-    DefaultMigrator::new()
-        .with_conn(db.clone())
-        .with_migrations_vec(get_migrations())
-        .down()
+/// Where an unfinished migration stands, saved to its record.
+struct StepProgress<'a, N: Namespace> {
+    db: &'a N,
+    id: &'a str,
+    direction: Direction,
+    change_index: usize,
+}
+
+impl<N: Namespace> StepProgress<'_, N> {
+    async fn save(&self, resume_after: Option<Bson>) -> Result<()> {
+        self.db
+            .collection::<MigrationRecord>(MIGRATIONS_COLLECTION_NAME)
+            .update_one(
+                doc! { "_id": self.id },
+                doc! { "$set": {
+                    "status": bson::to_bson(&RecordStatus::InProgress)?,
+                    "direction": bson::to_bson(&self.direction)?,
+                    "completed_changes": self.change_index as i64,
+                    "resume_after": resume_after,
+                } },
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+}
+
+async fn set_status(db: &impl Namespace, id: &str, status: RecordStatus) -> Result<()> {
+    db.collection::<MigrationRecord>(MIGRATIONS_COLLECTION_NAME)
+        .update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "status": bson::to_bson(&status)? } },
+        )
+        .upsert(true)
         .await?;
+    Ok(())
+}
+
+pub(crate) async fn run_migrations(db: &impl Namespace) -> Result<()> {
+    migrate_to(db, latest_version()).await?;
+    Ok(())
+}
 
+/// Reverts every migration, dropping the collections with their documents.
+pub(crate) async fn run_down_migrations(db: &impl Namespace) -> Result<()> {
+    migrate_to_with_options(
+        db,
+        0,
+        &MigrationOptions {
+            force: true,
+            ..MigrationOptions::default()
+        },
+    )
+    .await?;
     Ok(())
 }
@@ -0,0 +1,40 @@
+use crate::{account::BrokerageAccount, owner::Owner};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{Migration, SchemaChange};
+
+pub struct Migration005 {}
+
+const BROKERAGE_ACCOUNTS_BY_OWNER_INDEX_NAME: &str = "brokerage_accounts_by_owner_idx";
+
+impl Migration for Migration005 {
+    fn id(&self) -> &'static str {
+        "Migration005"
+    }
+
+    fn description(&self) -> &'static str {
+        "add owners"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the owners collection and index accounts by owner.
+        //
+        vec![
+            SchemaChange::create_collection(collection_prefix, Owner::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                BrokerageAccount::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "owner_id": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(BROKERAGE_ACCOUNTS_BY_OWNER_INDEX_NAME.to_owned()))
+                            .build(),
+                    )
+                    .build(),
+            ),
+        ]
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    account::BrokerageAccount, eod_summary::EODSummary, owner::Owner, security::Security,
+    trade_execution::TradeExecution,
+};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, number, object_id, one_of, string},
+};
+
+pub struct Migration006 {}
+
+pub(super) fn owners_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required("name", string())
+        .required("owner_type", one_of(&["Person", "Entity"]))
+}
+
+pub(super) fn brokerage_accounts_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required("brokerage_id", string())
+        .required("account_id", string())
+        .optional("owner_id", object_id())
+        .optional(
+            "account_type",
+            one_of(&[
+                "Individual",
+                "Joint",
+                "TraditionalIra",
+                "RothIra",
+                "Corporate",
+            ]),
+        )
+        .optional(
+            "tax_treatment",
+            one_of(&["Taxable", "TaxDeferred", "TaxExempt"]),
+        )
+        .optional("display_name", string())
+        .optional("opened_timestamp_ms", integer())
+        .optional("closed_timestamp_ms", integer())
+}
+
+pub(super) fn securities_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required("listing_exchange", string())
+        .required("security_type", one_of(&["Stock"]))
+        .required("ticker", string())
+        .optional("ibkr_conid", integer())
+}
+
+pub(super) fn trade_executions_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required("brokerage_account_id", object_id())
+        .required("brokerage_execution_id", string())
+        .required("commission", number())
+        .required("execution_timestamp_ms", integer())
+        .required("quantity", number())
+        .required("price", number())
+        .required("security_id", object_id())
+        .required("side", one_of(&["Buy", "Sell"]))
+}
+
+pub(super) fn eod_summaries_schema() -> JsonSchema {
+    let mut schema = JsonSchema::new()
+        .required("brokerage_account_id", object_id())
+        .required("start_timestamp_ms", integer())
+        .required("end_timestamp_ms", integer())
+        .required("starting_cash", number())
+        .required("ending_cash", number())
+        .required("net_trade_purchases", number())
+        .required("net_trade_sales", number());
+    for field in [
+        "commissions",
+        "deposits",
+        "dividends",
+        "interest",
+        "other_fees",
+        "withdrawals",
+    ] {
+        schema = schema
+            .required(field, number())
+            .optional(&format!("{}_mtd", field), number())
+            .optional(&format!("{}_ytd", field), number());
+    }
+    schema
+}
+
+impl Migration for Migration006 {
+    fn id(&self) -> &'static str {
+        "Migration006"
+    }
+
+    fn description(&self) -> &'static str {
+        "add collection validators"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Reject documents that the entity structs could not deserialize.
+        //
+        [
+            (Owner::COLLECTION_NAME, owners_schema()),
+            (
+                BrokerageAccount::COLLECTION_NAME,
+                brokerage_accounts_schema(),
+            ),
+            (Security::COLLECTION_NAME, securities_schema()),
+            (TradeExecution::COLLECTION_NAME, trade_executions_schema()),
+            (EODSummary::COLLECTION_NAME, eod_summaries_schema()),
+        ]
+        .into_iter()
+        .map(|(name, schema)| SchemaChange::set_validator(collection_prefix, name, &schema, None))
+        .collect()
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    account::BrokerageAccount, eod_summary::EODSummary, owner::Owner, security::Security,
+    trade_execution::TradeExecution, versioned::SCHEMA_VERSION_FIELD,
+};
+use bson::doc;
+
+use super::{
+    Backfill, Migration, SchemaChange, collection_name,
+    json_schema::{JsonSchema, integer},
+    v006_add_validators,
+};
+
+pub struct Migration007 {}
+
+fn with_schema_version(schema: JsonSchema) -> JsonSchema {
+    schema.required(SCHEMA_VERSION_FIELD, integer())
+}
+
+pub(super) fn owners_schema() -> JsonSchema {
+    with_schema_version(v006_add_validators::owners_schema())
+}
+
+pub(super) fn brokerage_accounts_schema() -> JsonSchema {
+    with_schema_version(v006_add_validators::brokerage_accounts_schema())
+}
+
+pub(super) fn securities_schema() -> JsonSchema {
+    with_schema_version(v006_add_validators::securities_schema())
+}
+
+pub(super) fn trade_executions_schema() -> JsonSchema {
+    with_schema_version(v006_add_validators::trade_executions_schema())
+}
+
+pub(super) fn eod_summaries_schema() -> JsonSchema {
+    with_schema_version(v006_add_validators::eod_summaries_schema())
+}
+
+impl Migration for Migration007 {
+    fn id(&self) -> &'static str {
+        "Migration007"
+    }
+
+    fn description(&self) -> &'static str {
+        "add document schema versions"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Stamp existing documents as version 1, then require the version.
+        //
+        [
+            (
+                Owner::COLLECTION_NAME,
+                owners_schema(),
+                v006_add_validators::owners_schema(),
+            ),
+            (
+                BrokerageAccount::COLLECTION_NAME,
+                brokerage_accounts_schema(),
+                v006_add_validators::brokerage_accounts_schema(),
+            ),
+            (
+                Security::COLLECTION_NAME,
+                securities_schema(),
+                v006_add_validators::securities_schema(),
+            ),
+            (
+                TradeExecution::COLLECTION_NAME,
+                trade_executions_schema(),
+                v006_add_validators::trade_executions_schema(),
+            ),
+            (
+                EODSummary::COLLECTION_NAME,
+                eod_summaries_schema(),
+                v006_add_validators::eod_summaries_schema(),
+            ),
+        ]
+        .into_iter()
+        .flat_map(|(name, schema, previous)| {
+            let backfill = Backfill::new(
+                &collection_name(collection_prefix, name),
+                &format!("stamp {} with {}", name, SCHEMA_VERSION_FIELD),
+                doc! { SCHEMA_VERSION_FIELD: { "$exists": false } },
+                vec![doc! { "$set": { SCHEMA_VERSION_FIELD: 1 } }],
+            )
+            .with_reverse(
+                &format!("remove {} from {}", SCHEMA_VERSION_FIELD, name),
+                doc! { SCHEMA_VERSION_FIELD: { "$exists": true } },
+                vec![doc! { "$unset": SCHEMA_VERSION_FIELD }],
+            );
+            [
+                SchemaChange::Backfill(backfill),
+                SchemaChange::set_validator(collection_prefix, name, &schema, Some(&previous)),
+            ]
+        })
+        .collect()
+    }
+}
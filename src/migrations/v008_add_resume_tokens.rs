@@ -0,0 +1,25 @@
+use crate::subscription::RESUME_TOKENS_COLLECTION_NAME;
+
+use super::{Migration, SchemaChange};
+
+pub struct Migration008 {}
+
+impl Migration for Migration008 {
+    fn id(&self) -> &'static str {
+        "Migration008"
+    }
+
+    fn description(&self) -> &'static str {
+        "add subscription resume tokens"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the collection holding each consumer's change stream position.
+        //
+        vec![SchemaChange::create_collection(
+            collection_prefix,
+            RESUME_TOKENS_COLLECTION_NAME,
+        )]
+    }
+}
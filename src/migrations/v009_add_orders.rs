@@ -0,0 +1,133 @@
+use crate::{order::Order, trade_execution::TradeExecution, versioned::SCHEMA_VERSION_FIELD};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, number, object_id, one_of, string},
+    v007_add_schema_versions,
+};
+
+pub struct Migration009 {}
+
+const ORDERS_UNIQUE_INDEX_NAME: &str = "orders_unique_idx";
+const ORDERS_BY_ACCOUNT_SUBMITTED_INDEX_NAME: &str = "orders_by_account_submitted_idx";
+const TRADE_EXECUTIONS_BY_ORDER_INDEX_NAME: &str = "trade_executions_by_order_idx";
+
+pub(super) fn orders_schema() -> JsonSchema {
+    let status = one_of(&[
+        "Submitted",
+        "PartiallyFilled",
+        "Filled",
+        "Cancelled",
+        "Rejected",
+    ]);
+    JsonSchema::new()
+        .required(SCHEMA_VERSION_FIELD, integer())
+        .required("brokerage_account_id", object_id())
+        .required("brokerage_order_id", string())
+        .required("security_id", object_id())
+        .required("side", one_of(&["Buy", "Sell"]))
+        .required(
+            "order_type",
+            one_of(&["Market", "Limit", "Stop", "StopLimit"]),
+        )
+        .required("quantity", number())
+        .optional("limit_price", number())
+        .optional("stop_price", number())
+        .required(
+            "time_in_force",
+            one_of(&[
+                "Day",
+                "GoodTillCancelled",
+                "ImmediateOrCancel",
+                "FillOrKill",
+            ]),
+        )
+        .required(
+            "status_history",
+            doc! {
+                "bsonType": "array",
+                "items": {
+                    "bsonType": "object",
+                    "required": ["status", "timestamp_ms"],
+                    "properties": { "status": status, "timestamp_ms": integer() },
+                },
+            },
+        )
+        .required("submitted_timestamp_ms", integer())
+        .optional("cancelled_timestamp_ms", integer())
+}
+
+pub(super) fn trade_executions_schema() -> JsonSchema {
+    v007_add_schema_versions::trade_executions_schema().optional("order_id", object_id())
+}
+
+impl Migration for Migration009 {
+    fn id(&self) -> &'static str {
+        "Migration009"
+    }
+
+    fn description(&self) -> &'static str {
+        "add orders"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the orders collection and link executions to their order.
+        //
+        vec![
+            SchemaChange::create_collection(collection_prefix, Order::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                Order::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "brokerage_account_id": 1, "brokerage_order_id": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(ORDERS_UNIQUE_INDEX_NAME.to_owned()))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::create_index(
+                collection_prefix,
+                Order::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "brokerage_account_id": 1, "submitted_timestamp_ms": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(ORDERS_BY_ACCOUNT_SUBMITTED_INDEX_NAME.to_owned()))
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                Order::COLLECTION_NAME,
+                &orders_schema(),
+                None,
+            ),
+            SchemaChange::create_index(
+                collection_prefix,
+                TradeExecution::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "order_id": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(TRADE_EXECUTIONS_BY_ORDER_INDEX_NAME.to_owned()))
+                            .sparse(true)
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                TradeExecution::COLLECTION_NAME,
+                &trade_executions_schema(),
+                Some(&v007_add_schema_versions::trade_executions_schema()),
+            ),
+        ]
+    }
+}
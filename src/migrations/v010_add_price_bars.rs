@@ -0,0 +1,64 @@
+use crate::{price_bar::PriceBar, versioned::SCHEMA_VERSION_FIELD};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, number, object_id},
+};
+
+pub struct Migration010 {}
+
+const PRICE_BARS_UNIQUE_INDEX_NAME: &str = "price_bars_unique_idx";
+
+pub(super) fn price_bars_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required(SCHEMA_VERSION_FIELD, integer())
+        .required("security_id", object_id())
+        .required("start_timestamp_ms", integer())
+        .required("duration_ms", integer())
+        .required("open", number())
+        .required("high", number())
+        .required("low", number())
+        .required("close", number())
+        .required("volume", number())
+}
+
+impl Migration for Migration010 {
+    fn id(&self) -> &'static str {
+        "Migration010"
+    }
+
+    fn description(&self) -> &'static str {
+        "add price bars"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the price bars collection, one bar per security, duration and
+        // start.
+        //
+        vec![
+            SchemaChange::create_collection(collection_prefix, PriceBar::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                PriceBar::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "security_id": 1, "duration_ms": 1, "start_timestamp_ms": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(PRICE_BARS_UNIQUE_INDEX_NAME.to_owned()))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                PriceBar::COLLECTION_NAME,
+                &price_bars_schema(),
+                None,
+            ),
+        ]
+    }
+}
@@ -0,0 +1,56 @@
+use crate::trade_execution::TradeExecution;
+use bson::doc;
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, number, one_of},
+    v009_add_orders,
+};
+
+pub struct Migration011 {}
+
+pub(super) fn trade_executions_schema() -> JsonSchema {
+    let category = one_of(&[
+        "Commission",
+        "Exchange",
+        "Sec",
+        "FinraTaf",
+        "Clearing",
+        "EcnRebate",
+        "Tax",
+        "Other",
+    ]);
+    v009_add_orders::trade_executions_schema().optional(
+        "fees",
+        doc! {
+            "bsonType": "array",
+            "items": {
+                "bsonType": "object",
+                "required": ["category", "amount"],
+                "properties": { "category": category, "amount": number() },
+            },
+        },
+    )
+}
+
+impl Migration for Migration011 {
+    fn id(&self) -> &'static str {
+        "Migration011"
+    }
+
+    fn description(&self) -> &'static str {
+        "add itemized execution fees"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Allow executions to carry their fee breakdown.
+        //
+        vec![SchemaChange::set_validator(
+            collection_prefix,
+            TradeExecution::COLLECTION_NAME,
+            &trade_executions_schema(),
+            Some(&v009_add_orders::trade_executions_schema()),
+        )]
+    }
+}
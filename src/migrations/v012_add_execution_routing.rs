@@ -0,0 +1,65 @@
+use crate::trade_execution::TradeExecution;
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, one_of, string},
+    v011_add_execution_fees,
+};
+
+pub struct Migration012 {}
+
+pub(super) fn trade_executions_schema() -> JsonSchema {
+    v011_add_execution_fees::trade_executions_schema()
+        .optional("venue", string())
+        .optional("liquidity", one_of(&["Added", "Removed"]))
+        .optional("order_reference", string())
+        .optional("strategy_tag", string())
+        .optional("position_effect", one_of(&["Open", "Close"]))
+}
+
+fn by_account_index(field: &str) -> IndexModel {
+    IndexModel::builder()
+        .keys(doc! { "brokerage_account_id": 1, field: 1, "execution_timestamp_ms": 1 })
+        .options(
+            IndexOptions::builder()
+                .name(Some(format!("trade_executions_by_account_{}_idx", field)))
+                .build(),
+        )
+        .build()
+}
+
+impl Migration for Migration012 {
+    fn id(&self) -> &'static str {
+        "Migration012"
+    }
+
+    fn description(&self) -> &'static str {
+        "add execution routing metadata"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Allow the routing metadata and index the fields executions are
+        // looked up by.
+        //
+        let mut changes: Vec<SchemaChange> = ["venue", "strategy_tag", "order_reference"]
+            .into_iter()
+            .map(|field| {
+                SchemaChange::create_index(
+                    collection_prefix,
+                    TradeExecution::COLLECTION_NAME,
+                    by_account_index(field),
+                )
+            })
+            .collect();
+        changes.push(SchemaChange::set_validator(
+            collection_prefix,
+            TradeExecution::COLLECTION_NAME,
+            &trade_executions_schema(),
+            Some(&v011_add_execution_fees::trade_executions_schema()),
+        ));
+        changes
+    }
+}
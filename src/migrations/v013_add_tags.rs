@@ -0,0 +1,84 @@
+use crate::{tag::Tag, trade_execution::TradeExecution, versioned::SCHEMA_VERSION_FIELD};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, object_id, one_of, string},
+    v012_add_execution_routing,
+};
+
+pub struct Migration013 {}
+
+const TAGS_UNIQUE_INDEX_NAME: &str = "tags_unique_idx";
+
+pub(super) fn tags_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required(SCHEMA_VERSION_FIELD, integer())
+        .required("name", string())
+        .required("kind", one_of(&["Strategy", "Label"]))
+}
+
+pub(super) fn trade_executions_schema() -> JsonSchema {
+    v012_add_execution_routing::trade_executions_schema().optional(
+        "tag_ids",
+        doc! { "bsonType": "array", "items": object_id() },
+    )
+}
+
+impl Migration for Migration013 {
+    fn id(&self) -> &'static str {
+        "Migration013"
+    }
+
+    fn description(&self) -> &'static str {
+        "add tags"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the tags collection with unique names, and let executions
+        // reference tags.
+        //
+        vec![
+            SchemaChange::create_collection(collection_prefix, Tag::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                Tag::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "name": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(TAGS_UNIQUE_INDEX_NAME.to_owned()))
+                            .unique(true)
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                Tag::COLLECTION_NAME,
+                &tags_schema(),
+                None,
+            ),
+            SchemaChange::create_index(
+                collection_prefix,
+                TradeExecution::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "tag_ids": 1, "execution_timestamp_ms": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some("trade_executions_by_tag_idx".to_owned()))
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                TradeExecution::COLLECTION_NAME,
+                &trade_executions_schema(),
+                Some(&v012_add_execution_routing::trade_executions_schema()),
+            ),
+        ]
+    }
+}
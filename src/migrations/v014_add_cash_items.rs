@@ -0,0 +1,60 @@
+use crate::{cash_item::CashItem, versioned::SCHEMA_VERSION_FIELD};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, number, object_id, one_of, string},
+};
+
+pub struct Migration014 {}
+
+const CASH_ITEMS_BY_ACCOUNT_INDEX_NAME: &str = "cash_items_by_account_idx";
+
+pub(super) fn cash_items_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required(SCHEMA_VERSION_FIELD, integer())
+        .required("brokerage_account_id", object_id())
+        .optional("security_id", object_id())
+        .required("kind", one_of(&["BorrowFee", "ShortInterest"]))
+        .required("amount", number())
+        .required("timestamp_ms", integer())
+        .optional("description", string())
+}
+
+impl Migration for Migration014 {
+    fn id(&self) -> &'static str {
+        "Migration014"
+    }
+
+    fn description(&self) -> &'static str {
+        "add cash items"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the cash items collection, read per account and time range.
+        //
+        vec![
+            SchemaChange::create_collection(collection_prefix, CashItem::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                CashItem::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "brokerage_account_id": 1, "timestamp_ms": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(CASH_ITEMS_BY_ACCOUNT_INDEX_NAME.to_owned()))
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                CashItem::COLLECTION_NAME,
+                &cash_items_schema(),
+                None,
+            ),
+        ]
+    }
+}
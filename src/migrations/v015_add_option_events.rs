@@ -0,0 +1,94 @@
+use crate::{option_event::OptionEvent, security::Security, versioned::SCHEMA_VERSION_FIELD};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, number, object_id, one_of},
+    v007_add_schema_versions,
+};
+
+pub struct Migration015 {}
+
+const OPTION_EVENTS_BY_ACCOUNT_INDEX_NAME: &str = "option_events_by_account_idx";
+
+pub(super) fn securities_schema() -> JsonSchema {
+    v007_add_schema_versions::securities_schema()
+        .required("security_type", one_of(&["Stock", "Option"]))
+        .optional(
+            "option_contract",
+            doc! {
+                "bsonType": "object",
+                "required": [
+                    "underlying_id",
+                    "right",
+                    "strike",
+                    "expiration_timestamp_ms",
+                    "multiplier",
+                ],
+                "properties": {
+                    "underlying_id": object_id(),
+                    "right": one_of(&["Call", "Put"]),
+                    "strike": number(),
+                    "expiration_timestamp_ms": integer(),
+                    "multiplier": number(),
+                },
+            },
+        )
+}
+
+pub(super) fn option_events_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required(SCHEMA_VERSION_FIELD, integer())
+        .required("brokerage_account_id", object_id())
+        .required("security_id", object_id())
+        .required("kind", one_of(&["Exercise", "Assignment", "Expiration"]))
+        .required("contracts", number())
+        .required("timestamp_ms", integer())
+        .required("option_execution_id", object_id())
+        .optional("stock_execution_id", object_id())
+}
+
+impl Migration for Migration015 {
+    fn id(&self) -> &'static str {
+        "Migration015"
+    }
+
+    fn description(&self) -> &'static str {
+        "add options and option events"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Allow option securities, and create the collection of their
+        // exercises, assignments and expirations.
+        //
+        vec![
+            SchemaChange::set_validator(
+                collection_prefix,
+                Security::COLLECTION_NAME,
+                &securities_schema(),
+                Some(&v007_add_schema_versions::securities_schema()),
+            ),
+            SchemaChange::create_collection(collection_prefix, OptionEvent::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                OptionEvent::COLLECTION_NAME,
+                IndexModel::builder()
+                    .keys(doc! { "brokerage_account_id": 1, "timestamp_ms": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(Some(OPTION_EVENTS_BY_ACCOUNT_INDEX_NAME.to_owned()))
+                            .build(),
+                    )
+                    .build(),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                OptionEvent::COLLECTION_NAME,
+                &option_events_schema(),
+                None,
+            ),
+        ]
+    }
+}
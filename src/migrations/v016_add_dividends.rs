@@ -0,0 +1,78 @@
+use crate::{dividend::Dividend, versioned::SCHEMA_VERSION_FIELD};
+use bson::doc;
+use mongodb::{IndexModel, options::IndexOptions};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, integer, number, object_id, one_of},
+};
+
+pub struct Migration016 {}
+
+const DIVIDENDS_BY_ACCOUNT_INDEX_NAME: &str = "dividends_by_account_idx";
+const DIVIDENDS_BY_SECURITY_INDEX_NAME: &str = "dividends_by_security_idx";
+
+pub(super) fn dividends_schema() -> JsonSchema {
+    JsonSchema::new()
+        .required(SCHEMA_VERSION_FIELD, integer())
+        .required("brokerage_account_id", object_id())
+        .required("security_id", object_id())
+        .optional("declaration_timestamp_ms", integer())
+        .required("ex_timestamp_ms", integer())
+        .optional("record_timestamp_ms", integer())
+        .required("pay_timestamp_ms", integer())
+        .required("amount_per_share", number())
+        .required("quantity", number())
+        .required("withholding_tax", number())
+        .required("classification", one_of(&["Qualified", "NonQualified"]))
+        .optional("reinvestment_execution_id", object_id())
+}
+
+fn index(name: &str, keys: bson::Document) -> IndexModel {
+    IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().name(Some(name.to_owned())).build())
+        .build()
+}
+
+impl Migration for Migration016 {
+    fn id(&self) -> &'static str {
+        "Migration016"
+    }
+
+    fn description(&self) -> &'static str {
+        "add dividends"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Create the dividends collection, read per account by pay date and
+        // per security by ex-date.
+        //
+        vec![
+            SchemaChange::create_collection(collection_prefix, Dividend::COLLECTION_NAME),
+            SchemaChange::create_index(
+                collection_prefix,
+                Dividend::COLLECTION_NAME,
+                index(
+                    DIVIDENDS_BY_ACCOUNT_INDEX_NAME,
+                    doc! { "brokerage_account_id": 1, "pay_timestamp_ms": 1 },
+                ),
+            ),
+            SchemaChange::create_index(
+                collection_prefix,
+                Dividend::COLLECTION_NAME,
+                index(
+                    DIVIDENDS_BY_SECURITY_INDEX_NAME,
+                    doc! { "security_id": 1, "ex_timestamp_ms": 1 },
+                ),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                Dividend::COLLECTION_NAME,
+                &dividends_schema(),
+                None,
+            ),
+        ]
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{cash_item::CashItem, dividend::Dividend};
+use bson::{Document, doc};
+
+use super::{
+    Migration, SchemaChange,
+    json_schema::{JsonSchema, number, one_of, string},
+    v014_add_cash_items, v016_add_dividends,
+};
+
+pub struct Migration017 {}
+
+fn withholding() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["country", "amount", "reclaimable_amount", "reclaim_status"],
+        "properties": {
+            "country": string(),
+            "amount": number(),
+            "reclaimable_amount": number(),
+            "reclaim_status": one_of(&["NotApplicable", "NotFiled", "Filed", "Refunded", "Rejected"]),
+        },
+    }
+}
+
+pub(super) fn cash_items_schema() -> JsonSchema {
+    v014_add_cash_items::cash_items_schema()
+        .required("kind", one_of(&["BorrowFee", "ShortInterest", "Interest"]))
+        .optional("withholding", withholding())
+}
+
+pub(super) fn dividends_schema() -> JsonSchema {
+    v016_add_dividends::dividends_schema().optional("foreign_withholding", withholding())
+}
+
+impl Migration for Migration017 {
+    fn id(&self) -> &'static str {
+        "Migration017"
+    }
+
+    fn description(&self) -> &'static str {
+        "add foreign withholding"
+    }
+
+    fn changes(&self, collection_prefix: &str) -> Vec<SchemaChange> {
+        //
+        // Allow interest cash items, and foreign withholding on them and on
+        // dividends.
+        //
+        vec![
+            SchemaChange::set_validator(
+                collection_prefix,
+                CashItem::COLLECTION_NAME,
+                &cash_items_schema(),
+                Some(&v014_add_cash_items::cash_items_schema()),
+            ),
+            SchemaChange::set_validator(
+                collection_prefix,
+                Dividend::COLLECTION_NAME,
+                &dividends_schema(),
+                Some(&v016_add_dividends::dividends_schema()),
+            ),
+        ]
+    }
+}
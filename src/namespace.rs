@@ -0,0 +1,32 @@
+//! Placement of the brokerage collections within a database.
+//!
+//! Every query and migration resolves its collection through a [`Namespace`],
+//! so several isolated portfolios or test runs can share one database by
+//! giving each its own collection prefix. A bare [`Database`] is the namespace
+//! without a prefix.
+
+use mongodb::{Collection, Database};
+
+pub trait Namespace: Send + Sync {
+    fn database(&self) -> &Database;
+
+    /// Prepended to every collection name, including the migrations
+    /// collection.
+    fn collection_prefix(&self) -> &str {
+        ""
+    }
+
+    fn collection_name(&self, name: &str) -> String {
+        format!("{}{}", self.collection_prefix(), name)
+    }
+
+    fn collection<T: Send + Sync>(&self, name: &str) -> Collection<T> {
+        self.database().collection(&self.collection_name(name))
+    }
+}
+
+impl Namespace for Database {
+    fn database(&self) -> &Database {
+        self
+    }
+}
@@ -0,0 +1,309 @@
+//! Exercises, assignments and expirations of option positions.
+//!
+//! Recording an event closes the option contracts with a trade execution and,
+//! for an exercise or assignment, delivers the underlying with a stock
+//! execution at the strike. The option is closed at its net premium, so it
+//! realizes no gain or loss; the premium is rolled into the price of the stock
+//! execution instead, raising the strike for calls and lowering it for puts.
+//! An expiration closes the contracts at zero and realizes the premium.
+
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    db_util,
+    lot::{LotReport, OpenLot},
+    namespace::Namespace,
+    security::{OptionRight, Security},
+    subscription::Watched,
+    trade_execution::{PositionEffect, TradeExecution, TradeSide},
+    versioned::Versioned,
+};
+
+/// Contract quantities smaller than this are treated as zero.
+const CONTRACT_EPSILON: f64 = 1e-9;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionEventKind {
+    /// The holder exercised long contracts.
+    Exercise,
+    /// Short contracts were assigned.
+    Assignment,
+    /// Long or short contracts expired worthless.
+    Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OptionEvent {
+    _id: ObjectId,
+    brokerage_account_id: ObjectId,
+    /// The option security.
+    security_id: ObjectId,
+    kind: OptionEventKind,
+    contracts: f64,
+    timestamp_ms: i64,
+    /// The execution closing the contracts.
+    option_execution_id: ObjectId,
+    /// The execution delivering the underlying, unless the options expired.
+    stock_execution_id: Option<ObjectId>,
+}
+
+impl OptionEvent {
+    pub const COLLECTION_NAME: &'static str = "option_events";
+
+    /// Builds the event and the executions it implies from the account's open
+    /// lots of the option, first-in, first-out.
+    ///
+    /// Exercises need long contracts, assignments short ones, and expirations
+    /// either. `contracts` must be positive. The executions are returned
+    /// option first, then stock.
+    pub fn from_open_lots(
+        brokerage_account_id: ObjectId,
+        option: &Security,
+        kind: OptionEventKind,
+        contracts: f64,
+        timestamp_ms: i64,
+        open_lots: &[OpenLot],
+    ) -> Result<(Self, Vec<TradeExecution>)> {
+        let contract = option
+            .option_contract()
+            .ok_or_else(|| anyhow!("security {} is not an option", option.ticker()))?;
+        if contracts <= CONTRACT_EPSILON {
+            bail!("contracts must be positive, got {}", contracts);
+        }
+
+        let lots: Vec<&OpenLot> = open_lots
+            .iter()
+            .filter(|lot| {
+                lot.brokerage_account_id() == brokerage_account_id
+                    && lot.security_id() == option.id()
+            })
+            .collect();
+        let short = match lots.first() {
+            Some(lot) => lot.is_short(),
+            None => bail!("no open position in option {}", option.ticker()),
+        };
+        match (kind, short) {
+            (OptionEventKind::Exercise, true) => {
+                bail!("cannot exercise short option {}", option.ticker())
+            }
+            (OptionEventKind::Assignment, false) => {
+                bail!("cannot assign long option {}", option.ticker())
+            }
+            _ => {}
+        }
+
+        // Net premium of the first `contracts` contracts.
+        let mut remaining = contracts;
+        let mut premium = 0.0;
+        for lot in lots {
+            if remaining <= CONTRACT_EPSILON {
+                break;
+            }
+            let quantity = remaining.min(lot.quantity());
+            let amount = if short {
+                lot.proceeds()
+            } else {
+                lot.cost_basis()
+            };
+            premium += amount * quantity / lot.quantity();
+            remaining -= quantity;
+        }
+        if remaining > CONTRACT_EPSILON {
+            bail!(
+                "{} contracts of option {} exceed the open position",
+                contracts,
+                option.ticker()
+            );
+        }
+
+        let id = ObjectId::new();
+        let execution = |security_id, side, quantity, price, suffix: &str| {
+            TradeExecution::builder()
+                .brokerage_account_id(brokerage_account_id)
+                .brokerage_execution_id(&format!("{}-{}", id.to_hex(), suffix))
+                .commission(0.0)
+                .execution_timestamp_ms(timestamp_ms)
+                .quantity(quantity)
+                .price(price)
+                .security_id(security_id)
+                .side(side)
+        };
+
+        let option_price = match kind {
+            OptionEventKind::Expiration => 0.0,
+            _ => premium / contracts,
+        };
+        let option_side = if short {
+            TradeSide::Buy
+        } else {
+            TradeSide::Sell
+        };
+        let mut executions = vec![
+            execution(option.id(), option_side, contracts, option_price, "option")
+                .position_effect(PositionEffect::Close)
+                .build()?,
+        ];
+
+        if kind != OptionEventKind::Expiration {
+            // Long calls and short puts buy the underlying; long puts and
+            // short calls sell it.
+            let buys = (contract.right == OptionRight::Call) != short;
+            let premium_per_share = premium / (contracts * contract.multiplier);
+            let price = match contract.right {
+                OptionRight::Call => contract.strike + premium_per_share,
+                OptionRight::Put => contract.strike - premium_per_share,
+            };
+            let side = if buys {
+                TradeSide::Buy
+            } else {
+                TradeSide::Sell
+            };
+            executions.push(
+                execution(
+                    contract.underlying_id,
+                    side,
+                    contracts * contract.multiplier,
+                    price,
+                    "stock",
+                )
+                .build()?,
+            );
+        }
+
+        let event = Self {
+            _id: id,
+            brokerage_account_id,
+            security_id: option.id(),
+            kind,
+            contracts,
+            timestamp_ms,
+            option_execution_id: executions[0].id(),
+            stock_execution_id: executions.get(1).map(TradeExecution::id),
+        };
+        Ok((event, executions))
+    }
+
+    /// Records the event against the account's executions of the option and
+    /// inserts it along with the executions it implies.
+    ///
+    /// Pass a transaction's session to insert them atomically.
+    pub async fn record(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        option: &Security,
+        kind: OptionEventKind,
+        contracts: f64,
+        timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<(Self, Vec<TradeExecution>)> {
+        let executions: Vec<TradeExecution> = TradeExecution::find_by_account_id_and_range(
+            db,
+            brokerage_account_id,
+            i64::MIN,
+            timestamp_ms,
+            session.clone(),
+        )
+        .await?
+        .into_iter()
+        .filter(|e| e.security_id() == option.id())
+        .collect();
+        let lots = LotReport::from_executions(&executions)?;
+
+        let (event, executions) = Self::from_open_lots(
+            brokerage_account_id,
+            option,
+            kind,
+            contracts,
+            timestamp_ms,
+            lots.open_lots(),
+        )?;
+        for execution in &executions {
+            execution.insert(db, session.clone()).await?;
+        }
+        db_util::insert(&event, db, Self::COLLECTION_NAME, session).await?;
+
+        Ok((event, executions))
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    pub fn kind(&self) -> OptionEventKind {
+        self.kind
+    }
+
+    pub fn contracts(&self) -> f64 {
+        self.contracts
+    }
+
+    pub fn timestamp_ms(&self) -> i64 {
+        self.timestamp_ms
+    }
+
+    pub fn option_execution_id(&self) -> ObjectId {
+        self.option_execution_id
+    }
+
+    pub fn stock_execution_id(&self) -> Option<ObjectId> {
+        self.stock_execution_id
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    /// Returns the account's events within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+}
+
+impl Versioned for OptionEvent {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for OptionEvent {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+
+    fn account_field() -> Option<&'static str> {
+        Some("brokerage_account_id")
+    }
+}
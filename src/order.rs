@@ -0,0 +1,521 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    account::BrokerageAccount,
+    db_util,
+    namespace::Namespace,
+    security::Security,
+    subscription::Watched,
+    trade_execution::{TradeExecution, TradeSide},
+    versioned::Versioned,
+};
+
+/// Fills smaller than this are treated as complete.
+const FILL_EPSILON: f64 = 1e-9;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum OrderType {
+    Market,
+    Limit,
+    Stop,
+    StopLimit,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TimeInForce {
+    Day,
+    GoodTillCancelled,
+    ImmediateOrCancel,
+    FillOrKill,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum OrderStatus {
+    Submitted,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OrderStatus {
+    /// True once the order can no longer fill.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Rejected
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OrderStatusChange {
+    pub status: OrderStatus,
+    pub timestamp_ms: i64,
+}
+
+/// An order placed with a brokerage, which is filled by zero or more
+/// [`TradeExecution`]s.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Order {
+    _id: ObjectId,
+    brokerage_account_id: ObjectId,
+    brokerage_order_id: String,
+    security_id: ObjectId,
+    side: TradeSide,
+    order_type: OrderType,
+    quantity: f64,
+    limit_price: Option<f64>,
+    stop_price: Option<f64>,
+    time_in_force: TimeInForce,
+    /// Oldest first. Starts with the submission.
+    status_history: Vec<OrderStatusChange>,
+    submitted_timestamp_ms: i64,
+    cancelled_timestamp_ms: Option<i64>,
+}
+
+impl Order {
+    pub const COLLECTION_NAME: &'static str = "orders";
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn brokerage_order_id(&self) -> &str {
+        &self.brokerage_order_id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    pub fn side(&self) -> &TradeSide {
+        &self.side
+    }
+
+    pub fn order_type(&self) -> OrderType {
+        self.order_type
+    }
+
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    pub fn limit_price(&self) -> Option<f64> {
+        self.limit_price
+    }
+
+    pub fn stop_price(&self) -> Option<f64> {
+        self.stop_price
+    }
+
+    pub fn time_in_force(&self) -> TimeInForce {
+        self.time_in_force
+    }
+
+    pub fn status_history(&self) -> &[OrderStatusChange] {
+        &self.status_history
+    }
+
+    /// The latest status.
+    pub fn status(&self) -> OrderStatus {
+        self.status_history
+            .last()
+            .map(|change| change.status)
+            .unwrap_or(OrderStatus::Submitted)
+    }
+
+    pub fn submitted_timestamp_ms(&self) -> i64 {
+        self.submitted_timestamp_ms
+    }
+
+    pub fn cancelled_timestamp_ms(&self) -> Option<i64> {
+        self.cancelled_timestamp_ms
+    }
+
+    pub async fn insert(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::insert(self, db, Self::COLLECTION_NAME, session).await
+    }
+
+    /// Appends a status change, recording the cancellation time when the order
+    /// is cancelled.
+    pub async fn update_status(
+        &mut self,
+        db: &impl Namespace,
+        status: OrderStatus,
+        timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        if self.status().is_final() {
+            bail!(
+                "order {} is already {:?}",
+                self.brokerage_order_id,
+                self.status()
+            );
+        }
+
+        let change = OrderStatusChange {
+            status,
+            timestamp_ms,
+        };
+        let mut update = bson::doc! {
+            "$push": { "status_history": bson::to_bson(&change)? },
+        };
+        if status == OrderStatus::Cancelled {
+            update.insert(
+                "$set",
+                bson::doc! { "cancelled_timestamp_ms": timestamp_ms },
+            );
+            self.cancelled_timestamp_ms = Some(timestamp_ms);
+        }
+        db_util::update_one::<Self>(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"_id": self._id},
+            update,
+            session,
+        )
+        .await?;
+        self.status_history.push(change);
+
+        Ok(())
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    pub async fn find_by_brokerage_order_id(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        brokerage_order_id: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "brokerage_order_id": brokerage_order_id,
+            },
+            session,
+        )
+        .await
+    }
+
+    /// Returns the account's orders submitted within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "submitted_timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "submitted_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+
+    pub async fn executions(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_order_id(db, self._id, session).await
+    }
+
+    pub async fn brokerage_account(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<BrokerageAccount> {
+        Ok(
+            BrokerageAccount::find_by_id(db, self.brokerage_account_id, session)
+                .await?
+                .unwrap(),
+        )
+    }
+
+    pub async fn security(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Security> {
+        Ok(Security::find_by_id(db, self.security_id, session)
+            .await?
+            .unwrap())
+    }
+}
+
+impl Versioned for Order {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for Order {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+
+    fn account_field() -> Option<&'static str> {
+        Some("brokerage_account_id")
+    }
+}
+
+pub struct Builder {
+    _id: ObjectId,
+    brokerage_account_id: Option<ObjectId>,
+    brokerage_order_id: Option<String>,
+    security_id: Option<ObjectId>,
+    side: Option<TradeSide>,
+    order_type: OrderType,
+    quantity: Option<f64>,
+    limit_price: Option<f64>,
+    stop_price: Option<f64>,
+    time_in_force: TimeInForce,
+    submitted_timestamp_ms: Option<i64>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            _id: ObjectId::new(),
+            brokerage_account_id: None,
+            brokerage_order_id: None,
+            security_id: None,
+            side: None,
+            order_type: OrderType::Market,
+            quantity: None,
+            limit_price: None,
+            stop_price: None,
+            time_in_force: TimeInForce::Day,
+            submitted_timestamp_ms: None,
+        }
+    }
+
+    pub fn brokerage_account_id(mut self, id: ObjectId) -> Self {
+        self.brokerage_account_id = Some(id);
+        self
+    }
+
+    pub fn brokerage_order_id(mut self, id: &str) -> Self {
+        self.brokerage_order_id = Some(id.to_owned());
+        self
+    }
+
+    pub fn security_id(mut self, id: ObjectId) -> Self {
+        self.security_id = Some(id);
+        self
+    }
+
+    pub fn side(mut self, side: TradeSide) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    pub fn order_type(mut self, order_type: OrderType) -> Self {
+        self.order_type = order_type;
+        self
+    }
+
+    pub fn quantity(mut self, quantity: f64) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    pub fn limit_price(mut self, price: f64) -> Self {
+        self.limit_price = Some(price);
+        self
+    }
+
+    pub fn stop_price(mut self, price: f64) -> Self {
+        self.stop_price = Some(price);
+        self
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn submitted_timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.submitted_timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn build(self) -> Result<Order> {
+        let needs_limit = matches!(self.order_type, OrderType::Limit | OrderType::StopLimit);
+        let needs_stop = matches!(self.order_type, OrderType::Stop | OrderType::StopLimit);
+        if needs_limit && self.limit_price.is_none() {
+            bail!("{:?} order requires a limit price", self.order_type);
+        }
+        if needs_stop && self.stop_price.is_none() {
+            bail!("{:?} order requires a stop price", self.order_type);
+        }
+
+        let submitted_timestamp_ms = self.submitted_timestamp_ms.unwrap();
+        Ok(Order {
+            _id: self._id,
+            brokerage_account_id: self.brokerage_account_id.unwrap(),
+            brokerage_order_id: self.brokerage_order_id.unwrap(),
+            security_id: self.security_id.unwrap(),
+            side: self.side.unwrap(),
+            order_type: self.order_type,
+            quantity: self.quantity.unwrap(),
+            limit_price: self.limit_price,
+            stop_price: self.stop_price,
+            time_in_force: self.time_in_force,
+            status_history: vec![OrderStatusChange {
+                status: OrderStatus::Submitted,
+                timestamp_ms: submitted_timestamp_ms,
+            }],
+            submitted_timestamp_ms,
+            cancelled_timestamp_ms: None,
+        })
+    }
+}
+
+/// An order together with the executions that filled it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderFill {
+    order: Order,
+    /// Oldest first.
+    executions: Vec<TradeExecution>,
+}
+
+impl OrderFill {
+    pub fn new(order: Order, mut executions: Vec<TradeExecution>) -> Self {
+        executions.sort_by_key(|e| e.execution_timestamp_ms());
+        Self { order, executions }
+    }
+
+    pub fn order(&self) -> &Order {
+        &self.order
+    }
+
+    pub fn executions(&self) -> &[TradeExecution] {
+        &self.executions
+    }
+
+    pub fn filled_quantity(&self) -> f64 {
+        self.executions.iter().map(|e| e.quantity().abs()).sum()
+    }
+
+    /// Filled quantity as a fraction of the ordered quantity.
+    pub fn fill_rate(&self) -> f64 {
+        let ordered = self.order.quantity.abs();
+        if ordered < FILL_EPSILON {
+            return 0.0;
+        }
+        self.filled_quantity() / ordered
+    }
+
+    /// True if some but not all of the order was filled.
+    pub fn is_partially_filled(&self) -> bool {
+        let filled = self.filled_quantity();
+        filled > FILL_EPSILON && filled < self.order.quantity.abs() - FILL_EPSILON
+    }
+
+    pub fn average_fill_price(&self) -> Option<f64> {
+        let filled = self.filled_quantity();
+        if filled < FILL_EPSILON {
+            return None;
+        }
+        let notional: f64 = self
+            .executions
+            .iter()
+            .map(|e| e.quantity().abs() * e.price())
+            .sum();
+        Some(notional / filled)
+    }
+
+    /// Time from submission to the first fill.
+    pub fn first_fill_latency_ms(&self) -> Option<i64> {
+        self.executions
+            .first()
+            .map(|e| e.execution_timestamp_ms() - self.order.submitted_timestamp_ms)
+    }
+
+    /// Time from submission to the last fill.
+    pub fn last_fill_latency_ms(&self) -> Option<i64> {
+        self.executions
+            .last()
+            .map(|e| e.execution_timestamp_ms() - self.order.submitted_timestamp_ms)
+    }
+
+    pub async fn for_order(
+        db: &impl Namespace,
+        order: Order,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let executions = order.executions(db, session).await?;
+        Ok(Self::new(order, executions))
+    }
+
+    /// Returns the fills of the account's orders submitted within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest order first.
+    pub async fn for_account_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        let orders = Order::find_by_account_id_and_range(
+            db,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            session.clone(),
+        )
+        .await?;
+        let order_ids: Vec<ObjectId> = orders.iter().map(|o| o.id()).collect();
+        let mut executions_by_order: HashMap<ObjectId, Vec<TradeExecution>> = HashMap::new();
+        for execution in TradeExecution::find_by_order_ids(db, &order_ids, session).await? {
+            if let Some(order_id) = execution.order_id() {
+                executions_by_order
+                    .entry(order_id)
+                    .or_default()
+                    .push(execution);
+            }
+        }
+
+        Ok(orders
+            .into_iter()
+            .map(|order| {
+                let executions = executions_by_order.remove(&order.id()).unwrap_or_default();
+                Self::new(order, executions)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{account::BrokerageAccount, db_util, namespace::Namespace, versioned::Versioned};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum OwnerType {
+    Person,
+    Entity,
+}
+
+/// A person or legal entity that owns one or more brokerage accounts, e.g. a
+/// taxpayer or household member.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Owner {
+    _id: ObjectId,
+    name: String,
+    owner_type: OwnerType,
+}
+
+impl Owner {
+    pub const COLLECTION_NAME: &'static str = "owners";
+
+    pub fn new(owner_type: OwnerType, name: &str) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            name: name.to_owned(),
+            owner_type,
+        }
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn owner_type(&self) -> &OwnerType {
+        &self.owner_type
+    }
+
+    pub async fn insert(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::insert(self, db, Self::COLLECTION_NAME, session).await
+    }
+
+    pub async fn find(
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(db, Self::COLLECTION_NAME, bson::doc! {}, None, session).await
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    pub async fn brokerage_accounts(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<BrokerageAccount>> {
+        BrokerageAccount::find_by_owner_id(db, self._id, session).await
+    }
+}
+
+impl Versioned for Owner {
+    const SCHEMA_VERSION: u32 = 1;
+}
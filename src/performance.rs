@@ -0,0 +1,219 @@
+use anyhow::{Result, bail};
+
+use crate::eod_summary::EODSummary;
+
+const MS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0 * 1000.0;
+const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// An `EODSummary` paired with the market value of the account's positions at
+/// the start and end of the summarized day.
+#[derive(Clone, Copy, Debug)]
+pub struct ValuedSummary<'a> {
+    summary: &'a EODSummary,
+    starting_positions_value: f64,
+    ending_positions_value: f64,
+}
+
+impl<'a> ValuedSummary<'a> {
+    pub fn new(
+        summary: &'a EODSummary,
+        starting_positions_value: f64,
+        ending_positions_value: f64,
+    ) -> Self {
+        Self {
+            summary,
+            starting_positions_value,
+            ending_positions_value,
+        }
+    }
+
+    pub fn summary(&self) -> &EODSummary {
+        self.summary
+    }
+
+    pub fn starting_value(&self) -> f64 {
+        self.summary.starting_cash() + self.starting_positions_value
+    }
+
+    pub fn ending_value(&self) -> f64 {
+        self.summary.ending_cash() + self.ending_positions_value
+    }
+
+    /// Net external flow into the account for the day.
+    ///
+    /// Brokers differ on whether withdrawals are reported as positive or
+    /// negative amounts, so both deposits and withdrawals are taken by magnitude.
+    pub fn external_flow(&self) -> f64 {
+        self.summary.deposits().abs() - self.summary.withdrawals().abs()
+    }
+
+    /// Modified Dietz return for the day, with external flows weighted to mid-day.
+    pub fn daily_return(&self) -> f64 {
+        let flow = self.external_flow();
+        let denominator = self.starting_value() + 0.5 * flow;
+        if denominator.abs() < f64::EPSILON {
+            return 0.0;
+        }
+        (self.ending_value() - self.starting_value() - flow) / denominator
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformanceMetrics {
+    start_timestamp_ms: i64,
+    end_timestamp_ms: i64,
+    daily_returns: Vec<f64>,
+    time_weighted_return: f64,
+    money_weighted_return: Option<f64>,
+    max_drawdown: f64,
+    volatility: f64,
+    sharpe_ratio: Option<f64>,
+}
+
+impl PerformanceMetrics {
+    /// Computes performance metrics over a chronologically ordered series of
+    /// daily summaries for a single account.
+    ///
+    /// `annual_risk_free_rate` is used for the Sharpe ratio, e.g. `0.04` for 4%.
+    pub fn compute(days: &[ValuedSummary], annual_risk_free_rate: f64) -> Result<Self> {
+        let (Some(first), Some(last)) = (days.first(), days.last()) else {
+            bail!("cannot compute performance metrics over an empty period");
+        };
+
+        if days.windows(2).any(|w| {
+            w[0].summary.end_timestamp_ms() > w[1].summary.end_timestamp_ms()
+                || w[0].summary.brokerage_account_id() != w[1].summary.brokerage_account_id()
+        }) {
+            bail!("summaries must belong to one account and be sorted by end timestamp");
+        }
+
+        let daily_returns: Vec<f64> = days.iter().map(ValuedSummary::daily_return).collect();
+
+        // Chain the daily returns into a wealth index for TWR and drawdown.
+        let mut wealth = 1.0;
+        let mut peak = 1.0;
+        let mut max_drawdown: f64 = 0.0;
+        for r in &daily_returns {
+            wealth *= 1.0 + r;
+            peak = f64::max(peak, wealth);
+            if peak > 0.0 {
+                max_drawdown = max_drawdown.max((peak - wealth) / peak);
+            }
+        }
+
+        let volatility = sample_std_dev(&daily_returns) * TRADING_DAYS_PER_YEAR.sqrt();
+        let sharpe_ratio = if volatility > 0.0 {
+            let mean = daily_returns.iter().sum::<f64>() / daily_returns.len() as f64;
+            Some((mean * TRADING_DAYS_PER_YEAR - annual_risk_free_rate) / volatility)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            start_timestamp_ms: first.summary.start_timestamp_ms(),
+            end_timestamp_ms: last.summary.end_timestamp_ms(),
+            time_weighted_return: wealth - 1.0,
+            money_weighted_return: money_weighted_return(days),
+            daily_returns,
+            max_drawdown,
+            volatility,
+            sharpe_ratio,
+        })
+    }
+
+    pub fn start_timestamp_ms(&self) -> i64 {
+        self.start_timestamp_ms
+    }
+
+    pub fn end_timestamp_ms(&self) -> i64 {
+        self.end_timestamp_ms
+    }
+
+    pub fn daily_returns(&self) -> &[f64] {
+        &self.daily_returns
+    }
+
+    /// Cumulative (not annualized) time-weighted return over the period.
+    pub fn time_weighted_return(&self) -> f64 {
+        self.time_weighted_return
+    }
+
+    /// Annualized internal rate of return, or `None` if it could not be solved.
+    pub fn money_weighted_return(&self) -> Option<f64> {
+        self.money_weighted_return
+    }
+
+    /// Largest peak-to-trough decline of the time-weighted wealth index, as a
+    /// positive fraction.
+    pub fn max_drawdown(&self) -> f64 {
+        self.max_drawdown
+    }
+
+    /// Annualized standard deviation of daily returns.
+    pub fn volatility(&self) -> f64 {
+        self.volatility
+    }
+
+    pub fn sharpe_ratio(&self) -> Option<f64> {
+        self.sharpe_ratio
+    }
+}
+
+fn sample_std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// Solves for the annualized rate at which the net present value of the
+/// investor's flows is zero: the starting value and each day's external flows
+/// go in, and the ending value comes out.
+fn money_weighted_return(days: &[ValuedSummary]) -> Option<f64> {
+    let first = days.first()?;
+    let last = days.last()?;
+    let t0 = first.summary.start_timestamp_ms();
+
+    let mut flows = vec![(0.0, -first.starting_value())];
+    for day in days {
+        let midpoint = (day.summary.start_timestamp_ms() + day.summary.end_timestamp_ms()) / 2;
+        flows.push(((midpoint - t0) as f64 / MS_PER_YEAR, -day.external_flow()));
+    }
+    flows.push((
+        (last.summary.end_timestamp_ms() - t0) as f64 / MS_PER_YEAR,
+        last.ending_value(),
+    ));
+
+    let npv = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(years, amount)| amount / (1.0 + rate).powf(*years))
+            .sum()
+    };
+
+    // Bisection over a bracket wide enough for short, volatile periods.
+    let (mut lo, mut hi) = (-0.9999, 1.0e6);
+    let (mut npv_lo, npv_hi) = (npv(lo), npv(hi));
+    if !npv_lo.is_finite() || !npv_hi.is_finite() || npv_lo.signum() == npv_hi.signum() {
+        return None;
+    }
+
+    for _ in 0..200 {
+        let mid = (lo + hi) / 2.0;
+        let npv_mid = npv(mid);
+        if npv_mid.abs() < 1e-9 {
+            return Some(mid);
+        }
+        if npv_mid.signum() == npv_lo.signum() {
+            lo = mid;
+            npv_lo = npv_mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Some((lo + hi) / 2.0)
+}
@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{db_util, namespace::Namespace, subscription::Watched, versioned::Versioned};
+
+/// Open, high, low, close and volume of a security over a fixed interval,
+/// e.g. one minute or one day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PriceBar {
+    _id: ObjectId,
+    security_id: ObjectId,
+    start_timestamp_ms: i64,
+    duration_ms: i64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: f64,
+}
+
+impl PriceBar {
+    pub const COLLECTION_NAME: &'static str = "price_bars";
+
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    pub fn start_timestamp_ms(&self) -> i64 {
+        self.start_timestamp_ms
+    }
+
+    pub fn duration_ms(&self) -> i64 {
+        self.duration_ms
+    }
+
+    /// Exclusive end of the interval.
+    pub fn end_timestamp_ms(&self) -> i64 {
+        self.start_timestamp_ms + self.duration_ms
+    }
+
+    pub fn open(&self) -> f64 {
+        self.open
+    }
+
+    pub fn high(&self) -> f64 {
+        self.high
+    }
+
+    pub fn low(&self) -> f64 {
+        self.low
+    }
+
+    pub fn close(&self) -> f64 {
+        self.close
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// Average of high, low and close, the usual per-bar price for VWAP.
+    pub fn typical_price(&self) -> f64 {
+        (self.high + self.low + self.close) / 3.0
+    }
+
+    pub async fn insert(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::insert(self, db, Self::COLLECTION_NAME, session).await
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    /// Returns the security's bars of the given duration starting within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_security_id_and_range(
+        db: &impl Namespace,
+        security_id: ObjectId,
+        duration_ms: i64,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "security_id": security_id,
+                "duration_ms": duration_ms,
+                "start_timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "start_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+}
+
+impl Versioned for PriceBar {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for PriceBar {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+}
+
+pub struct Builder {
+    _id: ObjectId,
+    security_id: Option<ObjectId>,
+    start_timestamp_ms: Option<i64>,
+    duration_ms: Option<i64>,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
+    volume: f64,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            _id: ObjectId::new(),
+            security_id: None,
+            start_timestamp_ms: None,
+            duration_ms: None,
+            open: None,
+            high: None,
+            low: None,
+            close: None,
+            volume: 0.0,
+        }
+    }
+
+    pub fn security_id(mut self, id: ObjectId) -> Self {
+        self.security_id = Some(id);
+        self
+    }
+
+    pub fn start_timestamp_ms(mut self, timestamp: i64) -> Self {
+        self.start_timestamp_ms = Some(timestamp);
+        self
+    }
+
+    pub fn duration_ms(mut self, duration: i64) -> Self {
+        self.duration_ms = Some(duration);
+        self
+    }
+
+    pub fn open(mut self, price: f64) -> Self {
+        self.open = Some(price);
+        self
+    }
+
+    pub fn high(mut self, price: f64) -> Self {
+        self.high = Some(price);
+        self
+    }
+
+    pub fn low(mut self, price: f64) -> Self {
+        self.low = Some(price);
+        self
+    }
+
+    pub fn close(mut self, price: f64) -> Self {
+        self.close = Some(price);
+        self
+    }
+
+    pub fn volume(mut self, volume: f64) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn build(self) -> Result<PriceBar> {
+        let duration_ms = self.duration_ms.unwrap();
+        if duration_ms <= 0 {
+            bail!("price bar duration must be positive, got {}", duration_ms);
+        }
+
+        Ok(PriceBar {
+            _id: self._id,
+            security_id: self.security_id.unwrap(),
+            start_timestamp_ms: self.start_timestamp_ms.unwrap(),
+            duration_ms,
+            open: self.open.unwrap(),
+            high: self.high.unwrap(),
+            low: self.low.unwrap(),
+            close: self.close.unwrap(),
+            volume: self.volume,
+        })
+    }
+}
@@ -0,0 +1,353 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use tokio::sync::Mutex;
+
+use crate::namespace::Namespace;
+use crate::trade_execution::{TradeExecution, TradeSide};
+
+/// Positions smaller than this are treated as flat.
+const FLAT_EPSILON: f64 = 1e-9;
+
+/// A campaign in one security within one account, from flat to flat.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundTrip {
+    brokerage_account_id: ObjectId,
+    security_id: ObjectId,
+    side: TradeSide,
+    entry_timestamp_ms: i64,
+    exit_timestamp_ms: i64,
+    quantity: f64,
+    average_entry_price: f64,
+    average_exit_price: f64,
+    max_position_size: f64,
+    gross_pnl: f64,
+    commission: f64,
+    trade_execution_ids: Vec<ObjectId>,
+    tag_ids: Vec<ObjectId>,
+}
+
+impl RoundTrip {
+    /// Groups executions into round trips per account and security.
+    ///
+    /// Executions that reverse the position are split: the closing part ends the
+    /// current round trip and the remainder opens the next one, with commission
+    /// allocated pro rata. Round trips still open at the end of the input are
+    /// not returned.
+    pub fn from_executions(executions: &[TradeExecution]) -> Vec<Self> {
+        let mut sorted: Vec<&TradeExecution> = executions.iter().collect();
+        sorted.sort_by_key(|e| e.execution_timestamp_ms());
+
+        let mut open: HashMap<(ObjectId, ObjectId), Accumulator> = HashMap::new();
+        let mut round_trips = Vec::new();
+
+        for execution in sorted {
+            let key = (execution.brokerage_account_id(), execution.security_id());
+            let mut remaining = execution.quantity().abs();
+            let commission_per_unit = if remaining > 0.0 {
+                execution.commission() / remaining
+            } else {
+                0.0
+            };
+
+            while remaining > FLAT_EPSILON {
+                let acc = open
+                    .entry(key)
+                    .or_insert_with(|| Accumulator::new(execution, key));
+
+                let quantity = if acc.side == *execution.side() {
+                    remaining
+                } else {
+                    remaining.min(acc.position)
+                };
+                acc.add(execution, quantity, quantity * commission_per_unit);
+                remaining -= quantity;
+
+                if acc.position <= FLAT_EPSILON {
+                    round_trips.push(open.remove(&key).unwrap().finish(execution));
+                }
+            }
+        }
+
+        round_trips.sort_by_key(|rt| rt.exit_timestamp_ms);
+        round_trips
+    }
+
+    /// Returns the account's round trips that closed within
+    /// `[start_timestamp_ms, end_timestamp_ms]`.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        // Start from the account's first execution so that round trips opened
+        // before the range are grouped correctly.
+        let executions = TradeExecution::find_by_account_id_and_range(
+            db,
+            brokerage_account_id,
+            i64::MIN,
+            end_timestamp_ms,
+            session,
+        )
+        .await?;
+
+        Ok(Self::from_executions(&executions)
+            .into_iter()
+            .filter(|rt| rt.exit_timestamp_ms >= start_timestamp_ms)
+            .collect())
+    }
+
+    pub fn brokerage_account_id(&self) -> ObjectId {
+        self.brokerage_account_id
+    }
+
+    pub fn security_id(&self) -> ObjectId {
+        self.security_id
+    }
+
+    /// `Buy` for a long round trip, `Sell` for a short one.
+    pub fn side(&self) -> &TradeSide {
+        &self.side
+    }
+
+    pub fn entry_timestamp_ms(&self) -> i64 {
+        self.entry_timestamp_ms
+    }
+
+    pub fn exit_timestamp_ms(&self) -> i64 {
+        self.exit_timestamp_ms
+    }
+
+    pub fn holding_duration_ms(&self) -> i64 {
+        self.exit_timestamp_ms - self.entry_timestamp_ms
+    }
+
+    /// Total quantity entered (and exited) over the round trip.
+    pub fn quantity(&self) -> f64 {
+        self.quantity
+    }
+
+    pub fn average_entry_price(&self) -> f64 {
+        self.average_entry_price
+    }
+
+    pub fn average_exit_price(&self) -> f64 {
+        self.average_exit_price
+    }
+
+    pub fn max_position_size(&self) -> f64 {
+        self.max_position_size
+    }
+
+    pub fn gross_pnl(&self) -> f64 {
+        self.gross_pnl
+    }
+
+    /// Total commission paid net of rebates, negative if the rebates exceeded
+    /// the fees.
+    pub fn commission(&self) -> f64 {
+        self.commission
+    }
+
+    pub fn net_pnl(&self) -> f64 {
+        self.gross_pnl - self.commission
+    }
+
+    pub fn trade_execution_ids(&self) -> &[ObjectId] {
+        &self.trade_execution_ids
+    }
+
+    /// Tags of any of the round trip's executions.
+    pub fn tag_ids(&self) -> &[ObjectId] {
+        &self.tag_ids
+    }
+}
+
+struct Accumulator {
+    brokerage_account_id: ObjectId,
+    security_id: ObjectId,
+    side: TradeSide,
+    entry_timestamp_ms: i64,
+    position: f64,
+    max_position_size: f64,
+    entry_quantity: f64,
+    entry_notional: f64,
+    exit_quantity: f64,
+    exit_notional: f64,
+    commission: f64,
+    trade_execution_ids: Vec<ObjectId>,
+    tag_ids: Vec<ObjectId>,
+}
+
+impl Accumulator {
+    fn new(execution: &TradeExecution, key: (ObjectId, ObjectId)) -> Self {
+        Self {
+            brokerage_account_id: key.0,
+            security_id: key.1,
+            side: execution.side().clone(),
+            entry_timestamp_ms: execution.execution_timestamp_ms(),
+            position: 0.0,
+            max_position_size: 0.0,
+            entry_quantity: 0.0,
+            entry_notional: 0.0,
+            exit_quantity: 0.0,
+            exit_notional: 0.0,
+            commission: 0.0,
+            trade_execution_ids: Vec::new(),
+            tag_ids: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, execution: &TradeExecution, quantity: f64, commission: f64) {
+        if self.side == *execution.side() {
+            self.position += quantity;
+            self.entry_quantity += quantity;
+            self.entry_notional += quantity * execution.price();
+            self.max_position_size = self.max_position_size.max(self.position);
+        } else {
+            self.position -= quantity;
+            self.exit_quantity += quantity;
+            self.exit_notional += quantity * execution.price();
+        }
+        self.commission += commission;
+        if self.trade_execution_ids.last() != Some(&execution.id()) {
+            self.trade_execution_ids.push(execution.id());
+        }
+        for tag_id in execution.tag_ids() {
+            if !self.tag_ids.contains(tag_id) {
+                self.tag_ids.push(*tag_id);
+            }
+        }
+    }
+
+    fn finish(self, last_execution: &TradeExecution) -> RoundTrip {
+        let gross_pnl = match self.side {
+            TradeSide::Buy => self.exit_notional - self.entry_notional,
+            TradeSide::Sell => self.entry_notional - self.exit_notional,
+        };
+
+        RoundTrip {
+            brokerage_account_id: self.brokerage_account_id,
+            security_id: self.security_id,
+            side: self.side,
+            entry_timestamp_ms: self.entry_timestamp_ms,
+            exit_timestamp_ms: last_execution.execution_timestamp_ms(),
+            quantity: self.entry_quantity,
+            average_entry_price: self.entry_notional / self.entry_quantity,
+            average_exit_price: self.exit_notional / self.exit_quantity,
+            max_position_size: self.max_position_size,
+            gross_pnl,
+            commission: self.commission,
+            trade_execution_ids: self.trade_execution_ids,
+            tag_ids: self.tag_ids,
+        }
+    }
+}
+
+/// Aggregate statistics over a set of round trips.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TradingStatistics {
+    round_trip_count: usize,
+    winning_count: usize,
+    losing_count: usize,
+    gross_profit: f64,
+    gross_loss: f64,
+    net_pnl: f64,
+    total_holding_duration_ms: i64,
+}
+
+impl TradingStatistics {
+    pub fn from_round_trips<'a>(round_trips: impl IntoIterator<Item = &'a RoundTrip>) -> Self {
+        let mut stats = Self {
+            round_trip_count: 0,
+            winning_count: 0,
+            losing_count: 0,
+            gross_profit: 0.0,
+            gross_loss: 0.0,
+            net_pnl: 0.0,
+            total_holding_duration_ms: 0,
+        };
+
+        for rt in round_trips {
+            let net_pnl = rt.net_pnl();
+            stats.round_trip_count += 1;
+            if net_pnl > 0.0 {
+                stats.winning_count += 1;
+                stats.gross_profit += net_pnl;
+            } else if net_pnl < 0.0 {
+                stats.losing_count += 1;
+                stats.gross_loss -= net_pnl;
+            }
+            stats.net_pnl += net_pnl;
+            stats.total_holding_duration_ms += rt.holding_duration_ms();
+        }
+
+        stats
+    }
+
+    /// Statistics per brokerage account.
+    pub fn by_account(round_trips: &[RoundTrip]) -> HashMap<ObjectId, Self> {
+        Self::grouped(round_trips, RoundTrip::brokerage_account_id)
+    }
+
+    /// Statistics per security.
+    pub fn by_security(round_trips: &[RoundTrip]) -> HashMap<ObjectId, Self> {
+        Self::grouped(round_trips, RoundTrip::security_id)
+    }
+
+    fn grouped<K, F>(round_trips: &[RoundTrip], key: F) -> HashMap<K, Self>
+    where
+        K: Eq + std::hash::Hash,
+        F: Fn(&RoundTrip) -> K,
+    {
+        let mut groups: HashMap<K, Vec<&RoundTrip>> = HashMap::new();
+        for rt in round_trips {
+            groups.entry(key(rt)).or_default().push(rt);
+        }
+        groups
+            .into_iter()
+            .map(|(k, rts)| (k, Self::from_round_trips(rts)))
+            .collect()
+    }
+
+    pub fn round_trip_count(&self) -> usize {
+        self.round_trip_count
+    }
+
+    pub fn winning_count(&self) -> usize {
+        self.winning_count
+    }
+
+    pub fn losing_count(&self) -> usize {
+        self.losing_count
+    }
+
+    pub fn net_pnl(&self) -> f64 {
+        self.net_pnl
+    }
+
+    /// Fraction of round trips with a positive net P&L.
+    pub fn win_rate(&self) -> Option<f64> {
+        (self.round_trip_count > 0)
+            .then(|| self.winning_count as f64 / self.round_trip_count as f64)
+    }
+
+    /// Gross profit of winners over gross loss of losers.
+    pub fn profit_factor(&self) -> Option<f64> {
+        (self.gross_loss > 0.0).then(|| self.gross_profit / self.gross_loss)
+    }
+
+    /// Average net P&L per round trip.
+    pub fn expectancy(&self) -> Option<f64> {
+        (self.round_trip_count > 0).then(|| self.net_pnl / self.round_trip_count as f64)
+    }
+
+    pub fn average_holding_duration_ms(&self) -> Option<i64> {
+        (self.round_trip_count > 0)
+            .then(|| self.total_holding_duration_ms / self.round_trip_count as i64)
+    }
+}
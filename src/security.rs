@@ -2,24 +2,50 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use bson::{doc, oid::ObjectId};
-use futures::stream::TryStreamExt;
-use mongodb::{ClientSession, Database};
+use mongodb::ClientSession;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use crate::db_util;
+use crate::namespace::Namespace;
+use crate::subscription::Watched;
+use crate::versioned::Versioned;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum SecurityType {
     Stock,
+    Option,
 }
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+/// Terms of an option contract.
+///
+/// Executions of an option are quantified in contracts and priced per
+/// contract, i.e. the quoted premium times the multiplier.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OptionContract {
+    pub underlying_id: ObjectId,
+    pub right: OptionRight,
+    pub strike: f64,
+    pub expiration_timestamp_ms: i64,
+    /// Shares delivered per contract, usually 100.
+    pub multiplier: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Security {
     _id: bson::oid::ObjectId,
     listing_exchange: String,
     security_type: SecurityType,
     ticker: String,
     ibkr_conid: Option<u32>,
+    /// Set for [`SecurityType::Option`].
+    option_contract: Option<OptionContract>,
 }
 
 impl Security {
@@ -37,6 +63,19 @@ impl Security {
             security_type,
             ticker: ticker.to_owned(),
             ibkr_conid,
+            option_contract: None,
+        }
+    }
+
+    pub fn new_option(
+        ticker: &str,
+        listing_exchange: &str,
+        ibkr_conid: Option<u32>,
+        option_contract: OptionContract,
+    ) -> Self {
+        Self {
+            option_contract: Some(option_contract),
+            ..Self::new(SecurityType::Option, ticker, listing_exchange, ibkr_conid)
         }
     }
 
@@ -60,50 +99,77 @@ impl Security {
         self.ibkr_conid
     }
 
+    pub fn option_contract(&self) -> Option<&OptionContract> {
+        self.option_contract.as_ref()
+    }
+
     pub async fn insert(
         &self,
-        db: &Database,
+        db: &impl Namespace,
         session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<()> {
         db_util::insert(self, db, Self::COLLECTION_NAME, session).await
     }
 
-    pub async fn find_by_id(db: &Database, id: ObjectId) -> Result<Option<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(bson::doc! {"_id": id})
-            .await?;
-
-        Ok(result)
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, doc! {"_id": id}, session).await
     }
 
     pub async fn find_by_ticker_and_exchange(
-        db: &Database,
+        db: &impl Namespace,
         ticker: &str,
         listing_exchange: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<Option<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(bson::doc! {"ticker": ticker, "listing_exchange": listing_exchange})
-            .await?;
+        db_util::find_one(
+            db,
+            Self::COLLECTION_NAME,
+            doc! {"ticker": ticker, "listing_exchange": listing_exchange},
+            session,
+        )
+        .await
+    }
 
-        Ok(result)
+    pub async fn find_by_conid(
+        db: &impl Namespace,
+        ibkr_conid: u32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(
+            db,
+            Self::COLLECTION_NAME,
+            doc! { "ibkr_conid": ibkr_conid },
+            session,
+        )
+        .await
     }
 
-    pub async fn find_by_conid(db: &Database, ibkr_conid: u32) -> Result<Option<Self>> {
-        Ok(db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(doc! { "ibkr_conid": ibkr_conid })
-            .await?)
+    pub async fn find_by_ticker(
+        db: &impl Namespace,
+        ticker: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            doc! { "ticker": ticker },
+            None,
+            session,
+        )
+        .await
     }
+}
 
-    pub async fn find_by_ticker(db: &Database, ticker: &str) -> Result<Vec<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find(doc! { "ticker": ticker })
-            .await?;
+impl Versioned for Security {
+    const SCHEMA_VERSION: u32 = 1;
+}
 
-        // Convert the cursor to a vector of Security objects.
-        Ok(result.try_collect().await?)
+impl Watched for Security {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
     }
 }
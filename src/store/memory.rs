@@ -0,0 +1,322 @@
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+
+use super::{BrokerageStore, DuplicateKeyError};
+use crate::{
+    account::BrokerageAccount, eod_summary::EODSummary, migrations, owner::Owner,
+    security::Security, trade_execution::TradeExecution,
+};
+
+const ID_INDEX_NAME: &str = "_id_";
+
+#[derive(Default)]
+struct Collections {
+    owners: Vec<Owner>,
+    brokerage_accounts: Vec<BrokerageAccount>,
+    securities: Vec<Security>,
+    trade_executions: Vec<TradeExecution>,
+    eod_summaries: Vec<EODSummary>,
+}
+
+/// In-process [`BrokerageStore`] that enforces the same unique indexes as the
+/// MongoDB migrations. Nothing is persisted.
+#[derive(Default)]
+pub struct MemoryStore {
+    collections: Mutex<Collections>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Collections> {
+        self.collections.lock().unwrap()
+    }
+}
+
+/// Appends `item` unless its `_id` or unique key is already present.
+fn insert_unique<T, K>(
+    items: &mut Vec<T>,
+    item: &T,
+    collection_name: &'static str,
+    index_name: &'static str,
+    id: impl Fn(&T) -> ObjectId,
+    key: impl Fn(&T) -> K,
+) -> Result<()>
+where
+    T: Clone,
+    K: PartialEq,
+{
+    if items.iter().any(|existing| id(existing) == id(item)) {
+        return Err(DuplicateKeyError {
+            collection_name,
+            index_name: ID_INDEX_NAME,
+        }
+        .into());
+    }
+    if items.iter().any(|existing| key(existing) == key(item)) {
+        return Err(DuplicateKeyError {
+            collection_name,
+            index_name,
+        }
+        .into());
+    }
+
+    items.push(item.clone());
+    Ok(())
+}
+
+#[async_trait]
+impl BrokerageStore for MemoryStore {
+    async fn insert_owner(&self, owner: &Owner) -> Result<()> {
+        insert_unique(
+            &mut self.lock().owners,
+            owner,
+            Owner::COLLECTION_NAME,
+            ID_INDEX_NAME,
+            Owner::id,
+            Owner::id,
+        )
+    }
+
+    async fn find_owners(&self) -> Result<Vec<Owner>> {
+        Ok(self.lock().owners.clone())
+    }
+
+    async fn find_owner_by_id(&self, id: ObjectId) -> Result<Option<Owner>> {
+        Ok(self.lock().owners.iter().find(|o| o.id() == id).cloned())
+    }
+
+    async fn insert_brokerage_account(&self, account: &BrokerageAccount) -> Result<()> {
+        insert_unique(
+            &mut self.lock().brokerage_accounts,
+            account,
+            BrokerageAccount::COLLECTION_NAME,
+            migrations::BROKERAGE_ACCOUNT_UNIQUE_INDEX_NAME,
+            BrokerageAccount::id,
+            |a| (a.brokerage_id().to_owned(), a.account_id().to_owned()),
+        )
+    }
+
+    async fn find_brokerage_accounts(&self) -> Result<Vec<BrokerageAccount>> {
+        Ok(self.lock().brokerage_accounts.clone())
+    }
+
+    async fn find_brokerage_account_by_id(&self, id: ObjectId) -> Result<Option<BrokerageAccount>> {
+        Ok(self
+            .lock()
+            .brokerage_accounts
+            .iter()
+            .find(|a| a.id() == id)
+            .cloned())
+    }
+
+    async fn find_brokerage_account_by_brokerage_and_account_id(
+        &self,
+        brokerage_id: &str,
+        account_id: &str,
+    ) -> Result<Option<BrokerageAccount>> {
+        Ok(self
+            .lock()
+            .brokerage_accounts
+            .iter()
+            .find(|a| a.brokerage_id() == brokerage_id && a.account_id() == account_id)
+            .cloned())
+    }
+
+    async fn find_brokerage_accounts_by_owner_id(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<BrokerageAccount>> {
+        Ok(self
+            .lock()
+            .brokerage_accounts
+            .iter()
+            .filter(|a| a.owner_id() == Some(owner_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn assign_brokerage_account_owner(
+        &self,
+        id: ObjectId,
+        owner_id: Option<ObjectId>,
+    ) -> Result<()> {
+        match self
+            .lock()
+            .brokerage_accounts
+            .iter_mut()
+            .find(|a| a.id() == id)
+        {
+            Some(account) => account.set_owner_id(owner_id),
+            None => bail!("brokerage account {} not found", id),
+        }
+        Ok(())
+    }
+
+    async fn insert_security(&self, security: &Security) -> Result<()> {
+        insert_unique(
+            &mut self.lock().securities,
+            security,
+            Security::COLLECTION_NAME,
+            migrations::SECURITIES_UNIQUE_INDEX_NAME,
+            Security::id,
+            |s| (s.ticker().to_owned(), s.listing_exchange().to_owned()),
+        )
+    }
+
+    async fn find_security_by_id(&self, id: ObjectId) -> Result<Option<Security>> {
+        Ok(self
+            .lock()
+            .securities
+            .iter()
+            .find(|s| s.id() == id)
+            .cloned())
+    }
+
+    async fn find_security_by_ticker_and_exchange(
+        &self,
+        ticker: &str,
+        listing_exchange: &str,
+    ) -> Result<Option<Security>> {
+        Ok(self
+            .lock()
+            .securities
+            .iter()
+            .find(|s| s.ticker() == ticker && s.listing_exchange() == listing_exchange)
+            .cloned())
+    }
+
+    async fn find_security_by_conid(&self, ibkr_conid: u32) -> Result<Option<Security>> {
+        Ok(self
+            .lock()
+            .securities
+            .iter()
+            .find(|s| s.ibkr_conid() == Some(ibkr_conid))
+            .cloned())
+    }
+
+    async fn find_securities_by_ticker(&self, ticker: &str) -> Result<Vec<Security>> {
+        Ok(self
+            .lock()
+            .securities
+            .iter()
+            .filter(|s| s.ticker() == ticker)
+            .cloned()
+            .collect())
+    }
+
+    async fn insert_trade_execution(&self, trade_execution: &TradeExecution) -> Result<()> {
+        insert_unique(
+            &mut self.lock().trade_executions,
+            trade_execution,
+            TradeExecution::COLLECTION_NAME,
+            migrations::TRADE_EXECUTIONS_UNIQUE_INDEX_NAME,
+            TradeExecution::id,
+            |e| {
+                (
+                    e.brokerage_account_id(),
+                    e.brokerage_execution_id().to_owned(),
+                )
+            },
+        )
+    }
+
+    async fn find_trade_execution_by_id(&self, id: ObjectId) -> Result<Option<TradeExecution>> {
+        Ok(self
+            .lock()
+            .trade_executions
+            .iter()
+            .find(|e| e.id() == id)
+            .cloned())
+    }
+
+    async fn find_trade_execution_by_brokerage_execution_id(
+        &self,
+        execution_id: &str,
+    ) -> Result<Option<TradeExecution>> {
+        Ok(self
+            .lock()
+            .trade_executions
+            .iter()
+            .find(|e| e.brokerage_execution_id() == execution_id)
+            .cloned())
+    }
+
+    async fn find_trade_executions_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<TradeExecution>> {
+        let mut result: Vec<TradeExecution> = self
+            .lock()
+            .trade_executions
+            .iter()
+            .filter(|e| {
+                e.brokerage_account_id() == brokerage_account_id
+                    && (start_timestamp_ms..=end_timestamp_ms).contains(&e.execution_timestamp_ms())
+            })
+            .cloned()
+            .collect();
+        result.sort_by_key(TradeExecution::execution_timestamp_ms);
+        Ok(result)
+    }
+
+    async fn insert_eod_summary(&self, eod_summary: &EODSummary) -> Result<()> {
+        insert_unique(
+            &mut self.lock().eod_summaries,
+            eod_summary,
+            EODSummary::COLLECTION_NAME,
+            migrations::EOD_SUMMARIES_UNIQUE_INDEX_NAME,
+            EODSummary::id,
+            |s| (s.brokerage_account_id(), s.end_timestamp_ms()),
+        )
+    }
+
+    async fn find_eod_summary_by_id(&self, id: ObjectId) -> Result<Option<EODSummary>> {
+        Ok(self
+            .lock()
+            .eod_summaries
+            .iter()
+            .find(|s| s.id() == id)
+            .cloned())
+    }
+
+    async fn find_eod_summaries_by_account_id(
+        &self,
+        brokerage_account_id: ObjectId,
+    ) -> Result<Vec<EODSummary>> {
+        Ok(self
+            .lock()
+            .eod_summaries
+            .iter()
+            .filter(|s| s.brokerage_account_id() == brokerage_account_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_eod_summaries_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<EODSummary>> {
+        let mut result: Vec<EODSummary> = self
+            .lock()
+            .eod_summaries
+            .iter()
+            .filter(|s| {
+                s.brokerage_account_id() == brokerage_account_id
+                    && (start_timestamp_ms..=end_timestamp_ms).contains(&s.end_timestamp_ms())
+            })
+            .cloned()
+            .collect();
+        result.sort_by_key(EODSummary::end_timestamp_ms);
+        Ok(result)
+    }
+}
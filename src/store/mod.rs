@@ -0,0 +1,110 @@
+//! Storage abstraction over the entity collections.
+//!
+//! [`MongoStore`] forwards to the MongoDB queries on each entity type, and
+//! [`MemoryStore`] keeps everything in process while enforcing the same unique
+//! constraints, which lets downstream crates test without a database server.
+//! With the `sqlite` feature, [`SqliteStore`] provides a relational backend
+//! for deployments without MongoDB.
+
+use std::fmt;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+
+use crate::{
+    account::BrokerageAccount, eod_summary::EODSummary, owner::Owner, security::Security,
+    trade_execution::TradeExecution,
+};
+
+mod memory;
+mod mongo;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::MemoryStore;
+pub use mongo::MongoStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[async_trait]
+pub trait BrokerageStore: Send + Sync {
+    async fn insert_owner(&self, owner: &Owner) -> Result<()>;
+    async fn find_owners(&self) -> Result<Vec<Owner>>;
+    async fn find_owner_by_id(&self, id: ObjectId) -> Result<Option<Owner>>;
+
+    async fn insert_brokerage_account(&self, account: &BrokerageAccount) -> Result<()>;
+    async fn find_brokerage_accounts(&self) -> Result<Vec<BrokerageAccount>>;
+    async fn find_brokerage_account_by_id(&self, id: ObjectId) -> Result<Option<BrokerageAccount>>;
+    async fn find_brokerage_account_by_brokerage_and_account_id(
+        &self,
+        brokerage_id: &str,
+        account_id: &str,
+    ) -> Result<Option<BrokerageAccount>>;
+    async fn find_brokerage_accounts_by_owner_id(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<BrokerageAccount>>;
+    /// Assigns the account to an owner, or detaches it with `None`.
+    async fn assign_brokerage_account_owner(
+        &self,
+        id: ObjectId,
+        owner_id: Option<ObjectId>,
+    ) -> Result<()>;
+
+    async fn insert_security(&self, security: &Security) -> Result<()>;
+    async fn find_security_by_id(&self, id: ObjectId) -> Result<Option<Security>>;
+    async fn find_security_by_ticker_and_exchange(
+        &self,
+        ticker: &str,
+        listing_exchange: &str,
+    ) -> Result<Option<Security>>;
+    async fn find_security_by_conid(&self, ibkr_conid: u32) -> Result<Option<Security>>;
+    async fn find_securities_by_ticker(&self, ticker: &str) -> Result<Vec<Security>>;
+
+    async fn insert_trade_execution(&self, trade_execution: &TradeExecution) -> Result<()>;
+    async fn find_trade_execution_by_id(&self, id: ObjectId) -> Result<Option<TradeExecution>>;
+    async fn find_trade_execution_by_brokerage_execution_id(
+        &self,
+        execution_id: &str,
+    ) -> Result<Option<TradeExecution>>;
+    async fn find_trade_executions_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<TradeExecution>>;
+
+    async fn insert_eod_summary(&self, eod_summary: &EODSummary) -> Result<()>;
+    async fn find_eod_summary_by_id(&self, id: ObjectId) -> Result<Option<EODSummary>>;
+    async fn find_eod_summaries_by_account_id(
+        &self,
+        brokerage_account_id: ObjectId,
+    ) -> Result<Vec<EODSummary>>;
+    async fn find_eod_summaries_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<EODSummary>>;
+}
+
+/// Returned by the non-MongoDB stores when an insert violates a unique
+/// constraint, mirroring MongoDB's E11000 duplicate key error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKeyError {
+    pub collection_name: &'static str,
+    pub index_name: &'static str,
+}
+
+impl fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duplicate key error collection: {} index: {}",
+            self.collection_name, self.index_name
+        )
+    }
+}
+
+impl std::error::Error for DuplicateKeyError {}
@@ -0,0 +1,183 @@
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use bson::oid::ObjectId;
+use mongodb::Database;
+
+use super::BrokerageStore;
+use crate::{
+    account::BrokerageAccount, eod_summary::EODSummary, namespace::Namespace, owner::Owner,
+    security::Security, trade_execution::TradeExecution,
+};
+
+/// [`BrokerageStore`] backed by a MongoDB database initialized with
+/// [`crate::initialize`].
+#[derive(Clone, Debug)]
+pub struct MongoStore {
+    db: Database,
+    collection_prefix: String,
+}
+
+impl MongoStore {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            collection_prefix: String::new(),
+        }
+    }
+
+    /// Uses the collections initialized under `collection_prefix`.
+    pub fn with_collection_prefix(mut self, collection_prefix: &str) -> Self {
+        self.collection_prefix = collection_prefix.to_owned();
+        self
+    }
+}
+
+impl Namespace for MongoStore {
+    fn database(&self) -> &Database {
+        &self.db
+    }
+
+    fn collection_prefix(&self) -> &str {
+        &self.collection_prefix
+    }
+}
+
+#[async_trait]
+impl BrokerageStore for MongoStore {
+    async fn insert_owner(&self, owner: &Owner) -> Result<()> {
+        owner.insert(self, None).await
+    }
+
+    async fn find_owners(&self) -> Result<Vec<Owner>> {
+        Owner::find(self, None).await
+    }
+
+    async fn find_owner_by_id(&self, id: ObjectId) -> Result<Option<Owner>> {
+        Owner::find_by_id(self, id, None).await
+    }
+
+    async fn insert_brokerage_account(&self, account: &BrokerageAccount) -> Result<()> {
+        account.insert(self, None).await
+    }
+
+    async fn find_brokerage_accounts(&self) -> Result<Vec<BrokerageAccount>> {
+        BrokerageAccount::find(self, None).await
+    }
+
+    async fn find_brokerage_account_by_id(&self, id: ObjectId) -> Result<Option<BrokerageAccount>> {
+        BrokerageAccount::find_by_id(self, id, None).await
+    }
+
+    async fn find_brokerage_account_by_brokerage_and_account_id(
+        &self,
+        brokerage_id: &str,
+        account_id: &str,
+    ) -> Result<Option<BrokerageAccount>> {
+        BrokerageAccount::find_by_brokerage_and_account_id(self, brokerage_id, account_id, None)
+            .await
+    }
+
+    async fn find_brokerage_accounts_by_owner_id(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<BrokerageAccount>> {
+        BrokerageAccount::find_by_owner_id(self, owner_id, None).await
+    }
+
+    async fn assign_brokerage_account_owner(
+        &self,
+        id: ObjectId,
+        owner_id: Option<ObjectId>,
+    ) -> Result<()> {
+        match BrokerageAccount::find_by_id(self, id, None).await? {
+            Some(mut account) => account.assign_owner(self, owner_id, None).await,
+            None => bail!("brokerage account {} not found", id),
+        }
+    }
+
+    async fn insert_security(&self, security: &Security) -> Result<()> {
+        security.insert(self, None).await
+    }
+
+    async fn find_security_by_id(&self, id: ObjectId) -> Result<Option<Security>> {
+        Security::find_by_id(self, id, None).await
+    }
+
+    async fn find_security_by_ticker_and_exchange(
+        &self,
+        ticker: &str,
+        listing_exchange: &str,
+    ) -> Result<Option<Security>> {
+        Security::find_by_ticker_and_exchange(self, ticker, listing_exchange, None).await
+    }
+
+    async fn find_security_by_conid(&self, ibkr_conid: u32) -> Result<Option<Security>> {
+        Security::find_by_conid(self, ibkr_conid, None).await
+    }
+
+    async fn find_securities_by_ticker(&self, ticker: &str) -> Result<Vec<Security>> {
+        Security::find_by_ticker(self, ticker, None).await
+    }
+
+    async fn insert_trade_execution(&self, trade_execution: &TradeExecution) -> Result<()> {
+        trade_execution.insert(self, None).await
+    }
+
+    async fn find_trade_execution_by_id(&self, id: ObjectId) -> Result<Option<TradeExecution>> {
+        TradeExecution::find_by_id(self, id, None).await
+    }
+
+    async fn find_trade_execution_by_brokerage_execution_id(
+        &self,
+        execution_id: &str,
+    ) -> Result<Option<TradeExecution>> {
+        TradeExecution::find_by_brokerage_execution_id(self, execution_id, None).await
+    }
+
+    async fn find_trade_executions_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_account_id_and_range(
+            self,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            None,
+        )
+        .await
+    }
+
+    async fn insert_eod_summary(&self, eod_summary: &EODSummary) -> Result<()> {
+        eod_summary.insert(self, None).await
+    }
+
+    async fn find_eod_summary_by_id(&self, id: ObjectId) -> Result<Option<EODSummary>> {
+        EODSummary::find_by_id(self, id, None).await
+    }
+
+    async fn find_eod_summaries_by_account_id(
+        &self,
+        brokerage_account_id: ObjectId,
+    ) -> Result<Vec<EODSummary>> {
+        EODSummary::find_by_account_id(self, brokerage_account_id, None).await
+    }
+
+    async fn find_eod_summaries_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<EODSummary>> {
+        EODSummary::find_by_account_id_and_range(
+            self,
+            brokerage_account_id,
+            start_timestamp_ms,
+            end_timestamp_ms,
+            None,
+        )
+        .await
+    }
+}
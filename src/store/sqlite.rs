@@ -0,0 +1,506 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use bson::{Document, oid::ObjectId};
+use rusqlite::{Connection, ErrorCode, OptionalExtension, params, params_from_iter, types::Value};
+
+use super::{BrokerageStore, DuplicateKeyError};
+use crate::{
+    account::BrokerageAccount,
+    eod_summary::EODSummary,
+    migrations,
+    owner::Owner,
+    security::Security,
+    trade_execution::TradeExecution,
+    versioned::{self, Versioned},
+};
+
+/// Schema migrations mirroring the MongoDB migrations, applied in order.
+///
+/// Each table keeps the queried and uniquely indexed fields in columns and the
+/// full entity as a BSON document, so entity types are shared with MongoDB.
+/// Documents are stamped and upgraded through [`versioned`] as in MongoDB.
+const MIGRATIONS: &[(i64, &str)] = &[
+    (
+        1,
+        "CREATE TABLE brokerage_accounts (
+            id TEXT PRIMARY KEY,
+            brokerage_id TEXT NOT NULL,
+            account_id TEXT NOT NULL,
+            document BLOB NOT NULL
+        );
+        CREATE UNIQUE INDEX brokerage_account_unique_idx
+            ON brokerage_accounts (brokerage_id, account_id);",
+    ),
+    (
+        2,
+        "CREATE TABLE securities (
+            id TEXT PRIMARY KEY,
+            ticker TEXT NOT NULL,
+            listing_exchange TEXT NOT NULL,
+            ibkr_conid INTEGER,
+            document BLOB NOT NULL
+        );
+        CREATE UNIQUE INDEX securities_unique_idx ON securities (ticker, listing_exchange);
+        CREATE INDEX securities_conid_idx ON securities (ibkr_conid);",
+    ),
+    (
+        3,
+        "CREATE TABLE trade_executions (
+            id TEXT PRIMARY KEY,
+            brokerage_account_id TEXT NOT NULL,
+            brokerage_execution_id TEXT NOT NULL,
+            security_id TEXT NOT NULL,
+            execution_timestamp_ms INTEGER NOT NULL,
+            document BLOB NOT NULL
+        );
+        CREATE UNIQUE INDEX trade_executions_unique_idx
+            ON trade_executions (brokerage_account_id, brokerage_execution_id);
+        CREATE INDEX trade_executions_by_account_security_timestamp_idx
+            ON trade_executions (brokerage_account_id, security_id, execution_timestamp_ms);
+        CREATE INDEX trade_executions_by_account_timestamp_idx
+            ON trade_executions (brokerage_account_id, execution_timestamp_ms);",
+    ),
+    (
+        4,
+        "CREATE TABLE eod_summaries (
+            id TEXT PRIMARY KEY,
+            brokerage_account_id TEXT NOT NULL,
+            end_timestamp_ms INTEGER NOT NULL,
+            document BLOB NOT NULL
+        );
+        CREATE UNIQUE INDEX eod_summaries_unique_idx
+            ON eod_summaries (brokerage_account_id, end_timestamp_ms);",
+    ),
+    (
+        5,
+        "CREATE TABLE owners (
+            id TEXT PRIMARY KEY,
+            document BLOB NOT NULL
+        );
+        ALTER TABLE brokerage_accounts ADD COLUMN owner_id TEXT;
+        CREATE INDEX brokerage_accounts_by_owner_idx ON brokerage_accounts (owner_id);",
+    ),
+];
+
+/// [`BrokerageStore`] backed by a SQLite database file.
+///
+/// Queries run on tokio's blocking thread pool, one at a time, so they do not
+/// stall the async runtime.
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    /// Opens or creates the database at `path` and applies pending migrations.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        run_migrations(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` with the connection on the blocking thread pool.
+    async fn run<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Connection) -> Result<R> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&conn.lock().unwrap())).await?
+    }
+
+    async fn insert<T: Versioned>(
+        &self,
+        table: &'static str,
+        unique_index_name: &'static str,
+        entity: &T,
+        columns: &[&str],
+        mut values: Vec<Value>,
+    ) -> Result<()> {
+        values.push(Value::Blob(bson::to_vec(&versioned::to_document(entity)?)?));
+        let placeholders = vec!["?"; values.len()].join(", ");
+        let sql = format!(
+            "INSERT INTO {} ({}, document) VALUES ({})",
+            table,
+            columns.join(", "),
+            placeholders
+        );
+
+        let result = self
+            .run(move |conn| Ok(conn.execute(&sql, params_from_iter(values))))
+            .await?;
+        match result {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == ErrorCode::ConstraintViolation =>
+            {
+                let index_name = if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY {
+                    "_id_"
+                } else {
+                    unique_index_name
+                };
+                Err(DuplicateKeyError {
+                    collection_name: table,
+                    index_name,
+                }
+                .into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn find_one<T: Versioned>(
+        &self,
+        sql: &'static str,
+        values: Vec<Value>,
+    ) -> Result<Option<T>> {
+        let document: Option<Vec<u8>> = self
+            .run(move |conn| {
+                Ok(conn
+                    .query_row(sql, params_from_iter(values), |row| row.get(0))
+                    .optional()?)
+            })
+            .await?;
+        document.map(|d| from_slice(&d)).transpose()
+    }
+
+    async fn find_all<T: Versioned>(
+        &self,
+        sql: &'static str,
+        values: Vec<Value>,
+    ) -> Result<Vec<T>> {
+        let documents = self
+            .run(move |conn| {
+                let mut statement = conn.prepare(sql)?;
+                Ok(statement
+                    .query_map(params_from_iter(values), |row| row.get::<_, Vec<u8>>(0))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await?;
+        documents.iter().map(|d| from_slice(d)).collect()
+    }
+}
+
+/// Reads a stored document, upgrading it from older schema versions.
+fn from_slice<T: Versioned>(document: &[u8]) -> Result<T> {
+    versioned::from_document(bson::from_slice::<Document>(document)?)
+}
+
+fn run_migrations(conn: &mut Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY)",
+    )?;
+
+    for (version, sql) in MIGRATIONS {
+        let applied: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM schema_migrations WHERE version = ?)",
+            params![version],
+            |row| row.get(0),
+        )?;
+        if applied {
+            continue;
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version) VALUES (?)",
+            params![version],
+        )?;
+        tx.commit()?;
+        tracing::info!("applied sqlite migration {}", version);
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl BrokerageStore for SqliteStore {
+    async fn insert_owner(&self, owner: &Owner) -> Result<()> {
+        self.insert(
+            Owner::COLLECTION_NAME,
+            "_id_",
+            owner,
+            &["id"],
+            vec![owner.id().to_hex().into()],
+        )
+        .await
+    }
+
+    async fn find_owners(&self) -> Result<Vec<Owner>> {
+        self.find_all("SELECT document FROM owners ORDER BY rowid", vec![])
+            .await
+    }
+
+    async fn find_owner_by_id(&self, id: ObjectId) -> Result<Option<Owner>> {
+        self.find_one(
+            "SELECT document FROM owners WHERE id = ?",
+            vec![id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn insert_brokerage_account(&self, account: &BrokerageAccount) -> Result<()> {
+        self.insert(
+            BrokerageAccount::COLLECTION_NAME,
+            migrations::BROKERAGE_ACCOUNT_UNIQUE_INDEX_NAME,
+            account,
+            &["id", "brokerage_id", "account_id", "owner_id"],
+            vec![
+                account.id().to_hex().into(),
+                account.brokerage_id().to_owned().into(),
+                account.account_id().to_owned().into(),
+                account.owner_id().map(|id| id.to_hex()).into(),
+            ],
+        )
+        .await
+    }
+
+    async fn find_brokerage_accounts(&self) -> Result<Vec<BrokerageAccount>> {
+        self.find_all(
+            "SELECT document FROM brokerage_accounts ORDER BY rowid",
+            vec![],
+        )
+        .await
+    }
+
+    async fn find_brokerage_account_by_id(&self, id: ObjectId) -> Result<Option<BrokerageAccount>> {
+        self.find_one(
+            "SELECT document FROM brokerage_accounts WHERE id = ?",
+            vec![id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn find_brokerage_account_by_brokerage_and_account_id(
+        &self,
+        brokerage_id: &str,
+        account_id: &str,
+    ) -> Result<Option<BrokerageAccount>> {
+        self.find_one(
+            "SELECT document FROM brokerage_accounts WHERE brokerage_id = ? AND account_id = ?",
+            vec![brokerage_id.to_owned().into(), account_id.to_owned().into()],
+        )
+        .await
+    }
+
+    async fn find_brokerage_accounts_by_owner_id(
+        &self,
+        owner_id: ObjectId,
+    ) -> Result<Vec<BrokerageAccount>> {
+        self.find_all(
+            "SELECT document FROM brokerage_accounts WHERE owner_id = ? ORDER BY rowid",
+            vec![owner_id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn assign_brokerage_account_owner(
+        &self,
+        id: ObjectId,
+        owner_id: Option<ObjectId>,
+    ) -> Result<()> {
+        // Rewrites the stored document rather than reserializing the entity so
+        // fields written by newer builds survive, as with MongoDB's `$set`.
+        self.run(move |conn| {
+            let id = id.to_hex();
+            let document: Option<Vec<u8>> = conn
+                .query_row(
+                    "SELECT document FROM brokerage_accounts WHERE id = ?",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(document) = document else {
+                bail!("brokerage account {} not found", id);
+            };
+            let mut document: Document = bson::from_slice(&document)?;
+            document.insert("owner_id", owner_id);
+            conn.execute(
+                "UPDATE brokerage_accounts SET owner_id = ?, document = ? WHERE id = ?",
+                params![owner_id.map(|o| o.to_hex()), bson::to_vec(&document)?, id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_security(&self, security: &Security) -> Result<()> {
+        self.insert(
+            Security::COLLECTION_NAME,
+            migrations::SECURITIES_UNIQUE_INDEX_NAME,
+            security,
+            &["id", "ticker", "listing_exchange", "ibkr_conid"],
+            vec![
+                security.id().to_hex().into(),
+                security.ticker().to_owned().into(),
+                security.listing_exchange().to_owned().into(),
+                security.ibkr_conid().into(),
+            ],
+        )
+        .await
+    }
+
+    async fn find_security_by_id(&self, id: ObjectId) -> Result<Option<Security>> {
+        self.find_one(
+            "SELECT document FROM securities WHERE id = ?",
+            vec![id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn find_security_by_ticker_and_exchange(
+        &self,
+        ticker: &str,
+        listing_exchange: &str,
+    ) -> Result<Option<Security>> {
+        self.find_one(
+            "SELECT document FROM securities WHERE ticker = ? AND listing_exchange = ?",
+            vec![ticker.to_owned().into(), listing_exchange.to_owned().into()],
+        )
+        .await
+    }
+
+    async fn find_security_by_conid(&self, ibkr_conid: u32) -> Result<Option<Security>> {
+        self.find_one(
+            "SELECT document FROM securities WHERE ibkr_conid = ?",
+            vec![ibkr_conid.into()],
+        )
+        .await
+    }
+
+    async fn find_securities_by_ticker(&self, ticker: &str) -> Result<Vec<Security>> {
+        self.find_all(
+            "SELECT document FROM securities WHERE ticker = ? ORDER BY rowid",
+            vec![ticker.to_owned().into()],
+        )
+        .await
+    }
+
+    async fn insert_trade_execution(&self, trade_execution: &TradeExecution) -> Result<()> {
+        self.insert(
+            TradeExecution::COLLECTION_NAME,
+            migrations::TRADE_EXECUTIONS_UNIQUE_INDEX_NAME,
+            trade_execution,
+            &[
+                "id",
+                "brokerage_account_id",
+                "brokerage_execution_id",
+                "security_id",
+                "execution_timestamp_ms",
+            ],
+            vec![
+                trade_execution.id().to_hex().into(),
+                trade_execution.brokerage_account_id().to_hex().into(),
+                trade_execution.brokerage_execution_id().to_owned().into(),
+                trade_execution.security_id().to_hex().into(),
+                trade_execution.execution_timestamp_ms().into(),
+            ],
+        )
+        .await
+    }
+
+    async fn find_trade_execution_by_id(&self, id: ObjectId) -> Result<Option<TradeExecution>> {
+        self.find_one(
+            "SELECT document FROM trade_executions WHERE id = ?",
+            vec![id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn find_trade_execution_by_brokerage_execution_id(
+        &self,
+        execution_id: &str,
+    ) -> Result<Option<TradeExecution>> {
+        self.find_one(
+            "SELECT document FROM trade_executions WHERE brokerage_execution_id = ?",
+            vec![execution_id.to_owned().into()],
+        )
+        .await
+    }
+
+    async fn find_trade_executions_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<TradeExecution>> {
+        self.find_all(
+            "SELECT document FROM trade_executions
+             WHERE brokerage_account_id = ? AND execution_timestamp_ms BETWEEN ? AND ?
+             ORDER BY execution_timestamp_ms",
+            vec![
+                brokerage_account_id.to_hex().into(),
+                start_timestamp_ms.into(),
+                end_timestamp_ms.into(),
+            ],
+        )
+        .await
+    }
+
+    async fn insert_eod_summary(&self, eod_summary: &EODSummary) -> Result<()> {
+        self.insert(
+            EODSummary::COLLECTION_NAME,
+            migrations::EOD_SUMMARIES_UNIQUE_INDEX_NAME,
+            eod_summary,
+            &["id", "brokerage_account_id", "end_timestamp_ms"],
+            vec![
+                eod_summary.id().to_hex().into(),
+                eod_summary.brokerage_account_id().to_hex().into(),
+                eod_summary.end_timestamp_ms().into(),
+            ],
+        )
+        .await
+    }
+
+    async fn find_eod_summary_by_id(&self, id: ObjectId) -> Result<Option<EODSummary>> {
+        self.find_one(
+            "SELECT document FROM eod_summaries WHERE id = ?",
+            vec![id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn find_eod_summaries_by_account_id(
+        &self,
+        brokerage_account_id: ObjectId,
+    ) -> Result<Vec<EODSummary>> {
+        self.find_all(
+            "SELECT document FROM eod_summaries WHERE brokerage_account_id = ? ORDER BY rowid",
+            vec![brokerage_account_id.to_hex().into()],
+        )
+        .await
+    }
+
+    async fn find_eod_summaries_by_account_id_and_range(
+        &self,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+    ) -> Result<Vec<EODSummary>> {
+        self.find_all(
+            "SELECT document FROM eod_summaries
+             WHERE brokerage_account_id = ? AND end_timestamp_ms BETWEEN ? AND ?
+             ORDER BY end_timestamp_ms",
+            vec![
+                brokerage_account_id.to_hex().into(),
+                start_timestamp_ms.into(),
+                end_timestamp_ms.into(),
+            ],
+        )
+        .await
+    }
+}
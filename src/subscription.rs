@@ -0,0 +1,218 @@
+//! Real-time notification of entity changes.
+//!
+//! A [`Subscription`] wraps a MongoDB change stream on one entity collection
+//! and yields typed insert, update and replace events, optionally only those of
+//! some brokerage accounts. A named consumer's position is kept in the
+//! `resume_tokens` collection, so a restarted consumer continues after the
+//! last change it acknowledged. Change streams require a replica set.
+
+use std::marker::PhantomData;
+
+use anyhow::{Result, bail};
+use bson::{Document, doc, oid::ObjectId};
+use futures::TryStreamExt;
+use mongodb::{
+    Collection,
+    change_stream::{
+        ChangeStream,
+        event::{ChangeStreamEvent, OperationType, ResumeToken},
+    },
+    options::FullDocumentType,
+};
+
+use crate::{
+    namespace::Namespace,
+    versioned::{self, Versioned},
+};
+
+pub const RESUME_TOKENS_COLLECTION_NAME: &str = "resume_tokens";
+
+/// An entity whose collection can be subscribed to.
+pub trait Watched: Versioned + Send + Sync {
+    fn collection_name() -> &'static str;
+
+    /// The field holding the owning brokerage account, for entities that
+    /// belong to one.
+    fn account_field() -> Option<&'static str> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Replace,
+}
+
+/// A document after it was inserted, updated or replaced.
+#[derive(Clone, Debug)]
+pub struct Change<T> {
+    kind: ChangeKind,
+    document: T,
+    resume_token: ResumeToken,
+}
+
+impl<T> Change<T> {
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    pub fn document(&self) -> &T {
+        &self.document
+    }
+
+    pub fn into_document(self) -> T {
+        self.document
+    }
+
+    pub fn resume_token(&self) -> &ResumeToken {
+        &self.resume_token
+    }
+}
+
+pub struct Subscription<T> {
+    stream: ChangeStream<ChangeStreamEvent<Document>>,
+    resume_tokens: Option<ResumeTokenStore>,
+    unacknowledged: Option<ResumeToken>,
+    _entity: PhantomData<T>,
+}
+
+impl<T: Watched> Subscription<T> {
+    pub fn builder() -> Builder<T> {
+        Builder::new()
+    }
+
+    /// Waits for the next change, acknowledging the one returned before.
+    ///
+    /// Returns `None` once the stream is invalidated, e.g. when the collection
+    /// is dropped.
+    pub async fn next(&mut self) -> Result<Option<Change<T>>> {
+        self.acknowledge().await?;
+
+        while let Some(event) = self.stream.try_next().await? {
+            let kind = match event.operation_type {
+                OperationType::Insert => ChangeKind::Insert,
+                OperationType::Update => ChangeKind::Update,
+                OperationType::Replace => ChangeKind::Replace,
+                _ => continue,
+            };
+            // An updated document deleted before the lookup has no full document.
+            let Some(document) = event.full_document else {
+                continue;
+            };
+
+            self.unacknowledged = Some(event.id.clone());
+            return Ok(Some(Change {
+                kind,
+                document: versioned::from_document(document)?,
+                resume_token: event.id,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Records the last returned change as processed, so a restarted consumer
+    /// resumes after it.
+    pub async fn acknowledge(&mut self) -> Result<()> {
+        if let (Some(store), Some(token)) = (&self.resume_tokens, self.unacknowledged.take()) {
+            store.save(&token).await?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Builder<T> {
+    consumer: Option<String>,
+    brokerage_account_ids: Vec<ObjectId>,
+    _entity: PhantomData<T>,
+}
+
+impl<T: Watched> Builder<T> {
+    fn new() -> Self {
+        Self {
+            consumer: None,
+            brokerage_account_ids: Vec::new(),
+            _entity: PhantomData,
+        }
+    }
+
+    /// Names the consumer whose position is persisted. Without one, the
+    /// subscription starts at the current time on every run.
+    pub fn consumer(mut self, consumer: &str) -> Self {
+        self.consumer = Some(consumer.to_owned());
+        self
+    }
+
+    /// Only yields changes of the given account. May be repeated.
+    pub fn brokerage_account_id(mut self, id: ObjectId) -> Self {
+        self.brokerage_account_ids.push(id);
+        self
+    }
+
+    pub async fn watch(self, db: &impl Namespace) -> Result<Subscription<T>> {
+        let mut filter = doc! { "operationType": { "$in": ["insert", "update", "replace"] } };
+        if !self.brokerage_account_ids.is_empty() {
+            let Some(account_field) = T::account_field() else {
+                bail!("{} cannot be filtered by account", T::collection_name());
+            };
+            filter.insert(
+                format!("fullDocument.{}", account_field),
+                doc! { "$in": &self.brokerage_account_ids },
+            );
+        }
+
+        let resume_tokens = self.consumer.map(|consumer| ResumeTokenStore {
+            collection: db.collection(RESUME_TOKENS_COLLECTION_NAME),
+            key: format!("{}/{}", consumer, T::collection_name()),
+        });
+        let resume_after = match &resume_tokens {
+            Some(store) => store.load().await?,
+            None => None,
+        };
+
+        let stream = db
+            .collection::<Document>(T::collection_name())
+            .watch()
+            .pipeline([doc! { "$match": filter }])
+            .full_document(FullDocumentType::UpdateLookup)
+            .resume_after(resume_after)
+            .await?;
+
+        Ok(Subscription {
+            stream,
+            resume_tokens,
+            unacknowledged: None,
+            _entity: PhantomData,
+        })
+    }
+}
+
+/// A consumer's last acknowledged position on one collection.
+struct ResumeTokenStore {
+    collection: Collection<Document>,
+    key: String,
+}
+
+impl ResumeTokenStore {
+    async fn load(&self) -> Result<Option<ResumeToken>> {
+        let Some(record) = self.collection.find_one(doc! { "_id": &self.key }).await? else {
+            return Ok(None);
+        };
+        match record.get("resume_token") {
+            Some(token) => Ok(Some(bson::from_bson(token.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, token: &ResumeToken) -> Result<()> {
+        self.collection
+            .update_one(
+                doc! { "_id": &self.key },
+                doc! { "$set": { "resume_token": bson::to_bson(token)? } },
+            )
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+}
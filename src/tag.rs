@@ -0,0 +1,332 @@
+//! Strategy labels and free-form tags on trade executions.
+//!
+//! Executions carry the ids of their [`Tag`]s, so several strategies trading
+//! in the same brokerage account can be told apart. Round trips inherit the
+//! tags of their executions, and [`TagPnl`] rolls P&L and open positions up
+//! per tag.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::Result;
+use bson::{Document, oid::ObjectId};
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    db_util,
+    lot::{LotReport, OpenLot},
+    namespace::Namespace,
+    round_trip::{RoundTrip, TradingStatistics},
+    trade_execution::{TradeExecution, TradeSide},
+    versioned::Versioned,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagKind {
+    /// A trading strategy the execution is attributed to.
+    Strategy,
+    /// Any other label.
+    Label,
+}
+
+/// A named label that can be attached to trade executions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Tag {
+    _id: ObjectId,
+    name: String,
+    kind: TagKind,
+}
+
+impl Tag {
+    pub const COLLECTION_NAME: &'static str = "tags";
+
+    pub fn new(kind: TagKind, name: &str) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            name: name.to_owned(),
+            kind,
+        }
+    }
+
+    pub fn id(&self) -> ObjectId {
+        self._id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> TagKind {
+        self.kind
+    }
+
+    pub async fn insert(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<()> {
+        db_util::insert(self, db, Self::COLLECTION_NAME, session).await
+    }
+
+    pub async fn find(
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {},
+            Some(bson::doc! { "name": 1 }),
+            session,
+        )
+        .await
+    }
+
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
+    }
+
+    pub async fn find_by_name(
+        db: &impl Namespace,
+        name: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"name": name},
+            session,
+        )
+        .await
+    }
+
+    /// Attaches the tag to every execution matching `query` and returns the
+    /// number of executions that did not have it yet.
+    pub async fn tag_executions(
+        &self,
+        db: &impl Namespace,
+        query: &ExecutionQuery,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<u64> {
+        db_util::update_many::<TradeExecution>(
+            db,
+            TradeExecution::COLLECTION_NAME,
+            query.filter()?,
+            bson::doc! { "$addToSet": { "tag_ids": self._id } },
+            session,
+        )
+        .await
+    }
+
+    /// Removes the tag from every execution matching `query` and returns the
+    /// number of executions that had it.
+    pub async fn untag_executions(
+        &self,
+        db: &impl Namespace,
+        query: &ExecutionQuery,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<u64> {
+        db_util::update_many::<TradeExecution>(
+            db,
+            TradeExecution::COLLECTION_NAME,
+            query.filter()?,
+            bson::doc! { "$pull": { "tag_ids": self._id } },
+            session,
+        )
+        .await
+    }
+
+    pub async fn executions(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<TradeExecution>> {
+        TradeExecution::find_by_tag_id(db, self._id, session).await
+    }
+}
+
+impl Versioned for Tag {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+/// Selects the trade executions of one account to tag or untag in bulk.
+///
+/// Criteria that are not set match every execution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionQuery {
+    brokerage_account_id: ObjectId,
+    security_id: Option<ObjectId>,
+    side: Option<TradeSide>,
+    start_timestamp_ms: Option<i64>,
+    end_timestamp_ms: Option<i64>,
+    order_id: Option<ObjectId>,
+    strategy_tag: Option<String>,
+}
+
+impl ExecutionQuery {
+    pub fn new(brokerage_account_id: ObjectId) -> Self {
+        Self {
+            brokerage_account_id,
+            security_id: None,
+            side: None,
+            start_timestamp_ms: None,
+            end_timestamp_ms: None,
+            order_id: None,
+            strategy_tag: None,
+        }
+    }
+
+    pub fn security_id(mut self, id: ObjectId) -> Self {
+        self.security_id = Some(id);
+        self
+    }
+
+    pub fn side(mut self, side: TradeSide) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    /// Executions within `[start_timestamp_ms, end_timestamp_ms]`.
+    pub fn range(mut self, start_timestamp_ms: i64, end_timestamp_ms: i64) -> Self {
+        self.start_timestamp_ms = Some(start_timestamp_ms);
+        self.end_timestamp_ms = Some(end_timestamp_ms);
+        self
+    }
+
+    pub fn order_id(mut self, id: ObjectId) -> Self {
+        self.order_id = Some(id);
+        self
+    }
+
+    /// Executions the broker attributed to `strategy_tag`.
+    pub fn strategy_tag(mut self, strategy_tag: &str) -> Self {
+        self.strategy_tag = Some(strategy_tag.to_owned());
+        self
+    }
+
+    fn filter(&self) -> Result<Document> {
+        let mut filter = bson::doc! { "brokerage_account_id": self.brokerage_account_id };
+        if let Some(id) = self.security_id {
+            filter.insert("security_id", id);
+        }
+        if let Some(side) = &self.side {
+            filter.insert("side", bson::to_bson(side)?);
+        }
+        if let (Some(start), Some(end)) = (self.start_timestamp_ms, self.end_timestamp_ms) {
+            filter.insert(
+                "execution_timestamp_ms",
+                bson::doc! { "$gte": start, "$lte": end },
+            );
+        }
+        if let Some(id) = self.order_id {
+            filter.insert("order_id", id);
+        }
+        if let Some(strategy_tag) = &self.strategy_tag {
+            filter.insert("strategy_tag", strategy_tag);
+        }
+        Ok(filter)
+    }
+}
+
+/// Round trips, statistics and open lots of the executions carrying one tag.
+///
+/// Each tag's executions are grouped into round trips and lots on their own,
+/// as if the tag traded in a separate account, so strategies sharing an
+/// account and security do not net against each other. Untagged executions
+/// are left out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagPnl {
+    round_trips: Vec<RoundTrip>,
+    statistics: TradingStatistics,
+    open_lots: Vec<OpenLot>,
+}
+
+impl TagPnl {
+    /// P&L and open positions per tag over the given executions.
+    ///
+    /// Fails if a tag closes more than it opened, as [`LotReport`] does.
+    pub fn by_tag(executions: &[TradeExecution]) -> Result<BTreeMap<ObjectId, Self>> {
+        let mut by_tag: BTreeMap<ObjectId, Vec<TradeExecution>> = BTreeMap::new();
+        for execution in executions {
+            for tag_id in execution.tag_ids() {
+                by_tag.entry(*tag_id).or_default().push(execution.clone());
+            }
+        }
+        by_tag
+            .into_iter()
+            .map(|(tag_id, executions)| {
+                let round_trips = RoundTrip::from_executions(&executions);
+                let open_lots = LotReport::from_executions(&executions)?
+                    .open_lots()
+                    .to_vec();
+                Ok((tag_id, Self::new(round_trips, open_lots)))
+            })
+            .collect()
+    }
+
+    /// P&L per tag of the account's round trips that closed within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, and each tag's open lots at
+    /// `end_timestamp_ms`.
+    pub async fn for_account_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<BTreeMap<ObjectId, Self>> {
+        // Start from the account's first execution so that round trips opened
+        // before the range are grouped correctly.
+        let executions = TradeExecution::find_by_account_id_and_range(
+            db,
+            brokerage_account_id,
+            i64::MIN,
+            end_timestamp_ms,
+            session,
+        )
+        .await?;
+
+        Ok(Self::by_tag(&executions)?
+            .into_iter()
+            .map(|(tag_id, pnl)| {
+                let round_trips = pnl
+                    .round_trips
+                    .into_iter()
+                    .filter(|rt| rt.exit_timestamp_ms() >= start_timestamp_ms)
+                    .collect();
+                (tag_id, Self::new(round_trips, pnl.open_lots))
+            })
+            .collect())
+    }
+
+    fn new(round_trips: Vec<RoundTrip>, open_lots: Vec<OpenLot>) -> Self {
+        let statistics = TradingStatistics::from_round_trips(&round_trips);
+        Self {
+            round_trips,
+            statistics,
+            open_lots,
+        }
+    }
+
+    /// Closed round trips, oldest exit first.
+    pub fn round_trips(&self) -> &[RoundTrip] {
+        &self.round_trips
+    }
+
+    pub fn statistics(&self) -> &TradingStatistics {
+        &self.statistics
+    }
+
+    pub fn net_pnl(&self) -> f64 {
+        self.statistics.net_pnl()
+    }
+
+    /// Lots opened by the tag's executions and not yet closed by them.
+    pub fn open_lots(&self) -> &[OpenLot] {
+        &self.open_lots
+    }
+}
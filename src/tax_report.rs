@@ -0,0 +1,316 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use anyhow::Result;
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use tokio::sync::Mutex;
+
+use crate::{
+    account::BrokerageAccount,
+    date_util,
+    lot::{ClosedLot, LotReport},
+    namespace::Namespace,
+    security::Security,
+};
+
+/// A calendar date as shown on Form 8949. Dates are taken in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaxDate {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl TaxDate {
+    pub fn from_timestamp_ms(timestamp_ms: i64) -> Self {
+        let (year, month, day) = date_util::civil_from_timestamp_ms(timestamp_ms);
+        Self { year, month, day }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+}
+
+impl fmt::Display for TaxDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}/{:02}/{:04}", self.month, self.day, self.year)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HoldingPeriod {
+    ShortTerm,
+    LongTerm,
+}
+
+/// One Form 8949 line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RealizedGain {
+    description: String,
+    date_acquired: TaxDate,
+    date_sold: TaxDate,
+    proceeds: f64,
+    cost_basis: f64,
+    adjustment_codes: String,
+    adjustment_amount: f64,
+    gain_loss: f64,
+    holding_period: HoldingPeriod,
+}
+
+impl RealizedGain {
+    fn from_closed_lot(lot: &ClosedLot, description: String) -> Self {
+        let holding_period =
+            if date_util::is_long_term(lot.acquired_timestamp_ms(), lot.disposed_timestamp_ms()) {
+                HoldingPeriod::LongTerm
+            } else {
+                HoldingPeriod::ShortTerm
+            };
+        let adjustment_codes = if lot.is_wash_sale() { "W" } else { "" };
+
+        Self {
+            description,
+            date_acquired: TaxDate::from_timestamp_ms(lot.acquired_timestamp_ms()),
+            date_sold: TaxDate::from_timestamp_ms(lot.disposed_timestamp_ms()),
+            proceeds: lot.proceeds(),
+            cost_basis: lot.cost_basis(),
+            adjustment_codes: adjustment_codes.to_owned(),
+            adjustment_amount: lot.disallowed_loss(),
+            gain_loss: lot.gain_loss(),
+            holding_period,
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn date_acquired(&self) -> TaxDate {
+        self.date_acquired
+    }
+
+    pub fn date_sold(&self) -> TaxDate {
+        self.date_sold
+    }
+
+    pub fn proceeds(&self) -> f64 {
+        self.proceeds
+    }
+
+    pub fn cost_basis(&self) -> f64 {
+        self.cost_basis
+    }
+
+    /// Form 8949 column (f), e.g. `W` for a wash sale.
+    pub fn adjustment_codes(&self) -> &str {
+        &self.adjustment_codes
+    }
+
+    /// Form 8949 column (g).
+    pub fn adjustment_amount(&self) -> f64 {
+        self.adjustment_amount
+    }
+
+    pub fn gain_loss(&self) -> f64 {
+        self.gain_loss
+    }
+
+    pub fn holding_period(&self) -> HoldingPeriod {
+        self.holding_period
+    }
+}
+
+/// Schedule D style totals for one holding period.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RealizedGainTotals {
+    pub proceeds: f64,
+    pub cost_basis: f64,
+    pub adjustment_amount: f64,
+    pub gain_loss: f64,
+}
+
+/// Realized gains for one tax year, split by holding period.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RealizedGainsReport {
+    tax_year: i32,
+    short_term: Vec<RealizedGain>,
+    long_term: Vec<RealizedGain>,
+}
+
+impl RealizedGainsReport {
+    pub const CSV_HEADER: &'static str = "Term,Description,Date Acquired,Date Sold,Proceeds,Cost Basis,Adjustment Code,Adjustment Amount,Gain or Loss";
+
+    /// Builds the report from closed lots disposed of during `tax_year`.
+    /// Lots whose security is missing from `securities` are described by id.
+    pub fn from_closed_lots(
+        tax_year: i32,
+        closed_lots: &[ClosedLot],
+        securities: &HashMap<ObjectId, Security>,
+    ) -> Self {
+        let mut short_term = Vec::new();
+        let mut long_term = Vec::new();
+
+        for lot in closed_lots {
+            if TaxDate::from_timestamp_ms(lot.disposed_timestamp_ms()).year != tax_year {
+                continue;
+            }
+
+            let name = securities
+                .get(&lot.security_id())
+                .map(|s| s.ticker().to_owned())
+                .unwrap_or_else(|| lot.security_id().to_hex());
+            let gain =
+                RealizedGain::from_closed_lot(lot, format!("{} sh {}", lot.quantity(), name));
+            match gain.holding_period {
+                HoldingPeriod::ShortTerm => short_term.push(gain),
+                HoldingPeriod::LongTerm => long_term.push(gain),
+            }
+        }
+
+        for gains in [&mut short_term, &mut long_term] {
+            gains.sort_by(|a, b| {
+                (a.date_sold, a.date_acquired).cmp(&(b.date_sold, b.date_acquired))
+            });
+        }
+
+        Self {
+            tax_year,
+            short_term,
+            long_term,
+        }
+    }
+
+    /// Matches all executions of the given accounts to lots, applying the wash
+    /// sale rule across them, and reports the gains realized in `tax_year`.
+    pub async fn for_accounts(
+        db: &impl Namespace,
+        brokerage_account_ids: &[ObjectId],
+        tax_year: i32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let lots =
+            LotReport::for_accounts(db, brokerage_account_ids, true, session.clone()).await?;
+        Self::load(db, tax_year, lots.closed_lots(), session).await
+    }
+
+    /// Reports the gains realized in `tax_year` across the owner's taxable
+    /// accounts. Purchases in tax-advantaged accounts still trigger wash sales,
+    /// and the losses they disallow are lost for good.
+    pub async fn for_owner(
+        db: &impl Namespace,
+        owner_id: ObjectId,
+        tax_year: i32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let accounts = BrokerageAccount::find_by_owner_id(db, owner_id, session.clone()).await?;
+        let account_ids: Vec<ObjectId> = accounts.iter().map(BrokerageAccount::id).collect();
+        let lots = LotReport::load(db, &account_ids, &accounts, true, session.clone()).await?;
+
+        let taxable_lots: Vec<ClosedLot> = lots
+            .closed_lots()
+            .iter()
+            .filter(|lot| {
+                accounts
+                    .iter()
+                    .any(|a| a.id() == lot.brokerage_account_id() && a.is_taxable())
+            })
+            .cloned()
+            .collect();
+        Self::load(db, tax_year, &taxable_lots, session).await
+    }
+
+    async fn load(
+        db: &impl Namespace,
+        tax_year: i32,
+        closed_lots: &[ClosedLot],
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let mut security_ids: Vec<ObjectId> = closed_lots.iter().map(|l| l.security_id()).collect();
+        security_ids.sort();
+        security_ids.dedup();
+
+        let mut securities = HashMap::new();
+        for id in security_ids {
+            if let Some(security) = Security::find_by_id(db, id, session.clone()).await? {
+                securities.insert(id, security);
+            }
+        }
+
+        Ok(Self::from_closed_lots(tax_year, closed_lots, &securities))
+    }
+
+    pub fn tax_year(&self) -> i32 {
+        self.tax_year
+    }
+
+    pub fn short_term(&self) -> &[RealizedGain] {
+        &self.short_term
+    }
+
+    pub fn long_term(&self) -> &[RealizedGain] {
+        &self.long_term
+    }
+
+    pub fn short_term_totals(&self) -> RealizedGainTotals {
+        totals(&self.short_term)
+    }
+
+    pub fn long_term_totals(&self) -> RealizedGainTotals {
+        totals(&self.long_term)
+    }
+
+    /// Renders all lines as CSV, short-term first.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(Self::CSV_HEADER);
+        csv.push('\n');
+
+        let rows = self
+            .short_term
+            .iter()
+            .map(|g| ("Short", g))
+            .chain(self.long_term.iter().map(|g| ("Long", g)));
+        for (term, gain) in rows {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.2},{:.2},{},{:.2},{:.2}\n",
+                term,
+                csv_field(&gain.description),
+                gain.date_acquired,
+                gain.date_sold,
+                gain.proceeds,
+                gain.cost_basis,
+                gain.adjustment_codes,
+                gain.adjustment_amount,
+                gain.gain_loss,
+            ));
+        }
+
+        csv
+    }
+}
+
+fn totals(gains: &[RealizedGain]) -> RealizedGainTotals {
+    gains
+        .iter()
+        .fold(RealizedGainTotals::default(), |acc, g| RealizedGainTotals {
+            proceeds: acc.proceeds + g.proceeds,
+            cost_basis: acc.cost_basis + g.cost_basis,
+            adjustment_amount: acc.adjustment_amount + g.adjustment_amount,
+            gain_loss: acc.gain_loss + g.gain_loss,
+        })
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
@@ -1,9 +1,18 @@
 use std::sync::Arc;
 
-use crate::{account::BrokerageAccount, db_util, security::Security};
-use anyhow::Result;
+use crate::{
+    account::BrokerageAccount,
+    db_util,
+    fee::{Fee, FeeCategory},
+    namespace::Namespace,
+    order::Order,
+    security::Security,
+    subscription::Watched,
+    versioned::Versioned,
+};
+use anyhow::{Result, bail};
 use bson::oid::ObjectId;
-use mongodb::{ClientSession, Database};
+use mongodb::ClientSession;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
@@ -12,17 +21,53 @@ pub enum TradeSide {
     Buy,
     Sell,
 }
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+
+/// Whether the execution added liquidity to the book or took it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Liquidity {
+    Added,
+    Removed,
+}
+
+/// Whether the execution opened or closed a position. A sell that opens is a
+/// short sale and a buy that closes covers one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionEffect {
+    Open,
+    Close,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct TradeExecution {
     _id: bson::oid::ObjectId,
     brokerage_account_id: bson::oid::ObjectId,
     brokerage_execution_id: String,
+    /// Total of `fees` when itemized.
     commission: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fees: Vec<Fee>,
     execution_timestamp_ms: i64,
     quantity: f64,
     price: f64,
     security_id: bson::oid::ObjectId,
     side: TradeSide,
+    /// The order this execution filled, if known.
+    order_id: Option<ObjectId>,
+
+    // Routing metadata, as far as the broker reports it.
+    /// Exchange or venue that executed the trade, e.g. a MIC.
+    venue: Option<String>,
+    liquidity: Option<Liquidity>,
+    /// The broker's or trader's reference for the order.
+    order_reference: Option<String>,
+    /// Trader or strategy the execution is attributed to.
+    strategy_tag: Option<String>,
+    position_effect: Option<PositionEffect>,
+
+    /// [`Tag`](crate::tag::Tag)s attached to the execution, in the order
+    /// they were added.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tag_ids: Vec<ObjectId>,
 }
 
 impl TradeExecution {
@@ -48,10 +93,30 @@ impl TradeExecution {
         self.execution_timestamp_ms
     }
 
+    /// All fees net of rebates, the total of [`TradeExecution::fees`]. Positive
+    /// for a cost and negative for a net rebate.
     pub fn commission(&self) -> f64 {
         self.commission
     }
 
+    /// The itemized fees, or the commission alone if the broker reported no
+    /// breakdown.
+    pub fn fees(&self) -> Vec<Fee> {
+        if self.fees.is_empty() {
+            vec![Fee::new(FeeCategory::Commission, self.commission)]
+        } else {
+            self.fees.clone()
+        }
+    }
+
+    pub fn fee(&self, category: FeeCategory) -> f64 {
+        self.fees()
+            .iter()
+            .filter(|f| f.category == category)
+            .map(|f| f.amount)
+            .sum()
+    }
+
     pub fn quantity(&self) -> f64 {
         self.quantity
     }
@@ -68,43 +133,239 @@ impl TradeExecution {
         &self.side
     }
 
+    pub fn order_id(&self) -> Option<ObjectId> {
+        self.order_id
+    }
+
+    pub fn venue(&self) -> Option<&str> {
+        self.venue.as_deref()
+    }
+
+    pub fn liquidity(&self) -> Option<Liquidity> {
+        self.liquidity
+    }
+
+    pub fn order_reference(&self) -> Option<&str> {
+        self.order_reference.as_deref()
+    }
+
+    pub fn strategy_tag(&self) -> Option<&str> {
+        self.strategy_tag.as_deref()
+    }
+
+    pub fn position_effect(&self) -> Option<PositionEffect> {
+        self.position_effect
+    }
+
+    pub fn tag_ids(&self) -> &[ObjectId] {
+        &self.tag_ids
+    }
+
+    pub fn has_tag(&self, tag_id: ObjectId) -> bool {
+        self.tag_ids.contains(&tag_id)
+    }
+
     pub async fn insert(
         &self,
-        db: &Database,
+        db: &impl Namespace,
         session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<()> {
         db_util::insert(self, db, Self::COLLECTION_NAME, session).await
     }
 
-    pub async fn find_by_id(db: &Database, id: ObjectId) -> Result<Option<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(bson::doc! {"_id": id})
-            .await?;
-
-        Ok(result)
+    pub async fn find_by_id(
+        db: &impl Namespace,
+        id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Self>> {
+        db_util::find_one(db, Self::COLLECTION_NAME, bson::doc! {"_id": id}, session).await
     }
 
     pub async fn find_by_brokerage_execution_id(
-        db: &Database,
+        db: &impl Namespace,
         execution_id: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
     ) -> Result<Option<Self>> {
-        let result = db
-            .collection::<Self>(Self::COLLECTION_NAME)
-            .find_one(bson::doc! {"brokerage_execution_id": execution_id})
-            .await?;
+        db_util::find_one(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {"brokerage_execution_id": execution_id},
+            session,
+        )
+        .await
+    }
+
+    /// Returns the account's executions with a timestamp within
+    /// `[start_timestamp_ms, end_timestamp_ms]`, oldest first.
+    pub async fn find_by_account_id_and_range(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        start_timestamp_ms: i64,
+        end_timestamp_ms: i64,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! {
+                "brokerage_account_id": brokerage_account_id,
+                "execution_timestamp_ms": { "$gte": start_timestamp_ms, "$lte": end_timestamp_ms },
+            },
+            Some(bson::doc! { "execution_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+
+    /// Returns the account's executions on `venue`, oldest first.
+    pub async fn find_by_account_id_and_venue(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        venue: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        Self::find_by_account_id_and_field(db, brokerage_account_id, "venue", venue, session).await
+    }
+
+    /// Returns the account's executions attributed to `strategy_tag`, oldest
+    /// first.
+    pub async fn find_by_account_id_and_strategy_tag(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        strategy_tag: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        Self::find_by_account_id_and_field(
+            db,
+            brokerage_account_id,
+            "strategy_tag",
+            strategy_tag,
+            session,
+        )
+        .await
+    }
+
+    /// Returns the account's executions with the order reference, oldest
+    /// first.
+    pub async fn find_by_account_id_and_order_reference(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        order_reference: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        Self::find_by_account_id_and_field(
+            db,
+            brokerage_account_id,
+            "order_reference",
+            order_reference,
+            session,
+        )
+        .await
+    }
+
+    async fn find_by_account_id_and_field(
+        db: &impl Namespace,
+        brokerage_account_id: ObjectId,
+        field: &str,
+        value: &str,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! { "brokerage_account_id": brokerage_account_id, field: value },
+            Some(bson::doc! { "execution_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
 
-        Ok(result)
+    /// Returns the executions that filled the order, oldest first.
+    pub async fn find_by_order_id(
+        db: &impl Namespace,
+        order_id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        Self::find_by_order_ids(db, &[order_id], session).await
+    }
+
+    /// Returns the executions that filled any of the orders, oldest first.
+    pub async fn find_by_order_ids(
+        db: &impl Namespace,
+        order_ids: &[ObjectId],
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! { "order_id": { "$in": order_ids } },
+            Some(bson::doc! { "execution_timestamp_ms": 1 }),
+            session,
+        )
+        .await
+    }
+
+    /// Returns the executions carrying the tag, oldest first.
+    pub async fn find_by_tag_id(
+        db: &impl Namespace,
+        tag_id: ObjectId,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Vec<Self>> {
+        db_util::find(
+            db,
+            Self::COLLECTION_NAME,
+            bson::doc! { "tag_ids": tag_id },
+            Some(bson::doc! { "execution_timestamp_ms": 1 }),
+            session,
+        )
+        .await
     }
 
-    pub async fn brokerage_account(&self, db: &Database) -> Result<BrokerageAccount> {
-        Ok(BrokerageAccount::find_by_id(db, self.brokerage_account_id)
+    pub async fn brokerage_account(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<BrokerageAccount> {
+        Ok(
+            BrokerageAccount::find_by_id(db, self.brokerage_account_id, session)
+                .await?
+                .unwrap(),
+        )
+    }
+
+    pub async fn security(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Security> {
+        Ok(Security::find_by_id(db, self.security_id, session)
             .await?
             .unwrap())
     }
 
-    pub async fn security(&self, db: &Database) -> Result<Security> {
-        Ok(Security::find_by_id(db, self.security_id).await?.unwrap())
+    pub async fn order(
+        &self,
+        db: &impl Namespace,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Option<Order>> {
+        match self.order_id {
+            Some(order_id) => Order::find_by_id(db, order_id, session).await,
+            None => Ok(None),
+        }
+    }
+}
+
+impl Versioned for TradeExecution {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl Watched for TradeExecution {
+    fn collection_name() -> &'static str {
+        Self::COLLECTION_NAME
+    }
+
+    fn account_field() -> Option<&'static str> {
+        Some("brokerage_account_id")
     }
 }
 
@@ -114,10 +375,18 @@ pub struct Builder {
     brokerage_execution_id: Option<String>,
     execution_timestamp_ms: Option<i64>,
     commission: Option<f64>,
+    fees: Vec<Fee>,
     quantity: Option<f64>,
     price: Option<f64>,
     security_id: Option<bson::oid::ObjectId>,
     side: Option<TradeSide>,
+    order_id: Option<ObjectId>,
+    venue: Option<String>,
+    liquidity: Option<Liquidity>,
+    order_reference: Option<String>,
+    strategy_tag: Option<String>,
+    position_effect: Option<PositionEffect>,
+    tag_ids: Vec<ObjectId>,
 }
 
 impl Builder {
@@ -128,10 +397,18 @@ impl Builder {
             brokerage_execution_id: None,
             execution_timestamp_ms: None,
             commission: None,
+            fees: Vec::new(),
             quantity: None,
             price: None,
             security_id: None,
             side: None,
+            order_id: None,
+            venue: None,
+            liquidity: None,
+            order_reference: None,
+            strategy_tag: None,
+            position_effect: None,
+            tag_ids: Vec::new(),
         }
     }
 
@@ -141,11 +418,22 @@ impl Builder {
             brokerage_account_id: Some(trade_execution.brokerage_account_id),
             brokerage_execution_id: Some(trade_execution.brokerage_execution_id.clone()),
             execution_timestamp_ms: Some(trade_execution.execution_timestamp_ms),
-            commission: Some(trade_execution.commission),
+            commission: trade_execution
+                .fees
+                .is_empty()
+                .then_some(trade_execution.commission),
+            fees: trade_execution.fees.clone(),
             quantity: Some(trade_execution.quantity),
             price: Some(trade_execution.price),
             security_id: Some(trade_execution.security_id),
             side: Some(trade_execution.side.clone()),
+            order_id: trade_execution.order_id,
+            venue: trade_execution.venue.clone(),
+            liquidity: trade_execution.liquidity,
+            order_reference: trade_execution.order_reference.clone(),
+            strategy_tag: trade_execution.strategy_tag.clone(),
+            position_effect: trade_execution.position_effect,
+            tag_ids: trade_execution.tag_ids.clone(),
         }
     }
 
@@ -161,11 +449,20 @@ impl Builder {
         self.execution_timestamp_ms = Some(timestamp);
         self
     }
+    /// The broker's commission as a positive cost, itemized alongside any
+    /// other fees. Brokers reporting costs as negative amounts, like IBKR,
+    /// need them negated.
     pub fn commission(mut self, commission: f64) -> Self {
         self.commission = Some(commission);
         self
     }
 
+    /// Adds an itemized fee; rebates are negative.
+    pub fn fee(mut self, category: FeeCategory, amount: f64) -> Self {
+        self.fees.push(Fee::new(category, amount));
+        self
+    }
+
     pub fn quantity(mut self, quantity: f64) -> Self {
         self.quantity = Some(quantity);
         self
@@ -186,17 +483,80 @@ impl Builder {
         self
     }
 
-    pub fn build(self) -> Result<TradeExecution> {
+    pub fn order_id(mut self, id: ObjectId) -> Self {
+        self.order_id = Some(id);
+        self
+    }
+
+    pub fn venue(mut self, venue: &str) -> Self {
+        self.venue = Some(venue.to_owned());
+        self
+    }
+
+    pub fn liquidity(mut self, liquidity: Liquidity) -> Self {
+        self.liquidity = Some(liquidity);
+        self
+    }
+
+    pub fn order_reference(mut self, order_reference: &str) -> Self {
+        self.order_reference = Some(order_reference.to_owned());
+        self
+    }
+
+    pub fn strategy_tag(mut self, strategy_tag: &str) -> Self {
+        self.strategy_tag = Some(strategy_tag.to_owned());
+        self
+    }
+
+    pub fn position_effect(mut self, position_effect: PositionEffect) -> Self {
+        self.position_effect = Some(position_effect);
+        self
+    }
+
+    /// Attaches a tag, ignoring duplicates.
+    pub fn tag_id(mut self, tag_id: ObjectId) -> Self {
+        if !self.tag_ids.contains(&tag_id) {
+            self.tag_ids.push(tag_id);
+        }
+        self
+    }
+
+    pub fn build(mut self) -> Result<TradeExecution> {
+        let commission = if self.fees.is_empty() {
+            self.commission.unwrap()
+        } else {
+            if let Some(commission) = self.commission {
+                if self
+                    .fees
+                    .iter()
+                    .any(|f| f.category == FeeCategory::Commission)
+                {
+                    bail!("commission given both directly and as an itemized fee");
+                }
+                self.fees
+                    .insert(0, Fee::new(FeeCategory::Commission, commission));
+            }
+            self.fees.iter().map(|f| f.amount).sum()
+        };
+
         Ok(TradeExecution {
             _id: self._id,
             brokerage_account_id: self.brokerage_account_id.unwrap(),
             brokerage_execution_id: self.brokerage_execution_id.unwrap(),
             execution_timestamp_ms: self.execution_timestamp_ms.unwrap(),
-            commission: self.commission.unwrap(),
+            commission,
+            fees: self.fees,
             quantity: self.quantity.unwrap(),
             price: self.price.unwrap(),
             security_id: self.security_id.unwrap(),
             side: self.side.unwrap(),
+            order_id: self.order_id,
+            venue: self.venue,
+            liquidity: self.liquidity,
+            order_reference: self.order_reference,
+            strategy_tag: self.strategy_tag,
+            position_effect: self.position_effect,
+            tag_ids: self.tag_ids,
         })
     }
 }
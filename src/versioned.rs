@@ -0,0 +1,70 @@
+//! Per-document schema versions.
+//!
+//! Every entity document is written with the `schema_version` of the build
+//! that wrote it. Reading a document written by an older build upgrades it in
+//! memory first. Reading one written by a newer build ignores the fields this
+//! build does not know, so services embedding different releases of this
+//! crate can share a database during a rolling deployment. Updates only `$set`
+//! the fields they change, so those unknown fields are never dropped.
+
+use std::any::type_name;
+
+use anyhow::{Context, Result, bail};
+use bson::{Bson, Document};
+use serde::{Serialize, de::DeserializeOwned};
+
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+/// Documents written before schema versions were introduced.
+const UNVERSIONED: u32 = 1;
+
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// The version this build writes.
+    const SCHEMA_VERSION: u32;
+
+    /// Rewrites `document`, written at `version`, into the shape of
+    /// `version + 1`.
+    fn upgrade(document: &mut Document, version: u32) -> Result<()> {
+        let _ = document;
+        bail!(
+            "no upgrade for {} from schema version {}",
+            type_name::<Self>(),
+            version
+        )
+    }
+}
+
+/// The schema version `document` was written with.
+pub fn schema_version(document: &Document) -> Result<u32> {
+    Ok(match document.get(SCHEMA_VERSION_FIELD) {
+        None | Some(Bson::Null) => UNVERSIONED,
+        Some(Bson::Int32(version)) => u32::try_from(*version)?,
+        Some(Bson::Int64(version)) => u32::try_from(*version)?,
+        Some(other) => bail!("invalid {}: {}", SCHEMA_VERSION_FIELD, other),
+    })
+}
+
+/// Deserializes a stored document, upgrading it from older schema versions.
+pub fn from_document<T: Versioned>(mut document: Document) -> Result<T> {
+    let version = schema_version(&document)?;
+    if version > T::SCHEMA_VERSION {
+        tracing::debug!(
+            "reading {} schema version {} with version {}",
+            type_name::<T>(),
+            version,
+            T::SCHEMA_VERSION
+        );
+    }
+    for from_version in version..T::SCHEMA_VERSION {
+        T::upgrade(&mut document, from_version)?;
+    }
+    bson::from_document(document)
+        .with_context(|| format!("reading {} schema version {}", type_name::<T>(), version))
+}
+
+/// Serializes `t` for storage, stamped with the current schema version.
+pub fn to_document<T: Versioned>(t: &T) -> Result<Document> {
+    let mut document = bson::to_document(t)?;
+    document.insert(SCHEMA_VERSION_FIELD, T::SCHEMA_VERSION as i32);
+    Ok(document)
+}
@@ -0,0 +1,263 @@
+//! Foreign tax withheld at source on dividends and interest.
+//!
+//! A [`Withholding`] on a [`Dividend`] or an interest [`CashItem`] records the
+//! source country, the amount withheld, and the part of it that can be
+//! reclaimed from that country, typically the excess over the treaty rate.
+//! The rest is creditable against domestic tax. [`ForeignWithholdingReport`]
+//! sums both per country for a tax year.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::{Result, bail};
+use bson::oid::ObjectId;
+use mongodb::ClientSession;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    account::BrokerageAccount,
+    cash_item::{CashItem, CashItemKind},
+    date_util,
+    dividend::Dividend,
+    eod_summary::EODSummary,
+    namespace::Namespace,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReclaimStatus {
+    /// Nothing is reclaimable.
+    NotApplicable,
+    NotFiled,
+    Filed,
+    Refunded,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Withholding {
+    /// ISO 3166-1 alpha-2 code of the country that withheld the tax.
+    pub country: String,
+    /// Tax withheld, as a positive amount.
+    pub amount: f64,
+    /// Part of `amount` that can be reclaimed from `country`.
+    pub reclaimable_amount: f64,
+    pub reclaim_status: ReclaimStatus,
+}
+
+impl Withholding {
+    /// A withholding with nothing to reclaim.
+    pub fn new(country: &str, amount: f64) -> Self {
+        Self {
+            country: country.to_owned(),
+            amount,
+            reclaimable_amount: 0.0,
+            reclaim_status: ReclaimStatus::NotApplicable,
+        }
+    }
+
+    /// Marks part of the amount as reclaimable, with no claim filed yet.
+    pub fn with_reclaimable_amount(mut self, reclaimable_amount: f64) -> Self {
+        self.reclaimable_amount = reclaimable_amount;
+        self.reclaim_status = ReclaimStatus::NotFiled;
+        self
+    }
+
+    /// The amount eligible for a foreign tax credit: what was withheld less
+    /// what can be reclaimed, whether or not the reclaim succeeds.
+    pub fn creditable_amount(&self) -> f64 {
+        self.amount - self.reclaimable_amount
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.amount < 0.0 || self.reclaimable_amount < 0.0 {
+            bail!("withholding amounts must not be negative");
+        }
+        if self.reclaimable_amount > self.amount {
+            bail!(
+                "reclaimable amount {} exceeds the {} withheld",
+                self.reclaimable_amount,
+                self.amount
+            );
+        }
+        self.check_reclaim_status(self.reclaim_status)
+    }
+
+    /// Only [`ReclaimStatus::NotApplicable`] applies when nothing is
+    /// reclaimable, and only the other statuses when something is.
+    pub(crate) fn check_reclaim_status(&self, status: ReclaimStatus) -> Result<()> {
+        let reclaimable = self.reclaimable_amount > 0.0;
+        if reclaimable == (status == ReclaimStatus::NotApplicable) {
+            bail!(
+                "reclaim status {:?} does not apply to a reclaimable amount of {}",
+                status,
+                self.reclaimable_amount
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Foreign income and withholding totals; see [`ForeignWithholdingReport`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WithholdingTotals {
+    /// Gross income the tax was withheld from.
+    pub income: f64,
+    pub withheld: f64,
+    pub reclaimable: f64,
+    /// Reclaimable amounts already refunded.
+    pub reclaimed: f64,
+    pub creditable: f64,
+}
+
+impl WithholdingTotals {
+    fn add(&mut self, income: f64, withholding: &Withholding) {
+        self.income += income;
+        self.withheld += withholding.amount;
+        self.reclaimable += withholding.reclaimable_amount;
+        if withholding.reclaim_status == ReclaimStatus::Refunded {
+            self.reclaimed += withholding.reclaimable_amount;
+        }
+        self.creditable += withholding.creditable_amount();
+    }
+}
+
+/// Foreign withholding on the dividends and interest of one tax year, per
+/// source country.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForeignWithholdingReport {
+    tax_year: i32,
+    by_country: BTreeMap<String, WithholdingTotals>,
+    outstanding_reclaims: f64,
+    reported_dividends: f64,
+    reported_interest: f64,
+}
+
+impl ForeignWithholdingReport {
+    /// Builds the report from the dividends paid and the interest received
+    /// during `tax_year`.
+    pub fn from_items(tax_year: i32, dividends: &[Dividend], cash_items: &[CashItem]) -> Self {
+        let mut report = Self {
+            tax_year,
+            by_country: BTreeMap::new(),
+            outstanding_reclaims: 0.0,
+            reported_dividends: 0.0,
+            reported_interest: 0.0,
+        };
+
+        let in_year = |timestamp_ms| date_util::civil_from_timestamp_ms(timestamp_ms).0 == tax_year;
+        let dividend_withholdings = dividends
+            .iter()
+            .filter(|d| in_year(d.pay_timestamp_ms()))
+            .filter_map(|d| Some((d.gross_amount(), d.foreign_withholding()?)));
+        let interest_withholdings = cash_items
+            .iter()
+            .filter(|c| c.kind() == CashItemKind::Interest && in_year(c.timestamp_ms()))
+            .filter_map(|c| Some((c.gross_amount(), c.withholding()?)));
+
+        for (income, withholding) in dividend_withholdings.chain(interest_withholdings) {
+            report
+                .by_country
+                .entry(withholding.country.clone())
+                .or_default()
+                .add(income, withholding);
+            if matches!(
+                withholding.reclaim_status,
+                ReclaimStatus::NotFiled | ReclaimStatus::Filed
+            ) {
+                report.outstanding_reclaims += withholding.reclaimable_amount;
+            }
+        }
+        report
+    }
+
+    /// Reports the foreign withholding of the given accounts in `tax_year`,
+    /// along with the dividends and interest their EOD summaries recorded.
+    pub async fn for_accounts(
+        db: &impl Namespace,
+        brokerage_account_ids: &[ObjectId],
+        tax_year: i32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let start = date_util::start_of_year_ms(tax_year);
+        let end = date_util::start_of_year_ms(tax_year + 1) - 1;
+
+        let mut dividends = Vec::new();
+        let mut cash_items = Vec::new();
+        let mut summaries = Vec::new();
+        for id in brokerage_account_ids {
+            dividends.extend(
+                Dividend::find_by_account_id_and_range(db, *id, start, end, session.clone())
+                    .await?,
+            );
+            cash_items.extend(
+                CashItem::find_by_account_id_and_range(db, *id, start, end, session.clone())
+                    .await?,
+            );
+            summaries.extend(
+                EODSummary::find_by_account_id_and_range(db, *id, start, end, session.clone())
+                    .await?,
+            );
+        }
+
+        let mut report = Self::from_items(tax_year, &dividends, &cash_items);
+        report.reported_dividends = summaries.iter().map(EODSummary::dividends).sum();
+        report.reported_interest = summaries.iter().map(EODSummary::interest).sum();
+        Ok(report)
+    }
+
+    /// Reports the foreign withholding of the owner's taxable accounts in
+    /// `tax_year`.
+    pub async fn for_owner(
+        db: &impl Namespace,
+        owner_id: ObjectId,
+        tax_year: i32,
+        session: Option<Arc<Mutex<ClientSession>>>,
+    ) -> Result<Self> {
+        let account_ids: Vec<ObjectId> =
+            BrokerageAccount::find_by_owner_id(db, owner_id, session.clone())
+                .await?
+                .iter()
+                .filter(|a| a.is_taxable())
+                .map(BrokerageAccount::id)
+                .collect();
+        Self::for_accounts(db, &account_ids, tax_year, session).await
+    }
+
+    pub fn tax_year(&self) -> i32 {
+        self.tax_year
+    }
+
+    /// Keyed by country code.
+    pub fn by_country(&self) -> &BTreeMap<String, WithholdingTotals> {
+        &self.by_country
+    }
+
+    pub fn totals(&self) -> WithholdingTotals {
+        self.by_country
+            .values()
+            .fold(WithholdingTotals::default(), |acc, t| WithholdingTotals {
+                income: acc.income + t.income,
+                withheld: acc.withheld + t.withheld,
+                reclaimable: acc.reclaimable + t.reclaimable,
+                reclaimed: acc.reclaimed + t.reclaimed,
+                creditable: acc.creditable + t.creditable,
+            })
+    }
+
+    /// Reclaimable amounts not yet refunded or rejected.
+    pub fn outstanding_reclaims(&self) -> f64 {
+        self.outstanding_reclaims
+    }
+
+    /// Total dividends recorded in the accounts' EOD summaries for the year,
+    /// domestic and foreign. Zero unless loaded from the database.
+    pub fn reported_dividends(&self) -> f64 {
+        self.reported_dividends
+    }
+
+    /// Total interest recorded in the accounts' EOD summaries for the year.
+    /// Zero unless loaded from the database.
+    pub fn reported_interest(&self) -> f64 {
+        self.reported_interest
+    }
+}
@@ -1,15 +1,37 @@
 use anyhow::Result;
 use brokerage_db::{
-    account::BrokerageAccount,
-    initialize, remove_data,
-    security::{Security, SecurityType},
-    trade_execution::{self, TradeExecution, TradeSide},
+    account::{AccountType, BrokerageAccount, TaxTreatment},
+    cash_item::{CashItem, CashItemKind},
+    db::{BrokerageDb, IntegrityMode},
+    dividend::{self, Dividend, DividendClassification, DividendIncomeReport},
+    eod_summary::EODSummary,
+    execution_quality::{Benchmark, ExecutionQualityReport},
+    fee::{FeeCategory, FeeSummary},
+    initialize,
+    lot::LotReport,
+    migrations::{self, Direction, MigrationOptions},
+    option_event::{OptionEvent, OptionEventKind},
+    order::{Order, OrderFill, OrderStatus, OrderType, TimeInForce},
+    owner::{Owner, OwnerType},
+    performance::{PerformanceMetrics, ValuedSummary},
+    price_bar::PriceBar,
+    remove_data,
+    round_trip::{RoundTrip, TradingStatistics},
+    security::{OptionContract, OptionRight, Security, SecurityType},
+    store::{BrokerageStore, DuplicateKeyError, MemoryStore},
+    subscription::{ChangeKind, Subscription},
+    tag::{ExecutionQuery, Tag, TagKind, TagPnl},
+    tax_report::{HoldingPeriod, RealizedGainsReport},
+    trade_execution::{self, Liquidity, PositionEffect, TradeExecution, TradeSide},
+    versioned::{self, Versioned},
+    withholding::{ForeignWithholdingReport, ReclaimStatus, Withholding},
 };
 use mongodb::{
     Client, Database,
     error::{Error, ErrorKind, WriteFailure},
 };
 use rstest::{fixture, rstest};
+use serde::{Deserialize, Serialize};
 use testcontainers_modules::{
     mongo::Mongo,
     testcontainers::{ContainerAsync, runners::AsyncRunner},
@@ -33,6 +55,18 @@ impl DbConnection {
 
         Ok(DbConnection { client, db, node })
     }
+
+    /// Starts a single node replica set, which transactions require.
+    pub async fn new_repl_set(db_name: &str) -> Result<Self> {
+        let node = Mongo::repl_set().start().await?;
+        let host_port = node.get_host_port_ipv4(27017).await?;
+
+        let url = format!("mongodb://localhost:{}/?directConnection=true", host_port);
+        let client = mongodb::Client::with_uri_str(url).await?;
+        let db = client.database(db_name);
+
+        Ok(DbConnection { client, db, node })
+    }
 }
 
 struct TradeExecutionDesc {
@@ -53,6 +87,11 @@ async fn test_db_conn() -> Result<DbConnection> {
     Ok(db_conn)
 }
 
+#[fixture]
+async fn repl_set_db_conn() -> Result<DbConnection> {
+    DbConnection::new_repl_set("test").await
+}
+
 #[fixture]
 async fn admin_db_conn() -> Result<DbConnection> {
     DbConnection::new("admin").await
@@ -178,7 +217,7 @@ async fn find_all_brokerage_accounts_works(
     brokerage_account.insert(&dbc.db, None).await?;
     brokerage_account_2.insert(&dbc.db, None).await?;
 
-    let found_accounts = BrokerageAccount::find(&dbc.db).await?;
+    let found_accounts = BrokerageAccount::find(&dbc.db, None).await?;
 
     assert!(found_accounts.contains(&brokerage_account));
     assert!(found_accounts.contains(&brokerage_account_2));
@@ -231,6 +270,7 @@ async fn insert_security_works(
         &dbc.db,
         security.ticker(),
         security.listing_exchange(),
+        None,
     )
     .await?;
 
@@ -278,6 +318,7 @@ async fn find_non_extant_security_fails(
         &dbc.db,
         security.ticker(),
         security.listing_exchange(),
+        None,
     )
     .await;
 
@@ -302,6 +343,7 @@ async fn find_security_with_ticker_and_exchange_works(
         &dbc.db,
         security.ticker(),
         security.listing_exchange(),
+        None,
     )
     .await;
     assert!(result.is_ok());
@@ -324,7 +366,7 @@ async fn find_security_with_ticker_and_one_match_works(
     let dbc = test_db_conn?;
     security.insert(&dbc.db, None).await?;
 
-    let result = Security::find_by_ticker(&dbc.db, security.ticker()).await;
+    let result = Security::find_by_ticker(&dbc.db, security.ticker(), None).await;
     assert!(result.is_ok());
 
     let found_securities = result.unwrap();
@@ -344,7 +386,7 @@ async fn find_non_extant_security_with_ticker_returns_zero_elements(
 ) -> Result<()> {
     let dbc = test_db_conn?;
 
-    let result = Security::find_by_ticker(&dbc.db, security.ticker()).await;
+    let result = Security::find_by_ticker(&dbc.db, security.ticker(), None).await;
     assert!(result.is_ok());
 
     let found_securities = result.unwrap();
@@ -367,7 +409,7 @@ async fn find_security_with_ticker_and_two_match_works(
     let security2 = Security::new(SecurityType::Stock, security.ticker(), "NYSE", None);
     security2.insert(&dbc.db, None).await?;
 
-    let result = Security::find_by_ticker(&dbc.db, security.ticker()).await;
+    let result = Security::find_by_ticker(&dbc.db, security.ticker(), None).await;
     assert!(result.is_ok());
 
     let found_securities = result.unwrap();
@@ -389,7 +431,8 @@ async fn find_security_by_conid_works(
     let dbc = test_db_conn?;
     security_with_conid.insert(&dbc.db, None).await?;
 
-    let result = Security::find_by_conid(&dbc.db, security_with_conid.ibkr_conid().unwrap()).await;
+    let result =
+        Security::find_by_conid(&dbc.db, security_with_conid.ibkr_conid().unwrap(), None).await;
     assert!(result.is_ok());
 
     let found_security = result.unwrap();
@@ -409,7 +452,8 @@ async fn find_non_extant_security_by_conid_fails(
 ) -> Result<()> {
     let dbc = test_db_conn?;
 
-    let result = Security::find_by_conid(&dbc.db, security_with_conid.ibkr_conid().unwrap()).await;
+    let result =
+        Security::find_by_conid(&dbc.db, security_with_conid.ibkr_conid().unwrap(), None).await;
     assert!(result.is_ok());
 
     let found_security = result.unwrap();
@@ -442,7 +486,8 @@ async fn insert_trade_execution_works(
         .await?;
 
     let found_trade_execution =
-        TradeExecution::find_by_id(&dbc.db, trade_execution_desc.trade_execution.id()).await?;
+        TradeExecution::find_by_id(&dbc.db, trade_execution_desc.trade_execution.id(), None)
+            .await?;
     assert!(found_trade_execution.is_some());
     assert_eq!(
         trade_execution_desc.trade_execution,
@@ -453,6 +498,7 @@ async fn insert_trade_execution_works(
     let found_brokerage_account = BrokerageAccount::find_by_id(
         &dbc.db,
         trade_execution_desc.trade_execution.brokerage_account_id(),
+        None,
     )
     .await?;
     assert!(found_brokerage_account.is_some());
@@ -460,8 +506,12 @@ async fn insert_trade_execution_works(
         trade_execution_desc.brokerage_account,
         found_brokerage_account.unwrap()
     );
-    let found_security =
-        Security::find_by_id(&dbc.db, trade_execution_desc.trade_execution.security_id()).await?;
+    let found_security = Security::find_by_id(
+        &dbc.db,
+        trade_execution_desc.trade_execution.security_id(),
+        None,
+    )
+    .await?;
     assert!(found_security.is_some());
     assert_eq!(trade_execution_desc.security, found_security.unwrap());
 
@@ -501,7 +551,8 @@ async fn insert_two_trade_executions_works(
     trade_execution_2.insert(&dbc.db, None).await?;
 
     let found_trade_execution =
-        TradeExecution::find_by_id(&dbc.db, trade_execution_desc.trade_execution.id()).await?;
+        TradeExecution::find_by_id(&dbc.db, trade_execution_desc.trade_execution.id(), None)
+            .await?;
     assert!(found_trade_execution.is_some());
     assert_eq!(
         trade_execution_desc.trade_execution,
@@ -509,9 +560,2434 @@ async fn insert_two_trade_executions_works(
     );
 
     let found_trade_execution_2 =
-        TradeExecution::find_by_id(&dbc.db, trade_execution_2.id()).await?;
+        TradeExecution::find_by_id(&dbc.db, trade_execution_2.id(), None).await?;
     assert!(found_trade_execution_2.is_some());
     assert_eq!(trade_execution_2, found_trade_execution_2.unwrap());
 
     Ok(())
 }
+
+fn eod_summary(
+    brokerage_account_id: bson::oid::ObjectId,
+    day: i64,
+    starting_cash: f64,
+    ending_cash: f64,
+    deposits: f64,
+    withdrawals: f64,
+) -> EODSummary {
+    EODSummary::builder()
+        .brokerage_account_id(brokerage_account_id)
+        .start_timestamp_ms(1735689600000 + day * DAY_MS)
+        .end_timestamp_ms(1735689600000 + (day + 1) * DAY_MS - 1)
+        .starting_cash(starting_cash)
+        .ending_cash(ending_cash)
+        .commissions(0.0)
+        .deposits(deposits)
+        .dividends(0.0)
+        .interest(0.0)
+        .net_trade_purchases(0.0)
+        .net_trade_sales(0.0)
+        .other_fees(0.0)
+        .withdrawals(withdrawals)
+        .build()
+        .expect("Failed to build EODSummary")
+}
+
+#[rstest]
+fn performance_metrics_exclude_external_flows(brokerage_account: BrokerageAccount) -> Result<()> {
+    let account_id = brokerage_account.id();
+    let summaries = [
+        eod_summary(account_id, 0, 1000.0, 1100.0, 0.0, 0.0),
+        // A deposit of 1100 on a flat day is not a return.
+        eod_summary(account_id, 1, 1100.0, 2200.0, 1100.0, 0.0),
+        eod_summary(account_id, 2, 2200.0, 1980.0, 0.0, 0.0),
+        // A withdrawal of 500 on a flat day is not a loss.
+        eod_summary(account_id, 3, 1980.0, 1480.0, 0.0, 500.0),
+    ];
+    let days: Vec<ValuedSummary> = summaries
+        .iter()
+        .map(|s| ValuedSummary::new(s, 0.0, 0.0))
+        .collect();
+
+    let metrics = PerformanceMetrics::compute(&days, 0.0)?;
+
+    assert_eq!(metrics.daily_returns().len(), 4);
+    assert!((metrics.daily_returns()[1]).abs() < 1e-12);
+    assert!((metrics.daily_returns()[3]).abs() < 1e-12);
+    assert!((metrics.time_weighted_return() - (1.1 * 0.9 - 1.0)).abs() < 1e-12);
+    assert!((metrics.max_drawdown() - 0.1).abs() < 1e-12);
+    assert!(metrics.volatility() > 0.0);
+    assert!(metrics.sharpe_ratio().is_some());
+    assert!(metrics.money_weighted_return().is_some());
+
+    Ok(())
+}
+
+#[rstest]
+fn performance_metrics_include_position_values(brokerage_account: BrokerageAccount) -> Result<()> {
+    let account_id = brokerage_account.id();
+    let summaries = [eod_summary(account_id, 0, 500.0, 500.0, 0.0, 0.0)];
+    let days = [ValuedSummary::new(&summaries[0], 500.0, 550.0)];
+
+    let metrics = PerformanceMetrics::compute(&days, 0.0)?;
+
+    assert!((metrics.time_weighted_return() - 0.05).abs() < 1e-12);
+    assert_eq!(metrics.volatility(), 0.0);
+    assert!(metrics.sharpe_ratio().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn performance_metrics_over_empty_period_fails() {
+    assert!(PerformanceMetrics::compute(&[], 0.0).is_err());
+}
+
+fn execution(
+    brokerage_account_id: bson::oid::ObjectId,
+    security_id: bson::oid::ObjectId,
+    timestamp_ms: i64,
+    side: TradeSide,
+    quantity: f64,
+    price: f64,
+    commission: f64,
+) -> TradeExecution {
+    TradeExecution::builder()
+        .brokerage_account_id(brokerage_account_id)
+        .brokerage_execution_id(&format!("exec-{}", timestamp_ms))
+        .commission(commission)
+        .execution_timestamp_ms(timestamp_ms)
+        .quantity(quantity)
+        .price(price)
+        .security_id(security_id)
+        .side(side)
+        .build()
+        .expect("Failed to build TradeExecution")
+}
+
+#[rstest]
+fn round_trips_group_flat_to_flat(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let executions = [
+        execution(a, s, 1000, TradeSide::Buy, 100.0, 10.0, 1.0),
+        execution(a, s, 2000, TradeSide::Buy, 100.0, 12.0, 1.0),
+        execution(a, s, 3000, TradeSide::Sell, 200.0, 13.0, 2.0),
+        // Opens a short, then reverses it into a long.
+        execution(a, s, 4000, TradeSide::Sell, 50.0, 20.0, 0.0),
+        execution(a, s, 5000, TradeSide::Buy, 100.0, 22.0, 2.0),
+        execution(a, s, 6000, TradeSide::Sell, 50.0, 21.0, 0.0),
+        // Still open, so not reported.
+        execution(a, s, 7000, TradeSide::Buy, 10.0, 21.0, 0.0),
+    ];
+
+    let round_trips = RoundTrip::from_executions(&executions);
+    assert_eq!(round_trips.len(), 3);
+
+    let long = &round_trips[0];
+    assert_eq!(long.side(), &TradeSide::Buy);
+    assert_eq!(long.entry_timestamp_ms(), 1000);
+    assert_eq!(long.exit_timestamp_ms(), 3000);
+    assert_eq!(long.holding_duration_ms(), 2000);
+    assert_eq!(long.max_position_size(), 200.0);
+    assert_eq!(long.average_entry_price(), 11.0);
+    assert_eq!(long.average_exit_price(), 13.0);
+    assert_eq!(long.gross_pnl(), 400.0);
+    assert_eq!(long.net_pnl(), 396.0);
+    assert_eq!(long.trade_execution_ids().len(), 3);
+
+    let short = &round_trips[1];
+    assert_eq!(short.side(), &TradeSide::Sell);
+    assert_eq!(short.gross_pnl(), -100.0);
+    assert_eq!(short.commission(), 1.0);
+
+    let flipped_long = &round_trips[2];
+    assert_eq!(flipped_long.side(), &TradeSide::Buy);
+    assert_eq!(flipped_long.entry_timestamp_ms(), 5000);
+    assert_eq!(flipped_long.gross_pnl(), -50.0);
+
+    let stats = TradingStatistics::from_round_trips(&round_trips);
+    assert_eq!(stats.round_trip_count(), 3);
+    assert_eq!(stats.win_rate(), Some(1.0 / 3.0));
+    assert_eq!(stats.profit_factor(), Some(396.0 / 152.0));
+    assert_eq!(stats.expectancy(), Some(244.0 / 3.0));
+    assert_eq!(stats.average_holding_duration_ms(), Some(4000 / 3));
+
+    let by_security = TradingStatistics::by_security(&round_trips);
+    assert_eq!(by_security[&s], stats);
+
+    Ok(())
+}
+
+const DAY_MS: i64 = 86_400_000;
+
+#[rstest]
+fn wash_sale_across_accounts_adjusts_replacement_lot(
+    brokerage_account: BrokerageAccount,
+    brokerage_account_2: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a1, a2, s) = (
+        brokerage_account.id(),
+        brokerage_account_2.id(),
+        security.id(),
+    );
+    let executions = [
+        execution(a1, s, 0, TradeSide::Buy, 100.0, 50.0, 0.0),
+        execution(a1, s, 40 * DAY_MS, TradeSide::Sell, 100.0, 40.0, 0.0),
+        execution(a2, s, 50 * DAY_MS, TradeSide::Buy, 60.0, 42.0, 0.0),
+        execution(a2, s, 100 * DAY_MS, TradeSide::Sell, 60.0, 45.0, 0.0),
+    ];
+
+    let plain = LotReport::from_executions(&executions)?;
+    assert!(plain.wash_sales().is_empty());
+    assert_eq!(plain.closed_lots()[0].gain_loss(), -1000.0);
+
+    let report = LotReport::from_executions_with_wash_sales(&executions)?;
+    assert_eq!(report.wash_sales().len(), 1);
+    let wash_sale = &report.wash_sales()[0];
+    assert_eq!(wash_sale.quantity(), 60.0);
+    assert_eq!(wash_sale.disallowed_loss(), 600.0);
+    assert_eq!(wash_sale.replacement_brokerage_account_id(), a2);
+
+    let loss_lot = &report.closed_lots()[0];
+    assert!(loss_lot.is_wash_sale());
+    assert_eq!(loss_lot.gain_loss(), -400.0);
+
+    let replacement_lot = &report.closed_lots()[1];
+    assert_eq!(replacement_lot.cost_basis(), 60.0 * 42.0 + 600.0);
+    assert_eq!(replacement_lot.acquired_timestamp_ms(), 10 * DAY_MS);
+    assert_eq!(replacement_lot.gain_loss(), -420.0);
+    assert!(!replacement_lot.is_wash_sale());
+    assert!(report.open_lots().is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+fn wash_sales_follow_account_tax_treatment(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let ira = BrokerageAccount::builder("batch-brokers", "I7654321")
+        .account_type(AccountType::TraditionalIra)
+        .tax_treatment(TaxTreatment::TaxDeferred)
+        .build();
+    let accounts = [brokerage_account.clone(), ira.clone()];
+    let (taxable, ira, s) = (brokerage_account.id(), ira.id(), security.id());
+
+    // A loss inside the IRA is not a wash sale, even if replaced elsewhere.
+    let ira_loss = [
+        execution(ira, s, 0, TradeSide::Buy, 100.0, 50.0, 0.0),
+        execution(ira, s, 40 * DAY_MS, TradeSide::Sell, 100.0, 40.0, 0.0),
+        execution(taxable, s, 50 * DAY_MS, TradeSide::Buy, 60.0, 42.0, 0.0),
+    ];
+    let report = LotReport::from_account_executions_with_wash_sales(&ira_loss, &accounts)?;
+    assert!(report.wash_sales().is_empty());
+    assert_eq!(report.closed_lots()[0].gain_loss(), -1000.0);
+    assert_eq!(report.open_lots()[0].cost_basis(), 60.0 * 42.0);
+
+    // A taxable loss replaced inside the IRA is disallowed for good.
+    let taxable_loss = [
+        execution(taxable, s, 0, TradeSide::Buy, 100.0, 50.0, 0.0),
+        execution(taxable, s, 40 * DAY_MS, TradeSide::Sell, 100.0, 40.0, 0.0),
+        execution(ira, s, 50 * DAY_MS, TradeSide::Buy, 60.0, 42.0, 0.0),
+        execution(ira, s, 100 * DAY_MS, TradeSide::Sell, 60.0, 45.0, 0.0),
+    ];
+    let report = LotReport::from_account_executions_with_wash_sales(&taxable_loss, &accounts)?;
+    assert_eq!(report.wash_sales().len(), 1);
+    assert!(report.wash_sales()[0].is_permanent());
+    assert_eq!(report.wash_sales()[0].disallowed_loss(), 600.0);
+    assert_eq!(report.closed_lots()[0].gain_loss(), -400.0);
+    let replacement_lot = &report.closed_lots()[1];
+    assert_eq!(replacement_lot.cost_basis(), 60.0 * 42.0);
+    assert_eq!(replacement_lot.acquired_timestamp_ms(), 50 * DAY_MS);
+
+    Ok(())
+}
+
+#[rstest]
+fn ira_accounts_default_to_tax_advantaged_treatment() {
+    let roth = BrokerageAccount::builder("batch-brokers", "R7654321")
+        .account_type(AccountType::RothIra)
+        .build();
+    assert_eq!(roth.tax_treatment(), Some(&TaxTreatment::TaxExempt));
+    assert!(!roth.is_taxable());
+
+    let traditional = BrokerageAccount::builder("batch-brokers", "I7654321")
+        .account_type(AccountType::TraditionalIra)
+        .build();
+    assert_eq!(
+        traditional.tax_treatment(),
+        Some(&TaxTreatment::TaxDeferred)
+    );
+    assert!(!traditional.is_taxable());
+
+    // An explicit treatment still wins over the account type's default.
+    let overridden = BrokerageAccount::builder("batch-brokers", "I7654322")
+        .account_type(AccountType::TraditionalIra)
+        .tax_treatment(TaxTreatment::Taxable)
+        .build();
+    assert!(overridden.is_taxable());
+
+    // Accounts stored before the treatment was defaulted fall back to the type.
+    let mut stored = bson::to_document(&roth).unwrap();
+    stored.remove("tax_treatment");
+    let stored: BrokerageAccount = bson::from_document(stored).unwrap();
+    assert_eq!(stored.tax_treatment(), None);
+    assert!(!stored.is_taxable());
+
+    assert!(BrokerageAccount::new("batch-brokers", "T7654321").is_taxable());
+}
+
+#[rstest]
+fn lot_matching_rejects_selling_more_than_held(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let buy = execution(a, s, 0, TradeSide::Buy, 10.0, 50.0, 0.0);
+    let closing_sell = trade_execution::Builder::from_trade_execution(&execution(
+        a,
+        s,
+        DAY_MS,
+        TradeSide::Sell,
+        20.0,
+        55.0,
+        0.0,
+    ))
+    .position_effect(PositionEffect::Close)
+    .build()?;
+    assert!(LotReport::from_executions(&[buy.clone(), closing_sell]).is_err());
+
+    let short_sale = trade_execution::Builder::from_trade_execution(&execution(
+        a,
+        s,
+        DAY_MS,
+        TradeSide::Sell,
+        5.0,
+        55.0,
+        0.0,
+    ))
+    .position_effect(PositionEffect::Open)
+    .build()?;
+    assert!(LotReport::from_executions(&[buy, short_sale]).is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn lot_matching_opens_and_covers_short_lots(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let executions = [
+        execution(a, s, 0, TradeSide::Buy, 10.0, 50.0, 0.0),
+        execution(a, s, DAY_MS, TradeSide::Sell, 20.0, 55.0, 2.0),
+        execution(a, s, 2 * DAY_MS, TradeSide::Buy, 15.0, 52.0, 0.0),
+    ];
+
+    let report = LotReport::from_executions(&executions)?;
+    let closed = report.closed_lots();
+    assert_eq!(closed.len(), 2);
+    assert!(!closed[0].is_short());
+    assert!((closed[0].gain_loss() - 49.0).abs() < 1e-9);
+
+    // The short is realized when covered, with the sale's share of commission.
+    assert!(closed[1].is_short());
+    assert_eq!(closed[1].opening_execution_id(), executions[1].id());
+    assert_eq!(closed[1].acquired_timestamp_ms(), 2 * DAY_MS);
+    assert!((closed[1].proceeds() - 549.0).abs() < 1e-9);
+    assert_eq!(closed[1].cost_basis(), 520.0);
+
+    // The rest of the covering purchase opens a long lot.
+    assert_eq!(report.open_lots().len(), 1);
+    assert!(!report.open_lots()[0].is_short());
+    assert_eq!(report.net_quantity(a, s), 5.0);
+
+    let still_short = LotReport::from_executions(&executions[..2])?;
+    assert!(still_short.open_lots()[0].is_short());
+    assert_eq!(still_short.net_quantity(a, s), -10.0);
+
+    Ok(())
+}
+
+#[rstest]
+fn covering_purchases_do_not_replace_shares_sold_at_a_loss(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let executions = [
+        execution(a, s, 0, TradeSide::Buy, 10.0, 50.0, 0.0),
+        execution(a, s, 40 * DAY_MS, TradeSide::Sell, 10.0, 40.0, 0.0),
+        execution(a, s, 45 * DAY_MS, TradeSide::Sell, 10.0, 42.0, 0.0),
+        // Covers the short and buys 5 more shares.
+        execution(a, s, 50 * DAY_MS, TradeSide::Buy, 15.0, 44.0, 0.0),
+    ];
+
+    let report = LotReport::from_executions_with_wash_sales(&executions)?;
+    assert_eq!(report.wash_sales().len(), 1);
+    assert_eq!(report.wash_sales()[0].quantity(), 5.0);
+    assert_eq!(report.closed_lots()[0].disallowed_loss(), 50.0);
+
+    // The disallowed loss lands in the long lot the purchase opened.
+    let open = &report.open_lots()[0];
+    assert_eq!(open.quantity(), 5.0);
+    assert_eq!(open.cost_basis(), 5.0 * 44.0 + 50.0);
+
+    // A purchase that only covers replaces nothing.
+    let mut covering_only = executions.clone();
+    covering_only[3] = execution(a, s, 50 * DAY_MS, TradeSide::Buy, 10.0, 44.0, 0.0);
+    let report = LotReport::from_executions_with_wash_sales(&covering_only)?;
+    assert!(report.wash_sales().is_empty());
+    assert_eq!(report.closed_lots()[0].gain_loss(), -100.0);
+
+    Ok(())
+}
+
+#[rstest]
+fn short_sales_replace_shorts_covered_at_a_loss(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let executions = [
+        execution(a, s, 0, TradeSide::Sell, 100.0, 40.0, 0.0),
+        execution(a, s, 10 * DAY_MS, TradeSide::Buy, 100.0, 50.0, 0.0),
+        execution(a, s, 20 * DAY_MS, TradeSide::Sell, 60.0, 48.0, 0.0),
+        execution(a, s, 60 * DAY_MS, TradeSide::Buy, 60.0, 45.0, 0.0),
+    ];
+
+    let report = LotReport::from_executions_with_wash_sales(&executions)?;
+    assert_eq!(report.wash_sales().len(), 1);
+    let wash_sale = &report.wash_sales()[0];
+    assert_eq!(wash_sale.replacement_execution_id(), executions[2].id());
+    assert_eq!(wash_sale.disallowed_loss(), 600.0);
+    assert_eq!(wash_sale.holding_period_ms(), 0);
+
+    let loss_lot = &report.closed_lots()[0];
+    assert!(loss_lot.is_short());
+    assert_eq!(loss_lot.gain_loss(), -400.0);
+
+    // The disallowed loss is added to the cost of covering the replacement.
+    let replacement_lot = &report.closed_lots()[1];
+    assert_eq!(replacement_lot.proceeds(), 60.0 * 48.0);
+    assert_eq!(replacement_lot.cost_basis(), 60.0 * 45.0 + 600.0);
+    assert_eq!(replacement_lot.acquired_timestamp_ms(), 60 * DAY_MS);
+
+    // A sale that closes a long position replaces nothing.
+    let mut closing_long = executions[..3].to_vec();
+    closing_long.insert(
+        2,
+        execution(a, s, 15 * DAY_MS, TradeSide::Buy, 60.0, 46.0, 0.0),
+    );
+    let report = LotReport::from_executions_with_wash_sales(&closing_long)?;
+    assert!(report.wash_sales().is_empty());
+    assert_eq!(report.closed_lots()[0].gain_loss(), -1000.0);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn short_position_charges_are_recorded_as_cash_items(
+    #[future] test_db_conn: Result<DbConnection>,
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let charge = |kind, amount, timestamp_ms| {
+        CashItem::builder()
+            .brokerage_account_id(brokerage_account.id())
+            .security_id(security.id())
+            .kind(kind)
+            .amount(amount)
+            .timestamp_ms(timestamp_ms)
+            .build()
+    };
+    let items = [
+        charge(CashItemKind::BorrowFee, -1.25, DAY_MS)?,
+        charge(CashItemKind::ShortInterest, -0.4, DAY_MS)?,
+        charge(CashItemKind::BorrowFee, -1.5, 2 * DAY_MS)?,
+    ];
+    for item in &items {
+        item.insert(&dbc.db, None).await?;
+    }
+
+    let found =
+        CashItem::find_by_account_id_and_range(&dbc.db, brokerage_account.id(), 0, DAY_MS, None)
+            .await?;
+    assert_eq!(found, items[..2]);
+    assert_eq!(CashItem::total(&items, CashItemKind::BorrowFee), -2.75);
+
+    Ok(())
+}
+
+#[rstest]
+fn realized_gains_report_splits_terms_and_flags_wash_sales(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let a = brokerage_account.id();
+    let msft = Security::new(SecurityType::Stock, "MSFT", "NASDAQ", None);
+    let (aapl_id, msft_id) = (security.id(), msft.id());
+    let executions = [
+        // 2023-01-03 to 2024-06-03: long-term gain.
+        execution(a, aapl_id, 1672758000000, TradeSide::Buy, 10.0, 100.0, 1.0),
+        execution(a, aapl_id, 1717426800000, TradeSide::Sell, 10.0, 150.0, 0.0),
+        // 2024-02-01 to 2024-03-01: short-term loss, replaced on 2024-03-15.
+        execution(a, msft_id, 1706799600000, TradeSide::Buy, 5.0, 200.0, 0.0),
+        execution(a, msft_id, 1709305200000, TradeSide::Sell, 5.0, 180.0, 0.0),
+        execution(a, msft_id, 1710514800000, TradeSide::Buy, 5.0, 190.0, 0.0),
+    ];
+    let lots = LotReport::from_executions_with_wash_sales(&executions)?;
+    let securities = [(aapl_id, security), (msft_id, msft)].into_iter().collect();
+
+    let report = RealizedGainsReport::from_closed_lots(2024, lots.closed_lots(), &securities);
+
+    assert_eq!(report.short_term().len(), 1);
+    assert_eq!(report.long_term().len(), 1);
+
+    let short = &report.short_term()[0];
+    assert_eq!(short.holding_period(), HoldingPeriod::ShortTerm);
+    assert_eq!(short.description(), "5 sh MSFT");
+    assert_eq!(short.adjustment_codes(), "W");
+    assert_eq!(short.adjustment_amount(), 100.0);
+    assert_eq!(short.gain_loss(), 0.0);
+
+    let long = &report.long_term()[0];
+    assert_eq!(long.date_acquired().to_string(), "01/03/2023");
+    assert_eq!(long.date_sold().to_string(), "06/03/2024");
+    assert_eq!(report.long_term_totals().gain_loss, 499.0);
+
+    assert_eq!(
+        report.to_csv(),
+        format!(
+            "{}\n{}\n{}\n",
+            RealizedGainsReport::CSV_HEADER,
+            "Short,5 sh MSFT,02/01/2024,03/01/2024,900.00,1000.00,W,100.00,0.00",
+            "Long,10 sh AAPL,01/03/2023,06/03/2024,1500.00,1001.00,,0.00,499.00",
+        )
+    );
+
+    let other_year = RealizedGainsReport::from_closed_lots(2023, lots.closed_lots(), &securities);
+    assert!(other_year.short_term().is_empty() && other_year.long_term().is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+fn brokerage_account_builder_sets_metadata() {
+    let owner = Owner::new(OwnerType::Person, "Pat Trader");
+    let account = BrokerageAccount::builder("batch-brokers", "R7654321")
+        .owner_id(owner.id())
+        .account_type(AccountType::RothIra)
+        .tax_treatment(TaxTreatment::TaxExempt)
+        .display_name("Pat's Roth")
+        .opened_timestamp_ms(1672758000000)
+        .build();
+
+    assert_eq!(account.owner_id(), Some(owner.id()));
+    assert_eq!(account.account_type(), Some(&AccountType::RothIra));
+    assert!(!account.is_taxable());
+    assert_eq!(account.display_name(), Some("Pat's Roth"));
+    assert_eq!(account.opened_timestamp_ms(), Some(1672758000000));
+    assert_eq!(account.closed_timestamp_ms(), None);
+    assert!(BrokerageAccount::new("batch-brokers", "A1234567").is_taxable());
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn find_brokerage_accounts_by_owner_works(
+    #[future] test_db_conn: Result<DbConnection>,
+    mut brokerage_account: BrokerageAccount,
+    brokerage_account_2: BrokerageAccount,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let owner = Owner::new(OwnerType::Person, "Pat Trader");
+    owner.insert(&dbc.db, None).await?;
+    brokerage_account.insert(&dbc.db, None).await?;
+    brokerage_account_2.insert(&dbc.db, None).await?;
+
+    brokerage_account
+        .assign_owner(&dbc.db, Some(owner.id()), None)
+        .await?;
+
+    let found_accounts = owner.brokerage_accounts(&dbc.db, None).await?;
+    assert_eq!(found_accounts, vec![brokerage_account]);
+    assert_eq!(found_accounts[0].owner(&dbc.db, None).await?, Some(owner));
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn memory_store_round_trips_entities(trade_execution_desc: TradeExecutionDesc) -> Result<()> {
+    let store = MemoryStore::new();
+    store
+        .insert_brokerage_account(&trade_execution_desc.brokerage_account)
+        .await?;
+    store
+        .insert_security(&trade_execution_desc.security)
+        .await?;
+    store
+        .insert_trade_execution(&trade_execution_desc.trade_execution)
+        .await?;
+
+    let account = &trade_execution_desc.brokerage_account;
+    assert_eq!(
+        store
+            .find_brokerage_account_by_brokerage_and_account_id(
+                account.brokerage_id(),
+                account.account_id()
+            )
+            .await?
+            .as_ref(),
+        Some(account)
+    );
+    assert_eq!(
+        store.find_securities_by_ticker("AAPL").await?,
+        vec![trade_execution_desc.security.clone()]
+    );
+    assert_eq!(
+        store
+            .find_trade_execution_by_brokerage_execution_id("abc-123-def")
+            .await?
+            .as_ref(),
+        Some(&trade_execution_desc.trade_execution)
+    );
+    assert_eq!(
+        store
+            .find_trade_executions_by_account_id_and_range(account.id(), 0, i64::MAX)
+            .await?
+            .len(),
+        1
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn memory_store_enforces_unique_indexes(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let store = MemoryStore::new();
+    store.insert_brokerage_account(&brokerage_account).await?;
+    store.insert_security(&security).await?;
+
+    // Same brokerage and account id under a new _id.
+    let duplicate_account = BrokerageAccount::new(
+        brokerage_account.brokerage_id(),
+        brokerage_account.account_id(),
+    );
+    let error = store
+        .insert_brokerage_account(&duplicate_account)
+        .await
+        .unwrap_err()
+        .downcast::<DuplicateKeyError>()?;
+    assert_eq!(error.index_name, "brokerage_account_unique_idx");
+
+    // Same ticker and exchange.
+    let duplicate_security = Security::new(SecurityType::Stock, "AAPL", "NASDAQ", Some(1));
+    assert!(store.insert_security(&duplicate_security).await.is_err());
+
+    // Same document twice.
+    assert!(store.insert_security(&security).await.is_err());
+    assert_eq!(store.find_securities_by_ticker("AAPL").await?.len(), 1);
+
+    Ok(())
+}
+
+/// Assigns `account`, already inserted into `store`, to a new owner and back.
+async fn assert_store_reassigns_owners(
+    store: &impl BrokerageStore,
+    account: &BrokerageAccount,
+) -> Result<()> {
+    let owner = Owner::new(OwnerType::Person, "Pat Trader");
+    store.insert_owner(&owner).await?;
+
+    store
+        .assign_brokerage_account_owner(account.id(), Some(owner.id()))
+        .await?;
+    let accounts = store
+        .find_brokerage_accounts_by_owner_id(owner.id())
+        .await?;
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].owner_id(), Some(owner.id()));
+
+    store
+        .assign_brokerage_account_owner(account.id(), None)
+        .await?;
+    assert!(
+        store
+            .find_brokerage_accounts_by_owner_id(owner.id())
+            .await?
+            .is_empty()
+    );
+    let found = store.find_brokerage_account_by_id(account.id()).await?;
+    assert_eq!(found.as_ref(), Some(account));
+
+    let missing = store
+        .assign_brokerage_account_owner(bson::oid::ObjectId::new(), Some(owner.id()))
+        .await;
+    assert!(missing.is_err());
+
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn memory_store_reassigns_owners(brokerage_account: BrokerageAccount) -> Result<()> {
+    let store = MemoryStore::new();
+    store.insert_brokerage_account(&brokerage_account).await?;
+    assert_store_reassigns_owners(&store, &brokerage_account).await
+}
+
+#[cfg(feature = "sqlite")]
+#[rstest]
+#[tokio::test]
+async fn sqlite_store_round_trips_and_enforces_unique_indexes(
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let store = brokerage_db::store::SqliteStore::open_in_memory()?;
+    let account = &trade_execution_desc.brokerage_account;
+    store.insert_brokerage_account(account).await?;
+    store
+        .insert_security(&trade_execution_desc.security)
+        .await?;
+    store
+        .insert_trade_execution(&trade_execution_desc.trade_execution)
+        .await?;
+
+    assert_eq!(
+        store
+            .find_brokerage_account_by_id(account.id())
+            .await?
+            .as_ref(),
+        Some(account)
+    );
+    assert_eq!(
+        store
+            .find_trade_executions_by_account_id_and_range(account.id(), 0, i64::MAX)
+            .await?,
+        vec![trade_execution_desc.trade_execution.clone()]
+    );
+
+    let duplicate =
+        trade_execution::Builder::from_trade_execution(&trade_execution_desc.trade_execution)
+            .build()?;
+    let error = store
+        .insert_trade_execution(&duplicate)
+        .await
+        .unwrap_err()
+        .downcast::<DuplicateKeyError>()?;
+    assert_eq!(error.index_name, "trade_executions_unique_idx");
+
+    let result = store.insert_security(&trade_execution_desc.security).await;
+    assert!(result.unwrap_err().is::<DuplicateKeyError>());
+
+    assert_store_reassigns_owners(&store, account).await
+}
+
+#[cfg(feature = "sqlite")]
+#[rstest]
+#[tokio::test]
+async fn sqlite_store_stamps_and_upgrades_schema_versions(security: Security) -> Result<()> {
+    let path = std::env::temp_dir().join(format!("brokerage-db-{}.sqlite", security.id()));
+    let store = brokerage_db::store::SqliteStore::open(&path)?;
+    store.insert_security(&security).await?;
+
+    let conn = rusqlite::Connection::open(&path)?;
+    let stored: Vec<u8> = conn.query_row(
+        "SELECT document FROM securities WHERE id = ?",
+        [security.id().to_hex()],
+        |row| row.get(0),
+    )?;
+    let mut document: bson::Document = bson::from_slice(&stored)?;
+    assert_eq!(
+        versioned::schema_version(&document)?,
+        Security::SCHEMA_VERSION
+    );
+
+    // A document written by a newer build still reads.
+    document.insert(versioned::SCHEMA_VERSION_FIELD, 99);
+    document.insert("added_in_a_later_release", true);
+    conn.execute(
+        "UPDATE securities SET document = ? WHERE id = ?",
+        rusqlite::params![bson::to_vec(&document)?, security.id().to_hex()],
+    )?;
+    assert_eq!(
+        store.find_security_by_id(security.id()).await?.as_ref(),
+        Some(&security)
+    );
+
+    drop((conn, store));
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn brokerage_db_requires_a_connection() {
+    let result = BrokerageDb::builder().database_name("test").open().await;
+    assert!(result.is_err());
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn brokerage_db_strict_integrity_rejects_dangling_references(
+    #[future] empty_test_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = empty_test_db_conn?;
+    let bdb = BrokerageDb::builder()
+        .client(dbc.client.clone())
+        .database_name("test")
+        .integrity_mode(IntegrityMode::Strict)
+        .open()
+        .await?;
+
+    // Neither the account nor the security exist yet.
+    let result = bdb
+        .trade_executions()
+        .insert(&trade_execution_desc.trade_execution)
+        .await;
+    assert!(result.is_err());
+
+    bdb.accounts()
+        .insert(&trade_execution_desc.brokerage_account)
+        .await?;
+    bdb.securities()
+        .insert(&trade_execution_desc.security)
+        .await?;
+    bdb.trade_executions()
+        .insert(&trade_execution_desc.trade_execution)
+        .await?;
+
+    let found = bdb
+        .trade_executions()
+        .find_by_id(trade_execution_desc.trade_execution.id())
+        .await?;
+    assert_eq!(found.as_ref(), Some(&trade_execution_desc.trade_execution));
+
+    // A reinvestment link must be a purchase of the dividend's security.
+    let sale =
+        trade_execution::Builder::from_trade_execution(&trade_execution_desc.trade_execution)
+            .brokerage_execution_id("sale")
+            .side(TradeSide::Sell)
+            .build()?;
+    bdb.trade_executions().insert(&sale).await?;
+    let pay_ms = trade_execution_desc
+        .trade_execution
+        .execution_timestamp_ms();
+    let reinvested = |execution: &TradeExecution| {
+        dividend(
+            &trade_execution_desc.brokerage_account,
+            &trade_execution_desc.security,
+            pay_ms,
+            DividendClassification::Qualified,
+        )
+        .reinvestment_execution_id(execution.id())
+        .build()
+    };
+    assert!(bdb.dividends().insert(&reinvested(&sale)?).await.is_err());
+    bdb.dividends()
+        .insert(&reinvested(&trade_execution_desc.trade_execution)?)
+        .await?;
+
+    // Bulk tagging needs the tag to exist too.
+    let tag = Tag::new(TagKind::Label, "reviewed");
+    let query = ExecutionQuery::new(trade_execution_desc.brokerage_account.id());
+    assert!(bdb.tags().tag_executions(&tag, &query).await.is_err());
+    bdb.tags().insert(&tag).await?;
+    assert_eq!(bdb.tags().tag_executions(&tag, &query).await?, 1);
+
+    // A fill must reference an existing order for its account and security.
+    let order = limit_order(&trade_execution_desc)?;
+    let fill = order_execution(&trade_execution_desc, &order, "fill", 1500, 20.0, 150.5)?;
+    assert!(bdb.trade_executions().insert(&fill).await.is_err());
+    let other_security = Security::new(SecurityType::Stock, "MSFT", "NASDAQ", Some(272093));
+    bdb.securities().insert(&other_security).await?;
+    let other_order = Order::builder()
+        .brokerage_account_id(trade_execution_desc.brokerage_account.id())
+        .brokerage_order_id("order-2")
+        .security_id(other_security.id())
+        .side(TradeSide::Buy)
+        .order_type(OrderType::Market)
+        .time_in_force(TimeInForce::Day)
+        .quantity(20.0)
+        .submitted_timestamp_ms(order.submitted_timestamp_ms())
+        .build()?;
+    bdb.orders().insert(&other_order).await?;
+    let mismatched = order_execution(
+        &trade_execution_desc,
+        &other_order,
+        "fill",
+        1500,
+        20.0,
+        150.5,
+    )?;
+    assert!(bdb.trade_executions().insert(&mismatched).await.is_err());
+    bdb.orders().insert(&order).await?;
+    bdb.trade_executions().insert(&fill).await?;
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn with_transaction_reads_own_writes_and_commits(
+    #[future] repl_set_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = repl_set_db_conn?;
+    let bdb = BrokerageDb::builder()
+        .client(dbc.client.clone())
+        .database_name("test")
+        .integrity_mode(IntegrityMode::Strict)
+        .open()
+        .await?;
+
+    let desc = &trade_execution_desc;
+    let found = bdb
+        .with_transaction(|tx| async move {
+            tx.accounts().insert(&desc.brokerage_account).await?;
+            tx.securities().insert(&desc.security).await?;
+            // The strict integrity checks only pass if they see the
+            // uncommitted account and security.
+            tx.trade_executions().insert(&desc.trade_execution).await?;
+            tx.trade_executions()
+                .find_by_id(desc.trade_execution.id())
+                .await
+        })
+        .await?;
+    assert_eq!(found.as_ref(), Some(&desc.trade_execution));
+
+    let committed = bdb
+        .trade_executions()
+        .find_by_id(desc.trade_execution.id())
+        .await?;
+    assert_eq!(committed, found);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn with_transaction_aborts_on_error(
+    #[future] repl_set_db_conn: Result<DbConnection>,
+    security: Security,
+) -> Result<()> {
+    let dbc = repl_set_db_conn?;
+    let bdb = BrokerageDb::builder()
+        .client(dbc.client.clone())
+        .database_name("test")
+        .open()
+        .await?;
+
+    let security = &security;
+    let result: Result<()> = bdb
+        .with_transaction(|tx| async move {
+            tx.securities().insert(security).await?;
+            anyhow::bail!("rolled back")
+        })
+        .await;
+    assert!(result.is_err());
+
+    assert_eq!(bdb.securities().find_by_id(security.id()).await?, None);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn session_reads_resolve_relationships_within_transaction(
+    #[future] repl_set_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = repl_set_db_conn?;
+    let bdb = BrokerageDb::builder()
+        .client(dbc.client.clone())
+        .database_name("test")
+        .open()
+        .await?;
+
+    let desc = &trade_execution_desc;
+    let resolved = bdb
+        .with_transaction(|tx| async move {
+            tx.accounts().insert(&desc.brokerage_account).await?;
+            tx.securities().insert(&desc.security).await?;
+            tx.trade_executions().insert(&desc.trade_execution).await?;
+
+            let security = desc
+                .trade_execution
+                .security(&tx, Some(tx.session()))
+                .await?;
+            let round_trips = RoundTrip::find_by_account_id_and_range(
+                &tx,
+                desc.brokerage_account.id(),
+                i64::MIN,
+                i64::MAX,
+                Some(tx.session()),
+            )
+            .await?;
+            Ok((security, round_trips))
+        })
+        .await?;
+    assert_eq!(resolved.0, desc.security);
+    // A single buy never closes a round trip.
+    assert!(resolved.1.is_empty());
+
+    let session = bdb.start_session().await?;
+    let found = bdb
+        .securities()
+        .with_session(session)
+        .find_by_ticker_and_exchange(desc.security.ticker(), desc.security.listing_exchange())
+        .await?;
+    assert_eq!(found, Some(desc.security.clone()));
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn collection_prefixes_isolate_tenants(
+    #[future] empty_test_db_conn: Result<DbConnection>,
+    security: Security,
+) -> Result<()> {
+    let dbc = empty_test_db_conn?;
+    let open_tenant = |prefix: &'static str| {
+        BrokerageDb::builder()
+            .client(dbc.client.clone())
+            .database_name("test")
+            .collection_prefix(prefix)
+            .open()
+    };
+    let tenant_a = open_tenant("tenant_a.").await?;
+    let tenant_b = open_tenant("tenant_b.").await?;
+
+    tenant_a.securities().insert(&security).await?;
+    assert_eq!(tenant_b.securities().find_by_id(security.id()).await?, None);
+
+    // Each tenant has its own unique indexes.
+    tenant_b.securities().insert(&security).await?;
+
+    let collection_names = dbc.db.list_collection_names().await?;
+    for name in [
+        "tenant_a.securities",
+        "tenant_a.migrations",
+        "tenant_b.securities",
+        "tenant_b.migrations",
+    ] {
+        assert!(collection_names.contains(&name.to_owned()), "{}", name);
+    }
+    assert!(!collection_names.contains(&Security::COLLECTION_NAME.to_owned()));
+
+    tenant_a.remove_data().await?;
+    assert_eq!(
+        tenant_b.securities().find_by_id(security.id()).await?,
+        Some(security)
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn migrations_report_status_and_migrate_to_target_version(
+    #[future] empty_test_db_conn: Result<DbConnection>,
+) -> Result<()> {
+    let dbc = empty_test_db_conn?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 0);
+    assert_eq!(
+        migrations::pending(&dbc.db).await?.len() as u32,
+        migrations::latest_version()
+    );
+
+    migrations::migrate_to(&dbc.db, 3).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 3);
+    let pending: Vec<u32> = migrations::pending(&dbc.db)
+        .await?
+        .iter()
+        .map(|m| m.version)
+        .collect();
+    assert_eq!(
+        pending,
+        (4..=migrations::latest_version()).collect::<Vec<_>>()
+    );
+
+    initialize(&dbc.db).await?;
+    assert_eq!(
+        migrations::current_version(&dbc.db).await?,
+        migrations::latest_version()
+    );
+
+    // A dry run reports the changes without applying them.
+    let plan = migrations::plan(&dbc.db, 3).await?;
+    assert!(plan.steps.iter().all(|s| s.direction == Direction::Down));
+    assert!(plan.to_string().contains("drop collection owners"));
+    assert_eq!(
+        migrations::current_version(&dbc.db).await?,
+        migrations::latest_version()
+    );
+
+    migrations::migrate_to(&dbc.db, 3).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 3);
+    let collection_names = dbc.db.list_collection_names().await?;
+    assert!(!collection_names.contains(&Owner::COLLECTION_NAME.to_owned()));
+    assert!(collection_names.contains(&TradeExecution::COLLECTION_NAME.to_owned()));
+
+    assert!(
+        migrations::plan(&dbc.db, migrations::latest_version() + 1)
+            .await
+            .is_err()
+    );
+
+    remove_data(&dbc.db).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 0);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn migrating_down_refuses_to_drop_non_empty_collections(
+    #[future] test_db_conn: Result<DbConnection>,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    Security::new(SecurityType::Stock, "AAPL", "NASDAQ", None)
+        .insert(&dbc.db, None)
+        .await?;
+
+    let error = migrations::migrate_to(&dbc.db, 1).await.unwrap_err();
+    assert!(error.to_string().contains(Security::COLLECTION_NAME));
+    assert_eq!(
+        migrations::current_version(&dbc.db).await?,
+        migrations::latest_version()
+    );
+
+    // Collections above the target that are empty can still be dropped.
+    migrations::migrate_to(&dbc.db, 2).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 2);
+
+    let options = MigrationOptions {
+        force: true,
+        ..MigrationOptions::default()
+    };
+    migrations::migrate_to_with_options(&dbc.db, 1, &options).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 1);
+    assert!(
+        !dbc.db
+            .list_collection_names()
+            .await?
+            .contains(&Security::COLLECTION_NAME.to_owned())
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn interrupted_migrations_resume_where_they_stopped(
+    #[future] empty_test_db_conn: Result<DbConnection>,
+) -> Result<()> {
+    let dbc = empty_test_db_conn?;
+    migrations::migrate_to(&dbc.db, 6).await?;
+
+    // Stop the schema version backfill of owners after its first batch.
+    let owners = dbc.db.collection::<bson::Document>(Owner::COLLECTION_NAME);
+    for name in ["Ann", "Bo", "Cy", "Di"] {
+        Owner::new(OwnerType::Person, name)
+            .insert(&dbc.db, None)
+            .await?;
+    }
+    let mut ids = Vec::new();
+    let mut cursor = owners
+        .find(bson::doc! {})
+        .sort(bson::doc! { "_id": 1 })
+        .await?;
+    while cursor.advance().await? {
+        ids.push(cursor.deserialize_current()?.get_object_id("_id")?);
+    }
+    owners
+        .update_many(
+            bson::doc! { "_id": { "$in": &ids[2..] } },
+            bson::doc! { "$unset": { versioned::SCHEMA_VERSION_FIELD: "" } },
+        )
+        .await?;
+    let records = dbc.db.collection::<bson::Document>("migrations");
+    records
+        .insert_one(bson::doc! {
+            "_id": "Migration007",
+            "status": "InProgress",
+            "direction": "Up",
+            "completed_changes": 0_i64,
+            "resume_after": ids[1],
+        })
+        .await?;
+
+    let options = MigrationOptions {
+        batch_size: 1,
+        ..MigrationOptions::default()
+    };
+    migrations::migrate_to_with_options(&dbc.db, 7, &options).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 7);
+    assert!(logs_contain("2 of 4 documents to update"));
+    assert!(logs_contain("2/2 documents updated"));
+    assert_eq!(
+        owners
+            .count_documents(bson::doc! { versioned::SCHEMA_VERSION_FIELD: 1 })
+            .await?,
+        4
+    );
+
+    // Stop the creation of the dividends collection after its first change.
+    migrations::migrate_to(&dbc.db, 15).await?;
+    dbc.db.create_collection(Dividend::COLLECTION_NAME).await?;
+    records
+        .insert_one(bson::doc! {
+            "_id": "Migration016",
+            "status": "InProgress",
+            "direction": "Up",
+            "completed_changes": 0_i64,
+        })
+        .await?;
+    migrations::migrate_to(&dbc.db, 16).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 16);
+
+    // Dropping an index that is already gone succeeds.
+    dbc.db
+        .collection::<bson::Document>(Dividend::COLLECTION_NAME)
+        .drop_index("dividends_by_security_idx")
+        .await?;
+    migrations::migrate_to(&dbc.db, 15).await?;
+    assert_eq!(migrations::current_version(&dbc.db).await?, 15);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn validators_reject_malformed_documents(
+    #[future] test_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    trade_execution_desc
+        .trade_execution
+        .insert(&dbc.db, None)
+        .await?;
+
+    let trade_executions = dbc
+        .db
+        .collection::<bson::Document>(TradeExecution::COLLECTION_NAME);
+    let mut malformed = bson::to_document(
+        &TradeExecution::builder()
+            .brokerage_account_id(trade_execution_desc.brokerage_account.id())
+            .brokerage_execution_id("malformed")
+            .commission(0.0)
+            .execution_timestamp_ms(1746665451000)
+            .quantity(100.0)
+            .price(150.0)
+            .security_id(trade_execution_desc.security.id())
+            .side(TradeSide::Buy)
+            .build()?,
+    )?;
+    malformed.insert("side", "Hold");
+    assert!(trade_executions.insert_one(&malformed).await.is_err());
+
+    malformed.insert("side", "Sell");
+    malformed.remove("price");
+    assert!(trade_executions.insert_one(&malformed).await.is_err());
+
+    let securities = dbc
+        .db
+        .collection::<bson::Document>(Security::COLLECTION_NAME);
+    assert!(
+        securities
+            .insert_one(bson::doc! { "ticker": 42, "listing_exchange": "NASDAQ" })
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn versioned_documents_tolerate_newer_fields() -> Result<()> {
+    let security = Security::new(SecurityType::Stock, "AAPL", "NASDAQ", Some(265598));
+    let mut document = versioned::to_document(&security)?;
+    assert_eq!(
+        versioned::schema_version(&document)?,
+        Security::SCHEMA_VERSION
+    );
+
+    // A newer build wrote a later version with a field this build lacks.
+    document.insert(versioned::SCHEMA_VERSION_FIELD, 99);
+    document.insert("primary_listing_mic", "XNAS");
+    assert_eq!(versioned::from_document::<Security>(document)?, security);
+
+    // Documents written before versioning read as version 1.
+    let mut document = bson::to_document(&security)?;
+    document.remove(versioned::SCHEMA_VERSION_FIELD);
+    assert_eq!(versioned::from_document::<Security>(document)?, security);
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Renamed {
+    quantity: f64,
+}
+
+impl Versioned for Renamed {
+    const SCHEMA_VERSION: u32 = 2;
+
+    fn upgrade(document: &mut bson::Document, version: u32) -> Result<()> {
+        assert_eq!(version, 1);
+        let quantity = document.remove("qty").unwrap();
+        document.insert("quantity", quantity);
+        Ok(())
+    }
+}
+
+#[test]
+fn versioned_documents_upgrade_on_read() -> Result<()> {
+    let renamed: Renamed = versioned::from_document(bson::doc! { "qty": 5.0 })?;
+    assert_eq!(renamed, Renamed { quantity: 5.0 });
+
+    let current = versioned::to_document(&renamed)?;
+    assert_eq!(versioned::schema_version(&current)?, 2);
+    assert_eq!(versioned::from_document::<Renamed>(current)?, renamed);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn subscriptions_yield_account_changes_and_resume(
+    #[future] repl_set_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+    brokerage_account_2: BrokerageAccount,
+) -> Result<()> {
+    let dbc = repl_set_db_conn?;
+    initialize(&dbc.db).await?;
+    let account_id = trade_execution_desc.brokerage_account.id();
+    let subscribe = || {
+        Subscription::<TradeExecution>::builder()
+            .consumer("risk-dashboard")
+            .brokerage_account_id(account_id)
+            .watch(&dbc.db)
+    };
+    let execution = |account_id, execution_id: &str| {
+        trade_execution::Builder::from_trade_execution(&trade_execution_desc.trade_execution)
+            .brokerage_account_id(account_id)
+            .brokerage_execution_id(execution_id)
+            .build()
+    };
+
+    let mut subscription = subscribe().await?;
+    let other = execution(brokerage_account_2.id(), "other-account")?;
+    other.insert(&dbc.db, None).await?;
+    let first = execution(account_id, "first")?;
+    first.insert(&dbc.db, None).await?;
+
+    let change = subscription.next().await?.unwrap();
+    assert_eq!(change.kind(), ChangeKind::Insert);
+    assert_eq!(change.document(), &first);
+    subscription.acknowledge().await?;
+    drop(subscription);
+
+    // Changes made while the consumer is down are delivered on restart.
+    let second = execution(account_id, "second")?;
+    second.insert(&dbc.db, None).await?;
+    let mut subscription = subscribe().await?;
+    let change = subscription.next().await?.unwrap();
+    assert_eq!(change.document(), &second);
+
+    assert!(
+        Subscription::<Security>::builder()
+            .brokerage_account_id(account_id)
+            .watch(&dbc.db)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+fn limit_order(desc: &TradeExecutionDesc) -> Result<Order> {
+    Order::builder()
+        .brokerage_account_id(desc.brokerage_account.id())
+        .brokerage_order_id("order-1")
+        .security_id(desc.security.id())
+        .side(TradeSide::Buy)
+        .order_type(OrderType::Limit)
+        .limit_price(150.5)
+        .time_in_force(TimeInForce::GoodTillCancelled)
+        .quantity(100.0)
+        .submitted_timestamp_ms(1746665450000)
+        .build()
+}
+
+fn order_execution(
+    desc: &TradeExecutionDesc,
+    order: &Order,
+    execution_id: &str,
+    latency_ms: i64,
+    quantity: f64,
+    price: f64,
+) -> Result<TradeExecution> {
+    trade_execution::Builder::from_trade_execution(&desc.trade_execution)
+        .brokerage_execution_id(execution_id)
+        .order_id(order.id())
+        .execution_timestamp_ms(order.submitted_timestamp_ms() + latency_ms)
+        .quantity(quantity)
+        .price(price)
+        .build()
+}
+
+#[rstest]
+fn order_fill_reports_rate_price_and_latency(
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let order = limit_order(&trade_execution_desc)?;
+    let fill = OrderFill::new(
+        order.clone(),
+        vec![
+            order_execution(&trade_execution_desc, &order, "e2", 1500, 20.0, 150.5)?,
+            order_execution(&trade_execution_desc, &order, "e1", 500, 60.0, 150.0)?,
+        ],
+    );
+
+    assert_eq!(fill.filled_quantity(), 80.0);
+    assert_eq!(fill.fill_rate(), 0.8);
+    assert!(fill.is_partially_filled());
+    assert_eq!(fill.average_fill_price(), Some(150.125));
+    assert_eq!(fill.first_fill_latency_ms(), Some(500));
+    assert_eq!(fill.last_fill_latency_ms(), Some(1500));
+
+    let unfilled = OrderFill::new(order, vec![]);
+    assert_eq!(unfilled.fill_rate(), 0.0);
+    assert!(!unfilled.is_partially_filled());
+    assert_eq!(unfilled.average_fill_price(), None);
+
+    assert!(
+        Order::builder()
+            .order_type(OrderType::StopLimit)
+            .limit_price(1.0)
+            .build()
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn orders_link_executions_and_track_status(
+    #[future] test_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let desc = &trade_execution_desc;
+    let mut order = limit_order(desc)?;
+    order.insert(&dbc.db, None).await?;
+    assert!(order.insert(&dbc.db, None).await.is_err());
+
+    let execution = order_execution(desc, &order, "e1", 250, 40.0, 150.25)?;
+    execution.insert(&dbc.db, None).await?;
+    order
+        .update_status(&dbc.db, OrderStatus::PartiallyFilled, 250, None)
+        .await?;
+    order
+        .update_status(&dbc.db, OrderStatus::Cancelled, 900, None)
+        .await?;
+    assert!(
+        order
+            .update_status(&dbc.db, OrderStatus::Filled, 1000, None)
+            .await
+            .is_err()
+    );
+
+    let found =
+        Order::find_by_brokerage_order_id(&dbc.db, desc.brokerage_account.id(), "order-1", None)
+            .await?
+            .unwrap();
+    assert_eq!(found, order);
+    assert_eq!(found.status(), OrderStatus::Cancelled);
+    assert_eq!(found.cancelled_timestamp_ms(), Some(900));
+    assert_eq!(execution.order(&dbc.db, None).await?, Some(order.clone()));
+
+    let fills =
+        OrderFill::for_account_and_range(&dbc.db, desc.brokerage_account.id(), 0, i64::MAX, None)
+            .await?;
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].executions(), &[execution]);
+    assert_eq!(fills[0].fill_rate(), 0.4);
+    assert_eq!(fills[0].first_fill_latency_ms(), Some(250));
+
+    Ok(())
+}
+
+const MINUTE_MS: i64 = 60_000;
+
+fn minute_bar(
+    security_id: bson::oid::ObjectId,
+    start: i64,
+    hlc: [f64; 3],
+    volume: f64,
+) -> PriceBar {
+    PriceBar::builder()
+        .security_id(security_id)
+        .start_timestamp_ms(start)
+        .duration_ms(MINUTE_MS)
+        .open(hlc[2])
+        .high(hlc[0])
+        .low(hlc[1])
+        .close(hlc[2])
+        .volume(volume)
+        .build()
+        .unwrap()
+}
+
+/// An order submitted at 10:00 UTC, one buy fill against it and an unrelated
+/// sell, with minute bars around the order and at the end of the day.
+fn slippage_fixture(
+    desc: &TradeExecutionDesc,
+) -> Result<(Order, Vec<TradeExecution>, Vec<PriceBar>)> {
+    let day_start = 1746662400000;
+    let submitted = day_start + 10 * 60 * MINUTE_MS;
+    let order = Order::builder()
+        .brokerage_account_id(desc.brokerage_account.id())
+        .brokerage_order_id("order-1")
+        .security_id(desc.security.id())
+        .side(TradeSide::Buy)
+        .quantity(100.0)
+        .submitted_timestamp_ms(submitted)
+        .build()?;
+    let buy = order_execution(desc, &order, "buy", 90_000, 100.0, 101.5)?;
+    let sell = trade_execution::Builder::from_trade_execution(&desc.trade_execution)
+        .brokerage_execution_id("sell")
+        .side(TradeSide::Sell)
+        .execution_timestamp_ms(submitted + 2 * MINUTE_MS)
+        .quantity(50.0)
+        .price(102.0)
+        .build()?;
+
+    let security_id = desc.security.id();
+    let bars = vec![
+        minute_bar(
+            security_id,
+            submitted - MINUTE_MS,
+            [101.0, 99.0, 100.0],
+            1000.0,
+        ),
+        minute_bar(security_id, submitted, [102.0, 100.0, 101.0], 1000.0),
+        minute_bar(
+            security_id,
+            submitted + MINUTE_MS,
+            [103.0, 101.0, 102.0],
+            2000.0,
+        ),
+        minute_bar(
+            security_id,
+            day_start + 20 * 60 * MINUTE_MS,
+            [106.0, 104.0, 105.0],
+            500.0,
+        ),
+    ];
+    Ok((order, vec![sell, buy], bars))
+}
+
+#[rstest]
+fn execution_quality_measures_slippage_against_benchmarks(
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let (order, executions, bars) = slippage_fixture(&trade_execution_desc)?;
+    let report = ExecutionQualityReport::from_executions(&executions, &[order], &bars);
+
+    let buy = &report.executions()[0];
+    assert_eq!(buy.execution().brokerage_execution_id(), "buy");
+    assert_eq!(buy.benchmark_price(Benchmark::Arrival), Some(100.0));
+    assert!((buy.slippage_bps(Benchmark::Arrival).unwrap() - 150.0).abs() < 1e-9);
+    assert!(
+        (buy.benchmark_price(Benchmark::IntervalVwap).unwrap() - 305_000.0 / 3000.0).abs() < 1e-9
+    );
+    assert!((buy.slippage_cost(Benchmark::Arrival).unwrap() - 150.0).abs() < 1e-9);
+    assert_eq!(buy.benchmark_price(Benchmark::Close), Some(105.0));
+
+    // Without an order only the close applies; selling below it costs.
+    let sell = &report.executions()[1];
+    assert_eq!(sell.slippage_bps(Benchmark::Arrival), None);
+    assert!((sell.slippage_bps(Benchmark::Close).unwrap() - 3.0 / 105.0 * 10_000.0).abs() < 1e-9);
+
+    let summary = report.summary();
+    assert_eq!(summary.executions(), 2);
+    assert_eq!(summary.quantity(), 150.0);
+    assert!((summary.average_slippage_bps(Benchmark::Arrival).unwrap() - 150.0).abs() < 1e-9);
+    let expected_close = (-3.5 / 105.0 * 10_000.0 * 10_150.0 + 3.0 / 105.0 * 10_000.0 * 5_100.0)
+        / (10_150.0 + 5_100.0);
+    assert!(
+        (summary.average_slippage_bps(Benchmark::Close).unwrap() - expected_close).abs() < 1e-9
+    );
+
+    assert_eq!(
+        report.by_hour_of_day().keys().collect::<Vec<_>>(),
+        vec![&10]
+    );
+    assert_eq!(report.by_security().len(), 1);
+    assert_eq!(
+        report.by_account()[&trade_execution_desc.brokerage_account.id()],
+        summary
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn execution_quality_loads_orders_and_bars(
+    #[future] test_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let (order, executions, bars) = slippage_fixture(&trade_execution_desc)?;
+    order.insert(&dbc.db, None).await?;
+    for execution in &executions {
+        execution.insert(&dbc.db, None).await?;
+    }
+    for bar in &bars {
+        bar.insert(&dbc.db, None).await?;
+    }
+    assert!(bars[0].insert(&dbc.db, None).await.is_err());
+
+    let loaded = ExecutionQualityReport::for_accounts(
+        &dbc.db,
+        &[trade_execution_desc.brokerage_account.id()],
+        0,
+        i64::MAX,
+        MINUTE_MS,
+        None,
+    )
+    .await?;
+    assert_eq!(
+        loaded,
+        ExecutionQualityReport::from_executions(&executions, &[order], &bars)
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn execution_quality_loads_bars_for_orders_spanning_days(
+    #[future] test_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let security_id = trade_execution_desc.security.id();
+    let submitted = 1746662400000 + 10 * 60 * MINUTE_MS;
+    let mut order = Order::builder()
+        .brokerage_account_id(trade_execution_desc.brokerage_account.id())
+        .brokerage_order_id("gtc-1")
+        .security_id(security_id)
+        .side(TradeSide::Buy)
+        .quantity(100.0)
+        .time_in_force(TimeInForce::GoodTillCancelled)
+        .submitted_timestamp_ms(submitted)
+        .build()?;
+    order.insert(&dbc.db, None).await?;
+    let fill = order_execution(&trade_execution_desc, &order, "gtc-fill", 0, 50.0, 100.0)?;
+    fill.insert(&dbc.db, None).await?;
+    // Cancelled two days after the partial fill.
+    order
+        .update_status(
+            &dbc.db,
+            OrderStatus::Cancelled,
+            submitted + 2 * DAY_MS,
+            None,
+        )
+        .await?;
+
+    let bars = [
+        minute_bar(security_id, submitted, [101.0, 99.0, 100.0], 1000.0),
+        minute_bar(
+            security_id,
+            submitted + 2 * DAY_MS - MINUTE_MS,
+            [111.0, 109.0, 110.0],
+            1000.0,
+        ),
+    ];
+    for bar in &bars {
+        bar.insert(&dbc.db, None).await?;
+    }
+
+    let loaded = ExecutionQualityReport::for_accounts(
+        &dbc.db,
+        &[trade_execution_desc.brokerage_account.id()],
+        0,
+        i64::MAX,
+        MINUTE_MS,
+        None,
+    )
+    .await?;
+    assert_eq!(
+        loaded.executions()[0].benchmark_price(Benchmark::IntervalVwap),
+        Some(105.0)
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn itemized_fees_total_to_the_commission(trade_execution_desc: TradeExecutionDesc) -> Result<()> {
+    let itemized =
+        trade_execution::Builder::from_trade_execution(&trade_execution_desc.trade_execution)
+            .brokerage_execution_id("itemized")
+            .commission(1.0)
+            .fee(FeeCategory::Exchange, 0.3)
+            .fee(FeeCategory::Sec, 0.02)
+            .fee(FeeCategory::FinraTaf, 0.01)
+            .fee(FeeCategory::EcnRebate, -0.2)
+            .build()?;
+    assert!((itemized.commission() - 1.13).abs() < 1e-12);
+    assert_eq!(itemized.fee(FeeCategory::Commission), 1.0);
+    assert_eq!(itemized.fees().len(), 5);
+
+    // Copies keep the breakdown rather than itemizing the total again.
+    let copy = trade_execution::Builder::from_trade_execution(&itemized).build()?;
+    assert_eq!(copy.fees(), itemized.fees());
+    assert!(
+        trade_execution::Builder::from_trade_execution(&itemized)
+            .commission(2.0)
+            .build()
+            .is_err()
+    );
+
+    let plain = &trade_execution_desc.trade_execution;
+    let summary = FeeSummary::from_executions(&[itemized, plain.clone()]);
+    assert_eq!(summary.executions(), 2);
+    assert_eq!(
+        summary.amount(FeeCategory::Commission),
+        1.0 + plain.commission()
+    );
+    assert_eq!(summary.amount(FeeCategory::Clearing), 0.0);
+    assert_eq!(summary.rebates(), -0.2);
+    assert!((summary.total() - 1.13 - plain.commission()).abs() < 1e-12);
+
+    Ok(())
+}
+
+#[rstest]
+fn net_rebates_reduce_costs_rather_than_add_to_them(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let rebated = |execution: TradeExecution, rebate: f64| {
+        trade_execution::Builder::from_trade_execution(&execution)
+            .fee(FeeCategory::EcnRebate, rebate)
+            .build()
+    };
+    let executions = [
+        rebated(execution(a, s, 0, TradeSide::Buy, 100.0, 10.0, 0.0), -0.5)?,
+        rebated(
+            execution(a, s, DAY_MS, TradeSide::Sell, 100.0, 11.0, 0.2),
+            -0.3,
+        )?,
+    ];
+    assert_eq!(executions[0].commission(), -0.5);
+
+    let report = LotReport::from_executions(&executions)?;
+    let lot = &report.closed_lots()[0];
+    assert!((lot.cost_basis() - 999.5).abs() < 1e-9);
+    assert!((lot.proceeds() - 1100.1).abs() < 1e-9);
+    assert!((lot.gain_loss() - 100.6).abs() < 1e-9);
+
+    let round_trips = RoundTrip::from_executions(&executions);
+    assert!((round_trips[0].commission() + 0.6).abs() < 1e-9);
+    assert!((round_trips[0].net_pnl() - 100.6).abs() < 1e-9);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn executions_are_found_by_routing_metadata(
+    #[future] test_db_conn: Result<DbConnection>,
+    trade_execution_desc: TradeExecutionDesc,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    migrations::migrate_to(&dbc.db, migrations::latest_version()).await?;
+
+    let plain = &trade_execution_desc.trade_execution;
+    let routed = trade_execution::Builder::from_trade_execution(plain)
+        .brokerage_execution_id("routed")
+        .venue("XNAS")
+        .liquidity(Liquidity::Added)
+        .order_reference("ref-1")
+        .strategy_tag("momentum")
+        .position_effect(PositionEffect::Open)
+        .build()?;
+    plain.insert(&dbc.db, None).await?;
+    routed.insert(&dbc.db, None).await?;
+
+    let copy = trade_execution::Builder::from_trade_execution(&routed).build()?;
+    assert_eq!(copy.liquidity(), Some(Liquidity::Added));
+    assert_eq!(copy.position_effect(), Some(PositionEffect::Open));
+    assert_eq!(plain.venue(), None);
+
+    let account_id = trade_execution_desc.brokerage_account.id();
+    assert_eq!(
+        TradeExecution::find_by_account_id_and_venue(&dbc.db, account_id, "XNAS", None).await?,
+        vec![routed.clone()]
+    );
+    assert_eq!(
+        TradeExecution::find_by_account_id_and_strategy_tag(&dbc.db, account_id, "momentum", None)
+            .await?,
+        vec![routed.clone()]
+    );
+    assert_eq!(
+        TradeExecution::find_by_account_id_and_order_reference(&dbc.db, account_id, "ref-1", None)
+            .await?,
+        vec![routed]
+    );
+    assert!(
+        TradeExecution::find_by_account_id_and_venue(&dbc.db, account_id, "ARCX", None)
+            .await?
+            .is_empty()
+    );
+
+    Ok(())
+}
+
+fn tagged(execution: TradeExecution, tag: &Tag) -> TradeExecution {
+    trade_execution::Builder::from_trade_execution(&execution)
+        .tag_id(tag.id())
+        .build()
+        .expect("Failed to build TradeExecution")
+}
+
+#[rstest]
+fn tag_pnl_separates_strategies_in_one_account(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let (a, s) = (brokerage_account.id(), security.id());
+    let momentum = Tag::new(TagKind::Strategy, "momentum");
+    let reversion = Tag::new(TagKind::Strategy, "reversion");
+    let executions = [
+        tagged(
+            execution(a, s, 1000, TradeSide::Buy, 100.0, 10.0, 0.0),
+            &momentum,
+        ),
+        tagged(
+            execution(a, s, 2000, TradeSide::Buy, 50.0, 11.0, 0.0),
+            &reversion,
+        ),
+        tagged(
+            execution(a, s, 3000, TradeSide::Sell, 100.0, 12.0, 0.0),
+            &momentum,
+        ),
+        tagged(
+            execution(a, s, 4000, TradeSide::Sell, 50.0, 10.0, 0.0),
+            &reversion,
+        ),
+        execution(a, s, 5000, TradeSide::Buy, 10.0, 10.0, 0.0),
+        tagged(
+            execution(a, s, 6000, TradeSide::Buy, 30.0, 10.0, 0.0),
+            &momentum,
+        ),
+    ];
+
+    // Combined, the strategies form a single round trip carrying both tags.
+    let combined = RoundTrip::from_executions(&executions);
+    assert_eq!(combined.len(), 1);
+    assert_eq!(combined[0].tag_ids(), &[momentum.id(), reversion.id()]);
+
+    let pnl = TagPnl::by_tag(&executions)?;
+    assert_eq!(pnl.len(), 2);
+    assert_eq!(pnl[&momentum.id()].round_trips().len(), 1);
+    assert!((pnl[&momentum.id()].net_pnl() - 200.0).abs() < 1e-9);
+    assert!((pnl[&reversion.id()].net_pnl() + 50.0).abs() < 1e-9);
+    assert_eq!(pnl[&reversion.id()].statistics().losing_count(), 1);
+
+    // Only the tag's own purchase is open; the untagged one is left out.
+    let open_lots = pnl[&momentum.id()].open_lots();
+    assert_eq!(open_lots.len(), 1);
+    assert_eq!(open_lots[0].quantity(), 30.0);
+    assert_eq!(open_lots[0].acquired_timestamp_ms(), 6000);
+    assert!(pnl[&reversion.id()].open_lots().is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn tags_are_applied_in_bulk_by_query(
+    #[future] test_db_conn: Result<DbConnection>,
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let (a, s) = (brokerage_account.id(), security.id());
+    let executions = [
+        execution(a, s, 1000, TradeSide::Buy, 100.0, 10.0, 0.0),
+        execution(a, s, 2000, TradeSide::Sell, 100.0, 12.0, 0.0),
+        execution(a, s, 3000, TradeSide::Buy, 50.0, 11.0, 0.0),
+    ];
+    for execution in &executions {
+        execution.insert(&dbc.db, None).await?;
+    }
+
+    let momentum = Tag::new(TagKind::Strategy, "momentum");
+    momentum.insert(&dbc.db, None).await?;
+    assert!(
+        Tag::new(TagKind::Label, "momentum")
+            .insert(&dbc.db, None)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        Tag::find_by_name(&dbc.db, "momentum", None).await?,
+        Some(momentum.clone())
+    );
+
+    let query = ExecutionQuery::new(a).security_id(s).range(1000, 2000);
+    assert_eq!(momentum.tag_executions(&dbc.db, &query, None).await?, 2);
+    assert_eq!(momentum.tag_executions(&dbc.db, &query, None).await?, 0);
+    let tagged_executions = momentum.executions(&dbc.db, None).await?;
+    assert_eq!(tagged_executions.len(), 2);
+    assert!(tagged_executions.iter().all(|e| e.has_tag(momentum.id())));
+
+    let pnl = TagPnl::for_account_and_range(&dbc.db, a, 0, i64::MAX, None).await?;
+    assert!((pnl[&momentum.id()].net_pnl() - 200.0).abs() < 1e-9);
+    assert!(pnl[&momentum.id()].open_lots().is_empty());
+
+    let buys = ExecutionQuery::new(a).side(TradeSide::Buy);
+    assert_eq!(momentum.untag_executions(&dbc.db, &buys, None).await?, 1);
+    assert_eq!(
+        TradeExecution::find_by_tag_id(&dbc.db, momentum.id(), None)
+            .await?
+            .len(),
+        1
+    );
+
+    Ok(())
+}
+
+fn option(underlying: &Security, right: OptionRight, strike: f64) -> Security {
+    Security::new_option(
+        &format!("{} {:?} {}", underlying.ticker(), right, strike),
+        "CBOE",
+        None,
+        OptionContract {
+            underlying_id: underlying.id(),
+            right,
+            strike,
+            expiration_timestamp_ms: 30 * DAY_MS,
+            multiplier: 100.0,
+        },
+    )
+}
+
+#[rstest]
+fn option_events_close_options_and_roll_premium_into_stock(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let a = brokerage_account.id();
+    let call = option(&security, OptionRight::Call, 150.0);
+    let put = option(&security, OptionRight::Put, 140.0);
+    let mut executions = vec![
+        execution(a, call.id(), 0, TradeSide::Buy, 2.0, 300.0, 2.0),
+        execution(a, put.id(), 0, TradeSide::Sell, 1.0, 200.0, 0.0),
+    ];
+    let mut record = |option: &Security, kind, contracts, timestamp_ms| {
+        let report = LotReport::from_executions(&executions)?;
+        let (event, implied) = OptionEvent::from_open_lots(
+            a,
+            option,
+            kind,
+            contracts,
+            timestamp_ms,
+            report.open_lots(),
+        )?;
+        executions.extend(implied.iter().cloned());
+        anyhow::Ok((event, implied))
+    };
+
+    // Exercising a long call buys the stock at the strike plus the premium.
+    let (event, implied) = record(&call, OptionEventKind::Exercise, 1.0, DAY_MS)?;
+    assert_eq!(event.stock_execution_id(), Some(implied[1].id()));
+    assert_eq!(*implied[0].side(), TradeSide::Sell);
+    assert_eq!(implied[0].price(), 301.0);
+    assert_eq!(*implied[1].side(), TradeSide::Buy);
+    assert_eq!(implied[1].quantity(), 100.0);
+    assert!((implied[1].price() - 153.01).abs() < 1e-9);
+
+    // An assigned short put buys the stock at the strike less the premium.
+    let (_, implied) = record(&put, OptionEventKind::Assignment, 1.0, DAY_MS)?;
+    assert_eq!(*implied[0].side(), TradeSide::Buy);
+    assert_eq!(*implied[1].side(), TradeSide::Buy);
+    assert_eq!(implied[1].price(), 138.0);
+
+    assert!(record(&call, OptionEventKind::Assignment, 1.0, 2 * DAY_MS).is_err());
+    assert!(record(&call, OptionEventKind::Exercise, 2.0, 2 * DAY_MS).is_err());
+    assert!(record(&security, OptionEventKind::Expiration, 1.0, 2 * DAY_MS).is_err());
+    assert!(record(&call, OptionEventKind::Exercise, 0.0, 2 * DAY_MS).is_err());
+    assert!(record(&call, OptionEventKind::Expiration, -1.0, 2 * DAY_MS).is_err());
+
+    // The remaining call expires worthless and realizes its premium.
+    let (event, implied) = record(&call, OptionEventKind::Expiration, 1.0, 30 * DAY_MS)?;
+    assert_eq!(event.stock_execution_id(), None);
+    assert_eq!(implied.len(), 1);
+
+    let report = LotReport::from_executions(&executions)?;
+    let option_gains: Vec<f64> = report
+        .closed_lots()
+        .iter()
+        .map(|lot| lot.gain_loss())
+        .collect();
+    assert_eq!(option_gains, vec![0.0, 0.0, -301.0]);
+    assert_eq!(report.net_quantity(a, call.id()), 0.0);
+    assert_eq!(report.net_quantity(a, put.id()), 0.0);
+    assert_eq!(report.net_quantity(a, security.id()), 200.0);
+    let stock_basis: f64 = report.open_lots().iter().map(|lot| lot.cost_basis()).sum();
+    assert!((stock_basis - 15_301.0 - 13_800.0).abs() < 1e-9);
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn recorded_option_events_insert_their_executions(
+    #[future] test_db_conn: Result<DbConnection>,
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let a = brokerage_account.id();
+    let call = option(&security, OptionRight::Call, 150.0);
+    call.insert(&dbc.db, None).await?;
+    execution(a, call.id(), 0, TradeSide::Buy, 1.0, 300.0, 0.0)
+        .insert(&dbc.db, None)
+        .await?;
+
+    let (event, implied) = OptionEvent::record(
+        &dbc.db,
+        a,
+        &call,
+        OptionEventKind::Exercise,
+        1.0,
+        DAY_MS,
+        None,
+    )
+    .await?;
+    assert_eq!(
+        OptionEvent::find_by_account_id_and_range(&dbc.db, a, 0, DAY_MS, None).await?,
+        vec![event]
+    );
+    let mut inserted: Vec<bson::oid::ObjectId> =
+        TradeExecution::find_by_account_id_and_range(&dbc.db, a, DAY_MS, DAY_MS, None)
+            .await?
+            .iter()
+            .map(TradeExecution::id)
+            .collect();
+    inserted.sort();
+    let mut expected: Vec<bson::oid::ObjectId> = implied.iter().map(TradeExecution::id).collect();
+    expected.sort();
+    assert_eq!(inserted, expected);
+    assert_eq!(
+        Security::find_by_id(&dbc.db, call.id(), None).await?,
+        Some(call)
+    );
+
+    Ok(())
+}
+
+/// 2025-01-01T00:00:00Z.
+const JAN_1_2025_MS: i64 = 1_735_689_600_000;
+
+fn dividend(
+    brokerage_account: &BrokerageAccount,
+    security: &Security,
+    pay_timestamp_ms: i64,
+    classification: DividendClassification,
+) -> dividend::Builder {
+    Dividend::builder()
+        .brokerage_account_id(brokerage_account.id())
+        .security_id(security.id())
+        .ex_timestamp_ms(pay_timestamp_ms - 14 * DAY_MS)
+        .pay_timestamp_ms(pay_timestamp_ms)
+        .amount_per_share(0.25)
+        .quantity(100.0)
+        .classification(classification)
+}
+
+#[rstest]
+fn dividend_income_report_classifies_and_tracks_reinvestment(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let msft = Security::new(SecurityType::Stock, "MSFT", "NASDAQ", None);
+    let dividends = [
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS - DAY_MS,
+            DividendClassification::Qualified,
+        )
+        .build()?,
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS + 40 * DAY_MS,
+            DividendClassification::Qualified,
+        )
+        .withholding_tax(3.75)
+        .reinvestment_execution_id(bson::oid::ObjectId::new())
+        .build()?,
+        dividend(
+            &brokerage_account,
+            &msft,
+            JAN_1_2025_MS + 90 * DAY_MS,
+            DividendClassification::NonQualified,
+        )
+        .build()?,
+    ];
+    assert_eq!(dividends[1].net_amount(), 21.25);
+
+    let report = DividendIncomeReport::from_dividends(2025, &dividends);
+    let totals = report.totals();
+    assert_eq!(totals.qualified, 25.0);
+    assert_eq!(totals.non_qualified, 25.0);
+    assert_eq!(totals.gross(), 50.0);
+    assert_eq!(totals.withholding_tax, 3.75);
+    assert_eq!(totals.reinvested, 21.25);
+    assert_eq!(report.by_security()[&msft.id()].non_qualified, 25.0);
+
+    assert!(
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS,
+            DividendClassification::Qualified
+        )
+        .ex_timestamp_ms(JAN_1_2025_MS + DAY_MS)
+        .build()
+        .is_err()
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn dividends_link_reinvestment_purchases(
+    #[future] test_db_conn: Result<DbConnection>,
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let pay_ms = JAN_1_2025_MS + 40 * DAY_MS;
+    let mut paid = dividend(
+        &brokerage_account,
+        &security,
+        pay_ms,
+        DividendClassification::Qualified,
+    )
+    .build()?;
+    paid.insert(&dbc.db, None).await?;
+
+    let (a, s) = (brokerage_account.id(), security.id());
+    let purchase = execution(a, s, pay_ms, TradeSide::Buy, 0.125, 200.0, 0.0);
+    purchase.insert(&dbc.db, None).await?;
+    let sale = execution(a, s, pay_ms + 1, TradeSide::Sell, 0.125, 200.0, 0.0);
+    assert!(paid.reinvest(&dbc.db, &sale, None).await.is_err());
+    paid.reinvest(&dbc.db, &purchase, None).await?;
+
+    assert_eq!(
+        Dividend::find_by_id(&dbc.db, paid.id(), None).await?,
+        Some(paid.clone())
+    );
+    assert_eq!(paid.reinvestment(&dbc.db, None).await?, Some(purchase));
+    assert_eq!(
+        Dividend::find_by_security_id(&dbc.db, s, None).await?,
+        vec![paid]
+    );
+
+    let report = DividendIncomeReport::for_accounts(&dbc.db, &[a], 2025, None).await?;
+    assert_eq!(report.totals().reinvested, 25.0);
+    assert!(
+        DividendIncomeReport::for_accounts(&dbc.db, &[a], 2024, None)
+            .await?
+            .by_security()
+            .is_empty()
+    );
+
+    Ok(())
+}
+
+fn interest(
+    brokerage_account: &BrokerageAccount,
+    timestamp_ms: i64,
+    amount: f64,
+    withholding: Withholding,
+) -> Result<CashItem> {
+    CashItem::builder()
+        .brokerage_account_id(brokerage_account.id())
+        .kind(CashItemKind::Interest)
+        .amount(amount)
+        .timestamp_ms(timestamp_ms)
+        .withholding(withholding)
+        .build()
+}
+
+#[rstest]
+fn foreign_withholding_report_totals_credits_and_reclaims_per_country(
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let dividends = [
+        // 30% withheld where the treaty rate is 15%.
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS + 40 * DAY_MS,
+            DividendClassification::Qualified,
+        )
+        .foreign_withholding(Withholding::new("CH", 7.5).with_reclaimable_amount(3.75))
+        .build()?,
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS + 130 * DAY_MS,
+            DividendClassification::Qualified,
+        )
+        .foreign_withholding(Withholding {
+            reclaim_status: ReclaimStatus::Refunded,
+            ..Withholding::new("CH", 7.5).with_reclaimable_amount(3.75)
+        })
+        .build()?,
+        // Paid in the previous tax year.
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS - DAY_MS,
+            DividendClassification::Qualified,
+        )
+        .foreign_withholding(Withholding::new("CH", 7.5))
+        .build()?,
+        // Domestic.
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS + 50 * DAY_MS,
+            DividendClassification::Qualified,
+        )
+        .build()?,
+    ];
+    assert_eq!(dividends[0].withholding_tax(), 7.5);
+    assert_eq!(dividends[0].net_amount(), 17.5);
+
+    let cash_items = [interest(
+        &brokerage_account,
+        JAN_1_2025_MS + 60 * DAY_MS,
+        85.0,
+        Withholding::new("DE", 15.0),
+    )?];
+    assert_eq!(cash_items[0].gross_amount(), 100.0);
+
+    let report = ForeignWithholdingReport::from_items(2025, &dividends, &cash_items);
+    let ch = report.by_country()["CH"];
+    assert_eq!(ch.income, 50.0);
+    assert_eq!(ch.withheld, 15.0);
+    assert_eq!(ch.reclaimable, 7.5);
+    assert_eq!(ch.reclaimed, 3.75);
+    assert_eq!(ch.creditable, 7.5);
+    assert_eq!(report.by_country()["DE"].creditable, 15.0);
+    assert_eq!(report.totals().withheld, 30.0);
+    assert_eq!(report.totals().creditable, 22.5);
+    assert_eq!(report.outstanding_reclaims(), 3.75);
+
+    assert!(
+        dividend(
+            &brokerage_account,
+            &security,
+            JAN_1_2025_MS,
+            DividendClassification::Qualified
+        )
+        .withholding_tax(5.0)
+        .foreign_withholding(Withholding::new("CH", 7.5))
+        .build()
+        .is_err()
+    );
+    assert!(
+        interest(
+            &brokerage_account,
+            JAN_1_2025_MS,
+            85.0,
+            Withholding::new("DE", 15.0).with_reclaimable_amount(20.0),
+        )
+        .is_err()
+    );
+    assert!(
+        interest(
+            &brokerage_account,
+            JAN_1_2025_MS,
+            85.0,
+            Withholding {
+                reclaim_status: ReclaimStatus::Refunded,
+                ..Withholding::new("DE", 15.0)
+            },
+        )
+        .is_err()
+    );
+
+    Ok(())
+}
+
+#[rstest]
+#[awt]
+#[traced_test]
+#[tokio::test]
+async fn foreign_withholding_reclaims_are_tracked(
+    #[future] test_db_conn: Result<DbConnection>,
+    brokerage_account: BrokerageAccount,
+    security: Security,
+) -> Result<()> {
+    let dbc = test_db_conn?;
+    let pay_ms = JAN_1_2025_MS + 40 * DAY_MS;
+    let mut paid = dividend(
+        &brokerage_account,
+        &security,
+        pay_ms,
+        DividendClassification::Qualified,
+    )
+    .foreign_withholding(Withholding::new("CH", 7.5).with_reclaimable_amount(3.75))
+    .build()?;
+    paid.insert(&dbc.db, None).await?;
+    let mut received = interest(
+        &brokerage_account,
+        pay_ms,
+        85.0,
+        Withholding::new("DE", 15.0),
+    )?;
+    received.insert(&dbc.db, None).await?;
+
+    paid.update_reclaim_status(&dbc.db, ReclaimStatus::Filed, None)
+        .await?;
+    assert_eq!(
+        Dividend::find_by_id(&dbc.db, paid.id(), None).await?,
+        Some(paid.clone())
+    );
+    assert_eq!(
+        paid.foreign_withholding().map(|w| w.reclaim_status),
+        Some(ReclaimStatus::Filed)
+    );
+    assert!(
+        paid.update_reclaim_status(&dbc.db, ReclaimStatus::NotApplicable, None)
+            .await
+            .is_err()
+    );
+    received
+        .update_reclaim_status(&dbc.db, ReclaimStatus::NotApplicable, None)
+        .await?;
+    assert!(
+        received
+            .update_reclaim_status(&dbc.db, ReclaimStatus::Refunded, None)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        CashItem::find_by_id(&dbc.db, received.id(), None).await?,
+        Some(received)
+    );
+
+    let mut domestic = dividend(
+        &brokerage_account,
+        &security,
+        pay_ms,
+        DividendClassification::Qualified,
+    )
+    .build()?;
+    assert!(
+        domestic
+            .update_reclaim_status(&dbc.db, ReclaimStatus::Filed, None)
+            .await
+            .is_err()
+    );
+
+    EODSummary::builder()
+        .brokerage_account_id(brokerage_account.id())
+        .start_timestamp_ms(pay_ms)
+        .end_timestamp_ms(pay_ms + DAY_MS - 1)
+        .starting_cash(0.0)
+        .ending_cash(102.5)
+        .commissions(0.0)
+        .deposits(0.0)
+        .dividends(17.5)
+        .interest(85.0)
+        .net_trade_purchases(0.0)
+        .net_trade_sales(0.0)
+        .other_fees(0.0)
+        .withdrawals(0.0)
+        .build()?
+        .insert(&dbc.db, None)
+        .await?;
+
+    let report =
+        ForeignWithholdingReport::for_accounts(&dbc.db, &[brokerage_account.id()], 2025, None)
+            .await?;
+    assert_eq!(report.totals().withheld, 22.5);
+    assert_eq!(report.outstanding_reclaims(), 3.75);
+    assert_eq!(report.reported_dividends(), 17.5);
+    assert_eq!(report.reported_interest(), 85.0);
+
+    Ok(())
+}